@@ -14,8 +14,8 @@ const BENCH_BUFFER_TIME: SampleCalc = BENCH_BUFFER_SIZE as SampleCalc / BENCH_SA
 
 #[bench]
 fn math_sin(bencher: &mut Bencher) {
-    let mut rad: f32 = 0.0;
-    let mut s: f32 = 0.0;
+    let mut rad: SampleCalc = 0.0;
+    let mut s: SampleCalc = 0.0;
 
     bencher.iter(|| {
         rad += 0.001;
@@ -24,6 +24,94 @@ fn math_sin(bencher: &mut Bencher) {
     });
 }
 
+#[bench]
+fn math_cos_fast(bencher: &mut Bencher) {
+    let mut rad: SampleCalc = 0.0;
+    let mut c: SampleCalc = 0.0;
+
+    bencher.iter(|| {
+        rad += 0.001;
+        c = rad.cos_fast();
+        test::black_box(c);
+    });
+}
+
+#[bench]
+fn math_sincos_fast(bencher: &mut Bencher) {
+    let mut rad: SampleCalc = 0.0;
+    let mut sc: (SampleCalc, SampleCalc) = (0.0, 0.0);
+
+    bencher.iter(|| {
+        rad += 0.001;
+        sc = rad.sincos_fast();
+        test::black_box(sc);
+    });
+}
+
+#[bench]
+fn math_exp(bencher: &mut Bencher) {
+    let mut x: SampleCalc = -10.0;
+    let mut e: SampleCalc = 0.0;
+
+    bencher.iter(|| {
+        x += 0.0001;
+        e = x.exp();
+        test::black_box(e);
+    });
+}
+
+#[bench]
+fn math_exp_fast(bencher: &mut Bencher) {
+    let mut x: SampleCalc = -10.0;
+    let mut e: SampleCalc = 0.0;
+
+    bencher.iter(|| {
+        x += 0.0001;
+        e = x.exp_fast();
+        test::black_box(e);
+    });
+}
+
+// Wave, with a separate summing loop
+#[bench]
+fn wave_get_sum(bencher: &mut Bencher) {
+    let frequency_buffer: Vec<SampleCalc> = vec![440.0; BENCH_BUFFER_SIZE];
+    let mut wave_buffer: Vec<SampleCalc> = vec![0.0; BENCH_BUFFER_SIZE];
+    let mut result: Vec<SampleCalc> = vec![0.0; BENCH_BUFFER_SIZE];
+    let mut wave = Wave::new(BENCH_SAMPLE_RATE, 0).unwrap();
+
+    bencher.iter(|| {
+        wave.get(&frequency_buffer, &mut wave_buffer).unwrap();
+        for (item, wave) in result.iter_mut().zip(wave_buffer.iter()) {
+            *item += *wave;
+        }
+    });
+}
+
+// Wave, accumulating into the result
+#[bench]
+fn wave_get_add(bencher: &mut Bencher) {
+    let frequency_buffer: Vec<SampleCalc> = vec![440.0; BENCH_BUFFER_SIZE];
+    let mut result: Vec<SampleCalc> = vec![0.0; BENCH_BUFFER_SIZE];
+    let mut wave = Wave::new(BENCH_SAMPLE_RATE, 0).unwrap();
+
+    bencher.iter(|| {
+        wave.get_add(&frequency_buffer, &mut result).unwrap();
+    });
+}
+
+// WaveTableSine, accumulating into the result
+#[bench]
+fn wave_table_sine_get_add(bencher: &mut Bencher) {
+    let frequency_buffer: Vec<SampleCalc> = vec![440.0; BENCH_BUFFER_SIZE];
+    let mut result: Vec<SampleCalc> = vec![0.0; BENCH_BUFFER_SIZE];
+    let mut wave = WaveTableSine::new(BENCH_SAMPLE_RATE, 0).unwrap();
+
+    bencher.iter(|| {
+        wave.get_add(&frequency_buffer, &mut result).unwrap();
+    });
+}
+
 // FrequencyConst
 #[bench]
 fn freqconst(bencher: &mut Bencher) {
@@ -73,6 +161,36 @@ fn tremolo(bencher: &mut Bencher) {
     });
 }
 
+// multiplication with a gain curve, sample by sample
+#[bench]
+fn gain_curve_loop(bencher: &mut Bencher) {
+    let mut amplitude_buffer: Vec<SampleCalc> = vec![1.0; BENCH_BUFFER_SIZE];
+    let curve: Vec<SampleCalc> = (0..BENCH_BUFFER_SIZE)
+        .map(|index| index as SampleCalc / BENCH_BUFFER_SIZE as SampleCalc)
+        .collect();
+
+    bencher.iter(|| {
+        for (index, item) in amplitude_buffer.iter_mut().enumerate() {
+            *item *= curve[index];
+        }
+        test::black_box(&mut amplitude_buffer);
+    });
+}
+
+// multiplication with a gain curve, by apply_gain_curve()
+#[bench]
+fn gain_curve_apply(bencher: &mut Bencher) {
+    let mut amplitude_buffer: Vec<SampleCalc> = vec![1.0; BENCH_BUFFER_SIZE];
+    let curve: Vec<SampleCalc> = (0..BENCH_BUFFER_SIZE)
+        .map(|index| index as SampleCalc / BENCH_BUFFER_SIZE as SampleCalc)
+        .collect();
+
+    bencher.iter(|| {
+        apply_gain_curve(&mut amplitude_buffer, &curve).unwrap();
+        test::black_box(&mut amplitude_buffer);
+    });
+}
+
 // AmplitudeConstOvertones
 #[bench]
 fn ampconst_overtone(bencher: &mut Bencher) {
@@ -143,6 +261,41 @@ fn timbre_freqconst_ampdec_overtones16(bencher: &mut Bencher) {
     });
 }
 
+// FrequencyConst, Timbre{ AmplitudeDecayExpOvertones with 16 overtones }, high fundamental:
+// most of the overtones are above the Nyquist frequency
+#[bench]
+fn timbre_freqconst_high_ampdec_overtones16(bencher: &mut Bencher) {
+    let mut generator_buffer: Vec<SampleCalc> = vec![0.0; BENCH_BUFFER_SIZE];
+    let mut frequency_buffer: Vec<SampleCalc> = vec![14000.0; BENCH_BUFFER_SIZE];
+    let mut time: SampleCalc = 0.0;
+    let frequency = FrequencyConst::new(14000.0).unwrap();
+    let amplitude = {
+        let overtones_amplitude: Vec<SampleCalc> = vec![
+            10.0, 1.0, 1.0, 0.95, 0.9, 0.9, 0.86, 0.83, 0.80, 0.78, 0.76, 0.74, 0.73, 0.72, 0.71,
+            0.70,
+        ];
+        let overtones_dec_rate: Vec<SampleCalc> = vec![
+            1.0, 0.6, 0.5, 0.4, 0.35, 0.3, 0.28, 0.26, 0.25, 0.24, 0.23, 0.22, 0.21, 0.1, 0.2, 0.2,
+        ];
+        AmplitudeDecayExpOvertones::new(
+            BENCH_SAMPLE_RATE,
+            15,
+            &overtones_amplitude,
+            &overtones_dec_rate,
+        )
+        .unwrap()
+    };
+    let timbre = Timbre::new(BENCH_SAMPLE_RATE, BENCH_BUFFER_SIZE, Rc::new(amplitude), 16).unwrap();
+
+    bencher.iter(|| {
+        frequency.get(time, None, &mut frequency_buffer).unwrap();
+        timbre
+            .get(&frequency_buffer, &mut generator_buffer)
+            .unwrap();
+        time += BENCH_BUFFER_TIME;
+    });
+}
+
 // FrequencyConst, Timbre{ AmplitudeDecayExpOvertones with 4 overtones }
 #[bench]
 fn timbre_freqconst_ampdec_overtones4(bencher: &mut Bencher) {
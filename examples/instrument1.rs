@@ -4,7 +4,9 @@
 //!
 //! The keys from <kbd>Q</kbd> to <kbd>O</kbd> changes the frequency to be higher,
 //! the keys from <kbd>A</kbd> to <kbd>L</kbd> changes the frequency to be lower.
-/// Other keys play the previous frequency. To quit press <kbd>Esc</kbd>.
+//! Other keys play the previous frequency. The tone sounds while the key is held, after the
+//! release it fades out. <kbd>Up</kbd> and <kbd>Down</kbd> changes the velocity (loudness and
+//! brightness) of the notes. To quit press <kbd>Esc</kbd>.
 use scaleless_music;
 
 use piston_window;
@@ -13,12 +15,25 @@ use piston_window::*;
 use scaleless_music::sound::*;
 use std::rc::Rc;
 
+/// The number of overtones of the tone.
+const OVERTONE_COUNT: usize = 4;
+
 /// Commands of the messages from the UI thread to the playback thread.
 pub enum GeneratorCommand {
     /// Mute
     Mute,
-    /// Keyboard event
-    Keypress { key: keyboard::Key },
+    /// Keyboard event, the velocity is in the (0.0, 1.0] range
+    Keypress {
+        key: keyboard::Key,
+        velocity: SampleCalc,
+    },
+    /// A key is pressed, the note starts. The velocity is in the (0.0, 1.0] range.
+    NoteOn {
+        key: keyboard::Key,
+        velocity: SampleCalc,
+    },
+    /// A key is released, the note fades out
+    NoteOff { key: keyboard::Key },
     /// Multiply frequency by a rational number
     FrequencyMultiple { numerator: u16, denominator: u16 },
 }
@@ -26,16 +41,25 @@ pub enum GeneratorCommand {
 pub struct InstrumentBasic {
     sample_rate: SampleCalc,
     timbre1: Timbre,
+    amplitude1: Rc<AmplitudeDecayExpOvertones>,
     frequency1: FrequencyConst,
     frequency1_buffer: Vec<SampleCalc>,
-    time: SampleCalc,
+    key_map: KeyMap<keyboard::Key>,
+    envelope: AmplitudeADSR,
+    /// The key of the sounding note.
+    key_held: Option<keyboard::Key>,
+    /// Number of samples generated since the last restart.
+    frame_count: usize,
 }
 
 impl InstrumentBasic {
-    /// Custom constructor
-    pub fn new(sample_rate: SampleCalc) -> SoundResult<InstrumentBasic> {
+    /// Custom constructor. The keys are mapped to frequency changes by the `key_map`.
+    pub fn new(
+        sample_rate: SampleCalc,
+        key_map: KeyMap<keyboard::Key>,
+    ) -> SoundResult<InstrumentBasic> {
         let frequency1 = FrequencyConst::new(220.0)?;
-        let amplitude = {
+        let amplitude1 = Rc::new({
             let overtones_amplitude: Vec<SampleCalc> = vec![
                 10.0, 1.0, 1.0, 0.95, 0.9, 0.9, 0.86, 0.83, 0.80, 0.78, 0.76, 0.74, 0.73, 0.72,
                 0.71, 0.70,
@@ -46,27 +70,82 @@ impl InstrumentBasic {
             ];
             AmplitudeDecayExpOvertones::new(
                 sample_rate,
-                4,
+                OVERTONE_COUNT,
                 &overtones_amplitude,
                 &overtones_half_life,
             )?
-        };
-        let timbre1 = Timbre::new(sample_rate, BUFFER_SIZE_DEFAULT, Rc::new(amplitude), 4)?;
+        });
+        let timbre1 = Timbre::new(
+            sample_rate,
+            BUFFER_SIZE_DEFAULT,
+            amplitude1.clone(),
+            OVERTONE_COUNT,
+        )?;
+        let envelope = AmplitudeADSR::new_with_time(sample_rate, 0.01, 0.1, 0.7, 0.3)?;
+        envelope.note_off(); // silent until the first key press
         Ok(InstrumentBasic {
             sample_rate: sample_rate,
             timbre1: timbre1,
+            amplitude1: amplitude1,
             frequency1: frequency1,
             frequency1_buffer: vec![1.0; BUFFER_SIZE_DEFAULT],
-            time: 0.0,
+            key_map: key_map,
+            envelope: envelope,
+            key_held: None,
+            frame_count: 0,
         })
     }
 
+    /// Changes the frequency according to the key. Unmapped keys play the previous frequency.
+    fn change_frequency_by_key(&mut self, key: keyboard::Key) -> SoundResult<()> {
+        let interval = self.key_map.get(&key).unwrap_or(INTERVAL_UNISON);
+        self.change_interval(interval)
+    }
+
+    /// Starts a new note, the envelope continues from its actual amplitude (legato).
+    fn note_on(&mut self, key: keyboard::Key, velocity: SampleCalc) -> SoundResult<()> {
+        if self.key_held == Some(key) {
+            return Ok(()); // repeated key event
+        }
+        self.change_frequency_by_key(key)?;
+        self.envelope
+            .set_amplitude_start(self.envelope.get_amplitude())?;
+        self.key_held = Some(key);
+        self.apply_velocity(velocity)
+    }
+
+    /// Scales the starting amplitudes of the tone by the velocity. The higher overtones are
+    /// scaled more, so softer notes are less bright.
+    fn apply_velocity(&mut self, velocity: SampleCalc) -> SoundResult<()> {
+        // fixed size, as it is called from the audio thread
+        let mut amplitudes: [SampleCalc; OVERTONE_COUNT + 1] = [0.0; OVERTONE_COUNT + 1];
+        self.amplitude1.get_amplitudes(&mut amplitudes)?;
+        let mut overtone_velocity = velocity;
+        for amplitude in amplitudes.iter_mut() {
+            *amplitude *= overtone_velocity;
+            overtone_velocity *= velocity;
+        }
+        self.amplitude1.set_amplitudes_start(&amplitudes)
+    }
+
+    /// Releases the note, if it belongs to the key.
+    fn note_off(&mut self, key: keyboard::Key) {
+        if self.key_held == Some(key) {
+            self.envelope.note_off();
+            self.key_held = None;
+        }
+    }
+
     /// Change frequency in harmony with the previous value
     #[allow(dead_code)]
     pub fn change_frequency(&mut self, numerator: u16, denominator: u16) -> SoundResult<()> {
-        let interval = Interval::new(numerator, denominator)?;
+        self.change_interval(Interval::new(numerator, denominator)?)
+    }
+
+    /// Change frequency by the interval
+    fn change_interval(&mut self, interval: Interval) -> SoundResult<()> {
         self.frequency1.change(interval)?;
-        self.time = 0.0;
+        self.frame_count = 0;
         self.timbre1.restart();
         println!("{}  {}", interval, interval.get_name());
         Ok(())
@@ -76,43 +155,56 @@ impl InstrumentBasic {
 // TODO: making thread-safe as many components as possible.
 unsafe impl Send for InstrumentBasic {} // this is a temporary ugly workaround for the SDL2 backend
 
-// TODO: -unwrap()
 impl SoundGenerator for InstrumentBasic {
     type Command = GeneratorCommand;
 
-    fn get_samples(&mut self, sample_count: usize, result: &mut Vec<SampleCalc>) {
+    fn get_samples(
+        &mut self,
+        sample_count: usize,
+        result: &mut Vec<SampleCalc>,
+    ) -> SoundResult<()> {
         self.frequency1
-            .get(self.time, None, &mut self.frequency1_buffer)
-            .unwrap();
-        self.timbre1.get(&self.frequency1_buffer, result).unwrap();
-        self.time += sample_count as SampleCalc / self.sample_rate;
+            .get(self.get_time(), None, &mut self.frequency1_buffer)?;
+        self.timbre1.get(&self.frequency1_buffer, result)?;
+        match self.envelope.apply(result) {
+            Ok(()) => {}
+            Err(Error::ItemsCompleted(completed)) => {
+                for item in result.iter_mut().skip(completed) {
+                    *item = 0.0;
+                }
+            }
+            Err(e) => return Err(e),
+        }
+        self.frame_count += sample_count;
+        Ok(())
+    }
+
+    fn get_time(&self) -> SampleCalc {
+        self.frame_count as SampleCalc / self.sample_rate
+    }
+
+    fn reset(&mut self) {
+        self.frame_count = 0;
+        self.timbre1.restart();
     }
 
     fn process_command(&mut self, command: GeneratorCommand) {
         match command {
-            GeneratorCommand::Keypress { key } => {
-                let _ = match key {
-                    Key::Q => self.change_frequency(7, 6),
-                    Key::W => self.change_frequency(6, 5),
-                    Key::E => self.change_frequency(5, 4),
-                    Key::R => self.change_frequency(4, 3),
-                    Key::T => self.change_frequency(7, 5),
-                    Key::Y => self.change_frequency(3, 2),
-                    Key::U => self.change_frequency(5, 3),
-                    Key::I => self.change_frequency(7, 4),
-                    Key::O => self.change_frequency(2, 1),
-                    // Key::P => self.change_frequency(1, 1),
-                    Key::A => self.change_frequency(6, 7),
-                    Key::S => self.change_frequency(5, 6),
-                    Key::D => self.change_frequency(4, 5),
-                    Key::F => self.change_frequency(3, 4),
-                    Key::G => self.change_frequency(5, 7),
-                    Key::H => self.change_frequency(2, 3),
-                    Key::J => self.change_frequency(3, 5),
-                    Key::K => self.change_frequency(4, 7),
-                    Key::L => self.change_frequency(1, 2),
-                    _ => self.change_frequency(1, 1),
-                };
+            GeneratorCommand::Keypress { key, velocity } => {
+                let result = self
+                    .change_frequency_by_key(key)
+                    .and_then(|_| self.apply_velocity(velocity));
+                if let Err(e) = result {
+                    println!("{}", e);
+                }
+            }
+            GeneratorCommand::NoteOn { key, velocity } => {
+                if let Err(e) = self.note_on(key, velocity) {
+                    println!("{}", e);
+                }
+            }
+            GeneratorCommand::NoteOff { key } => {
+                self.note_off(key);
             }
             GeneratorCommand::Mute => {
                 let _ = self.change_frequency(1, 1);
@@ -129,8 +221,30 @@ impl SoundGenerator for InstrumentBasic {
 
 fn main() {
     println!("scaleless_music v{} example", env!("CARGO_PKG_VERSION"));
+    let key_map = KeyMap::from_ratios(&[
+        (Key::Q, 7, 6),
+        (Key::W, 6, 5),
+        (Key::E, 5, 4),
+        (Key::R, 4, 3),
+        (Key::T, 7, 5),
+        (Key::Y, 3, 2),
+        (Key::U, 5, 3),
+        (Key::I, 7, 4),
+        (Key::O, 2, 1),
+        (Key::A, 6, 7),
+        (Key::S, 5, 6),
+        (Key::D, 4, 5),
+        (Key::F, 3, 4),
+        (Key::G, 5, 7),
+        (Key::H, 2, 3),
+        (Key::J, 3, 5),
+        (Key::K, 4, 7),
+        (Key::L, 1, 2),
+    ])
+    .expect("KeyMap construction shouldn't fail.");
     let sound_generator = Box::new(
-        InstrumentBasic::new(48000.0).expect("InstrumentBasic construction shouldn't fail."),
+        InstrumentBasic::new(48000.0, key_map)
+            .expect("InstrumentBasic construction shouldn't fail."),
     );
     let mut sound = SoundInterface::new(48000, BUFFER_SIZE_DEFAULT, 2, sound_generator)
         .expect("SoundInterface construction shouldn't fail.");
@@ -144,17 +258,37 @@ fn main() {
     sound.start().expect("sound.start() shouldn't fail.");
     println!("\n\nThe keys from [Q] to [O] changes the frequency to be higher,");
     println!("the keys from [A] to [L] changes the frequency to be lower.");
-    println!("Other keys play the previous frequency. To quit press [Esc].");
+    println!("Other keys play the previous frequency.");
+    println!("[Up] and [Down] changes the velocity of the notes. To quit press [Esc].");
+    let mut velocity: SampleCalc = 1.0;
     while let Some(event) = window.next() {
         if let Some(button) = event.press_args() {
             if let Button::Keyboard(key) = button {
-                sound
-                    .send_command(GeneratorCommand::Keypress { key: key })
-                    .expect("send_command failed.");
+                match key {
+                    Key::Up | Key::Down => {
+                        velocity = if key == Key::Up {
+                            (velocity + 0.1).min(1.0)
+                        } else {
+                            (velocity - 0.1).max(0.1)
+                        };
+                        println!("Velocity: {:.1}", velocity);
+                    }
+                    _ => sound
+                        .send_command(GeneratorCommand::NoteOn {
+                            key: key,
+                            velocity: velocity,
+                        })
+                        .expect("send_command failed."),
+                }
             } else {
                 println!("Pressed {:?}", button);
             }
         }
+        if let Some(Button::Keyboard(key)) = event.release_args() {
+            sound
+                .send_command(GeneratorCommand::NoteOff { key: key })
+                .expect("send_command failed.");
+        }
         window.draw_2d(&event, |_c, g, _| {
             clear([1.0, 1.0, 1.0, 1.0], g);
         });
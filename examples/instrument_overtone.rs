@@ -10,29 +10,60 @@ use piston_window::*;
 use scaleless_music::sound::*;
 use std::rc::Rc;
 
+/// The number of overtones of the timbres.
+const OVERTONE_COUNT: usize = 4;
+
 /// Commands of the messages from the UI thread to the playback thread.
 pub enum GeneratorCommand {
     /// Mute
     Mute,
-    /// Keyboard event
-    Keypress { key: keyboard::Key },
+    /// Keyboard event, the velocity is in the (0.0, 1.0] range
+    Keypress {
+        key: keyboard::Key,
+        velocity: SampleCalc,
+    },
+    /// A key is pressed, the note starts. The velocity is in the (0.0, 1.0] range.
+    NoteOn {
+        key: keyboard::Key,
+        velocity: SampleCalc,
+    },
+    /// A key is released, the note fades out
+    NoteOff { key: keyboard::Key },
     /// Multiply frequency by a rational number
     FrequencyMultiple { numerator: u16, denominator: u16 },
+    /// Set the relative volume of a mixer channel
+    SetChannelVolume { channel: usize, volume: SampleCalc },
+    /// Set the interval of a mixer channel, relative to the base frequency
+    SetChannelInterval {
+        channel: usize,
+        numerator: u16,
+        denominator: u16,
+    },
 }
 
 pub struct InstrumentBasic {
     sample_rate: SampleCalc,
     frequency1: Rc<FrequencyConst>,
     frequency1_buffer: Vec<SampleCalc>,
+    key_map: KeyMap<keyboard::Key>,
+    amplitude1: Rc<AmplitudeDecayExpOvertones>,
     mixer: Rc<Mixer>,
-    time: SampleCalc,
+    envelope: AmplitudeADSR,
+    /// The key of the sounding note.
+    key_held: Option<keyboard::Key>,
+    /// Number of samples generated since the last restart.
+    frame_count: usize,
 }
 
 impl InstrumentBasic {
-    /// Custom constructor
-    pub fn new(sample_rate: SampleCalc) -> SoundResult<InstrumentBasic> {
+    /// Custom constructor. The keys are mapped to the frequency ratios of the resonances by the
+    /// `key_map`.
+    pub fn new(
+        sample_rate: SampleCalc,
+        key_map: KeyMap<keyboard::Key>,
+    ) -> SoundResult<InstrumentBasic> {
         let frequency1 = Rc::new(FrequencyConst::new(110.0)?);
-        let amplitude = {
+        let amplitude1 = Rc::new({
             let overtones_amplitude: Vec<SampleCalc> = vec![
                 10.0, 1.0, 1.0, 0.95, 0.9, 0.9, 0.86, 0.83, 0.80, 0.78, 0.76, 0.74, 0.73, 0.72,
                 0.71, 0.70,
@@ -43,91 +74,149 @@ impl InstrumentBasic {
             ];
             AmplitudeDecayExpOvertones::new(
                 sample_rate,
-                4,
+                OVERTONE_COUNT,
                 &overtones_amplitude,
                 &overtones_half_life,
             )?
-        };
+        });
         let timbre1 = Rc::new(Timbre::new(
             sample_rate,
             BUFFER_SIZE_DEFAULT,
-            Rc::new(amplitude),
-            4,
+            amplitude1.clone(),
+            OVERTONE_COUNT,
         )?);
         let amplitude = {
             let overtones_amplitude: Vec<SampleCalc> = vec![
                 1.0, 0.1, 0.1, 0.1, 0.2, 0.5, 0.1, 0.1, 0.1, 0.1, 0.1, 0.1, 0.1, 0.1, 0.1, 0.1,
                 0.1, 0.1, 0.1, 0.1,
             ];
-            AmplitudeConstOvertones::new(sample_rate, 4, &overtones_amplitude)?
+            AmplitudeConstOvertones::new(sample_rate, OVERTONE_COUNT, &overtones_amplitude)?
         };
         let timbre2 = Rc::new(Timbre::new(
             sample_rate,
             BUFFER_SIZE_DEFAULT,
             Rc::new(amplitude),
-            4,
+            OVERTONE_COUNT,
         )?);
         let mixer = Rc::new(Mixer::new(sample_rate, BUFFER_SIZE_DEFAULT)?);
         mixer.add(Interval::new(1, 1)?, timbre1, 4.0)?;
         mixer.add(Interval::new(1, 1)?, timbre2, 1.0)?;
+        let envelope = AmplitudeADSR::new_with_time(sample_rate, 0.01, 0.1, 0.7, 0.3)?;
+        envelope.note_off(); // silent until the first key press
 
         Ok(InstrumentBasic {
             sample_rate: sample_rate,
             frequency1: frequency1,
             frequency1_buffer: vec![1.0; BUFFER_SIZE_DEFAULT],
+            key_map: key_map,
+            amplitude1: amplitude1,
             mixer: mixer,
-            time: 0.0,
+            envelope: envelope,
+            key_held: None,
+            frame_count: 0,
         })
     }
 
     /// Change frequency in harmony with the previous value
     #[allow(dead_code)]
     pub fn change_frequency(&mut self, numerator: u16, denominator: u16) -> SoundResult<()> {
-        let interval = Interval::new(numerator, denominator)?;
+        self.change_interval(Interval::new(numerator, denominator)?)
+    }
+
+    /// Change frequency by the interval
+    fn change_interval(&mut self, interval: Interval) -> SoundResult<()> {
         self.mixer.set_interval(0, interval)?;
-        self.time = 0.0;
+        self.frame_count = 0;
         self.mixer.restart();
         println!("{}", interval);
         Ok(())
     }
+
+    /// Starts a new note, the envelope continues from its actual amplitude (legato). Unmapped
+    /// keys play the base frequency.
+    fn note_on(&mut self, key: keyboard::Key, velocity: SampleCalc) -> SoundResult<()> {
+        if self.key_held == Some(key) {
+            return Ok(()); // repeated key event
+        }
+        self.change_interval(self.key_map.get(&key).unwrap_or(INTERVAL_UNISON))?;
+        self.envelope
+            .set_amplitude_start(self.envelope.get_amplitude())?;
+        self.key_held = Some(key);
+        self.apply_velocity(velocity)
+    }
+
+    /// Releases the note, if it belongs to the key.
+    fn note_off(&mut self, key: keyboard::Key) {
+        if self.key_held == Some(key) {
+            self.envelope.note_off();
+            self.key_held = None;
+        }
+    }
+
+    /// Scales the starting amplitudes of the first timbre by the velocity. The higher overtones
+    /// are scaled more, so softer notes are less bright.
+    fn apply_velocity(&mut self, velocity: SampleCalc) -> SoundResult<()> {
+        // fixed size, as it is called from the audio thread
+        let mut amplitudes: [SampleCalc; OVERTONE_COUNT + 1] = [0.0; OVERTONE_COUNT + 1];
+        self.amplitude1.get_amplitudes(&mut amplitudes)?;
+        let mut overtone_velocity = velocity;
+        for amplitude in amplitudes.iter_mut() {
+            *amplitude *= overtone_velocity;
+            overtone_velocity *= velocity;
+        }
+        self.amplitude1.set_amplitudes_start(&amplitudes)
+    }
 }
-// TODO: -unwrap()
 impl SoundGenerator for InstrumentBasic {
     type Command = GeneratorCommand;
 
-    fn get_samples(&mut self, sample_count: usize, result: &mut Vec<SampleCalc>) {
+    fn get_samples(
+        &mut self,
+        sample_count: usize,
+        result: &mut Vec<SampleCalc>,
+    ) -> SoundResult<()> {
         self.frequency1
-            .get(self.time, None, &mut self.frequency1_buffer)
-            .unwrap();
-        self.mixer.get(&self.frequency1_buffer, result).unwrap();
-        self.time += sample_count as SampleCalc / self.sample_rate;
+            .get(self.get_time(), None, &mut self.frequency1_buffer)?;
+        self.mixer.get(&self.frequency1_buffer, result)?;
+        match self.envelope.apply(result) {
+            Ok(()) => {}
+            Err(Error::ItemsCompleted(completed)) => {
+                for item in result.iter_mut().skip(completed) {
+                    *item = 0.0;
+                }
+            }
+            Err(e) => return Err(e),
+        }
+        self.frame_count += sample_count;
+        Ok(())
+    }
+
+    fn get_time(&self) -> SampleCalc {
+        self.frame_count as SampleCalc / self.sample_rate
+    }
+
+    fn reset(&mut self) {
+        self.frame_count = 0;
+        self.mixer.restart();
     }
 
     fn process_command(&mut self, command: GeneratorCommand) {
         match command {
-            GeneratorCommand::Keypress { key } => {
-                let _ = match key {
-                    Key::Q => self.change_frequency(1, 2),
-                    Key::W => self.change_frequency(3, 2),
-                    Key::E => self.change_frequency(5, 2),
-                    Key::R => self.change_frequency(7, 2),
-                    Key::T => self.change_frequency(9, 2),
-                    Key::Y => self.change_frequency(11, 2),
-                    Key::U => self.change_frequency(13, 2),
-                    Key::I => self.change_frequency(15, 2),
-                    Key::O => self.change_frequency(17, 2),
-                    Key::P => self.change_frequency(19, 2),
-                    Key::S => self.change_frequency(2, 1),
-                    Key::D => self.change_frequency(3, 1),
-                    Key::F => self.change_frequency(4, 1),
-                    Key::G => self.change_frequency(5, 1),
-                    Key::H => self.change_frequency(6, 1),
-                    Key::J => self.change_frequency(7, 1),
-                    Key::K => self.change_frequency(8, 1),
-                    Key::L => self.change_frequency(9, 1),
-                    Key::A | _ => self.change_frequency(1, 1),
-                    // _ => self.change_frequency(1, 1),
-                };
+            GeneratorCommand::Keypress { key, velocity } => {
+                // unmapped keys play the base frequency
+                let interval = self.key_map.get(&key).unwrap_or(INTERVAL_UNISON);
+                let result = self.change_interval(interval);
+                if let Err(e) = result.and_then(|_| self.apply_velocity(velocity)) {
+                    println!("{}", e);
+                }
+            }
+            GeneratorCommand::NoteOn { key, velocity } => {
+                if let Err(e) = self.note_on(key, velocity) {
+                    println!("{}", e);
+                }
+            }
+            GeneratorCommand::NoteOff { key } => {
+                self.note_off(key);
             }
             GeneratorCommand::Mute => {
                 let _ = self.change_frequency(1, 1);
@@ -138,6 +227,22 @@ impl SoundGenerator for InstrumentBasic {
             } => {
                 let _ = self.change_frequency(numerator, denominator);
             }
+            GeneratorCommand::SetChannelVolume { channel, volume } => {
+                if let Err(e) = self.mixer.set_volume(channel, volume) {
+                    println!("{}", e);
+                }
+            }
+            GeneratorCommand::SetChannelInterval {
+                channel,
+                numerator,
+                denominator,
+            } => {
+                let result = Interval::new(numerator, denominator)
+                    .and_then(|interval| self.mixer.set_interval(channel, interval));
+                if let Err(e) = result {
+                    println!("{}", e);
+                }
+            }
         }
     }
 }
@@ -150,8 +255,31 @@ fn main() {
         "scaleless_music v{} example: overtone instrument\n",
         env!("CARGO_PKG_VERSION")
     );
+    let key_map = KeyMap::from_ratios(&[
+        (Key::Q, 1, 2),
+        (Key::W, 3, 2),
+        (Key::E, 5, 2),
+        (Key::R, 7, 2),
+        (Key::T, 9, 2),
+        (Key::Y, 11, 2),
+        (Key::U, 13, 2),
+        (Key::I, 15, 2),
+        (Key::O, 17, 2),
+        (Key::P, 19, 2),
+        (Key::A, 1, 1),
+        (Key::S, 2, 1),
+        (Key::D, 3, 1),
+        (Key::F, 4, 1),
+        (Key::G, 5, 1),
+        (Key::H, 6, 1),
+        (Key::J, 7, 1),
+        (Key::K, 8, 1),
+        (Key::L, 9, 1),
+    ])
+    .expect("KeyMap construction shouldn't fail.");
     let sound_generator = Box::new(
-        InstrumentBasic::new(48000.0).expect("InstrumentBasic construction shouldn't fail."),
+        InstrumentBasic::new(48000.0, key_map)
+            .expect("InstrumentBasic construction shouldn't fail."),
     );
     let mut sound = SoundInterface::new(48000, BUFFER_SIZE_DEFAULT, 2, sound_generator)
         .expect("SoundInterface construction shouldn't fail.");
@@ -165,19 +293,132 @@ fn main() {
     sound.start().expect("sound.start() shouldn't fail.");
     println!("\n\nThe keys from [Q] to [P] produces half wave resonances,");
     println!("the keys from [A] to [L] makes full wave resonances.");
+    println!("The tone sounds while the key is held, after the release it fades out.");
+    println!("[Up] and [Down] changes the volume of the second timbre.");
     println!("To quit press [Esc].");
+    let mut timbre2_volume: SampleCalc = 1.0;
     while let Some(event) = window.next() {
         if let Some(button) = event.press_args() {
             if let Button::Keyboard(key) = button {
-                sound
-                    .send_command(GeneratorCommand::Keypress { key: key })
-                    .expect("send_command failed.");
+                let command = match key {
+                    Key::Up | Key::Down => {
+                        timbre2_volume = if key == Key::Up {
+                            timbre2_volume + 0.5
+                        } else {
+                            (timbre2_volume - 0.5).max(0.0)
+                        };
+                        println!("Volume of the second timbre: {}", timbre2_volume);
+                        GeneratorCommand::SetChannelVolume {
+                            channel: 1,
+                            volume: timbre2_volume,
+                        }
+                    }
+                    _ => GeneratorCommand::NoteOn {
+                        key: key,
+                        velocity: 1.0,
+                    },
+                };
+                sound.send_command(command).expect("send_command failed.");
             } else {
                 println!("Pressed {:?}", button);
             }
         }
+        if let Some(Button::Keyboard(key)) = event.release_args() {
+            sound
+                .send_command(GeneratorCommand::NoteOff { key: key })
+                .expect("send_command failed.");
+        }
         window.draw_2d(&event, |_c, g, _| {
             clear([1.0, 1.0, 1.0, 1.0], g);
         });
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Renders `buffer_count` buffers through the null backend, in mono.
+    fn render(
+        sound: &mut NullSoundInterface<GeneratorCommand>,
+        buffer_count: usize,
+    ) -> Vec<SampleCalc> {
+        let mut output = vec![0.0; BUFFER_SIZE_DEFAULT * buffer_count];
+        sound.render(&mut output);
+        output.iter().map(|item| SampleCalc::from(*item)).collect()
+    }
+
+    #[test]
+    fn set_channel_volume_changes_the_level_of_the_channel() {
+        let key_map = KeyMap::from_ratios(&[(Key::A, 1, 1)]).unwrap();
+        let instrument = InstrumentBasic::new(48000.0, key_map).unwrap();
+        let mut sound = NullSoundInterface::new_with_fade_in(
+            48000,
+            BUFFER_SIZE_DEFAULT,
+            1,
+            0.0,
+            Box::new(instrument),
+        )
+        .unwrap();
+        sound.start().unwrap();
+        sound
+            .send_command(GeneratorCommand::NoteOn {
+                key: Key::A,
+                velocity: 1.0,
+            })
+            .unwrap();
+        // only the constant second timbre is audible
+        sound
+            .send_command(GeneratorCommand::SetChannelVolume {
+                channel: 0,
+                volume: 0.0,
+            })
+            .unwrap();
+        // the commands are applied one per buffer, then the envelope reaches the sustain level
+        let _ = render(&mut sound, 30);
+        let before = render(&mut sound, 10);
+        sound
+            .send_command(GeneratorCommand::SetChannelVolume {
+                channel: 1,
+                volume: 0.5,
+            })
+            .unwrap();
+        let after = render(&mut sound, 10);
+        let ratio = rms(&after) / rms(&before);
+        assert!((ratio - 0.5).abs() < 0.02, "{}", ratio);
+    }
+
+    #[test]
+    fn note_off_fades_out_the_note() {
+        let key_map = KeyMap::from_ratios(&[(Key::A, 1, 1)]).unwrap();
+        let instrument = InstrumentBasic::new(48000.0, key_map).unwrap();
+        let mut sound = NullSoundInterface::new_with_fade_in(
+            48000,
+            BUFFER_SIZE_DEFAULT,
+            1,
+            0.0,
+            Box::new(instrument),
+        )
+        .unwrap();
+        sound.start().unwrap();
+        assert_eq!(peak(&render(&mut sound, 1)), 0.0);
+        sound
+            .send_command(GeneratorCommand::NoteOn {
+                key: Key::A,
+                velocity: 1.0,
+            })
+            .unwrap();
+        assert!(peak(&render(&mut sound, 10)) > 0.1);
+        // the release of another key is ignored
+        sound
+            .send_command(GeneratorCommand::NoteOff { key: Key::S })
+            .unwrap();
+        assert!(peak(&render(&mut sound, 10)) > 0.1);
+        sound
+            .send_command(GeneratorCommand::NoteOff { key: Key::A })
+            .unwrap();
+        // the release takes 0.3 s
+        let _ = render(&mut sound, 30);
+        assert_eq!(peak(&render(&mut sound, 1)), 0.0);
+    }
+}
@@ -0,0 +1,146 @@
+//! This example is a metering tap: it plays a decaying tone, and prints the peak level of each
+//! buffer (in dBFS) during the playback.
+use scaleless_music;
+
+use scaleless_music::sound::*;
+use std::rc::Rc;
+use std::sync::mpsc::{Receiver, SyncSender};
+use std::time::{Duration, Instant};
+
+/// The number of overtones of the timbre.
+const OVERTONE_COUNT: usize = 4;
+/// The length of the playback (in seconds).
+const PLAYBACK_DURATION: u64 = 6;
+
+/// Commands of the messages from the main thread to the playback thread.
+pub enum GeneratorCommand {
+    /// Start the tone again
+    Restart,
+}
+
+/// The measured levels of a buffer.
+pub struct Levels {
+    peak: SampleCalc,
+    rms: SampleCalc,
+}
+
+pub struct MeteredTone {
+    sample_rate: SampleCalc,
+    frequency_buffer: Vec<SampleCalc>,
+    timbre: Rc<Timbre>,
+    /// The levels are sent to the main thread, the bounded queue doesn't allocate.
+    level_sender: SyncSender<Levels>,
+    /// Number of samples generated since the last restart.
+    frame_count: usize,
+}
+
+impl MeteredTone {
+    /// Custom constructor.
+    pub fn new(
+        sample_rate: SampleCalc,
+        frequency: SampleCalc,
+        level_sender: SyncSender<Levels>,
+    ) -> SoundResult<MeteredTone> {
+        let amplitude = {
+            let overtones_amplitude: Vec<SampleCalc> = vec![1.0, 0.4, 0.3, 0.2, 0.1];
+            let overtones_half_life: Vec<SampleCalc> = vec![0.5, 0.3, 0.2, 0.1, 0.05];
+            AmplitudeDecayExpOvertones::new(
+                sample_rate,
+                OVERTONE_COUNT,
+                &overtones_amplitude,
+                &overtones_half_life,
+            )?
+        };
+        let timbre = Rc::new(Timbre::new(
+            sample_rate,
+            BUFFER_SIZE_DEFAULT,
+            Rc::new(amplitude),
+            OVERTONE_COUNT,
+        )?);
+        Ok(MeteredTone {
+            sample_rate,
+            frequency_buffer: vec![frequency; BUFFER_SIZE_DEFAULT],
+            timbre,
+            level_sender,
+            frame_count: 0,
+        })
+    }
+}
+
+impl SoundGenerator for MeteredTone {
+    type Command = GeneratorCommand;
+
+    fn get_samples(
+        &mut self,
+        sample_count: usize,
+        result: &mut Vec<SampleCalc>,
+    ) -> SoundResult<()> {
+        self.timbre.get(
+            &self.frequency_buffer[..sample_count],
+            &mut result[..sample_count],
+        )?;
+        // the measurement doesn't allocate, it can be done in the audio thread
+        let levels = Levels {
+            peak: peak(&result[..sample_count]),
+            rms: rms(&result[..sample_count]),
+        };
+        let _ = self.level_sender.try_send(levels);
+        self.frame_count += sample_count;
+        Ok(())
+    }
+
+    fn get_time(&self) -> SampleCalc {
+        self.frame_count as SampleCalc / self.sample_rate
+    }
+
+    fn reset(&mut self) {
+        self.frame_count = 0;
+        self.timbre.restart();
+    }
+
+    fn process_command(&mut self, command: GeneratorCommand) {
+        match command {
+            GeneratorCommand::Restart => self.reset(),
+        }
+    }
+}
+
+// TODO: making thread-safe as many components as possible.
+unsafe impl Send for MeteredTone {} // this is a temporary ugly workaround for the SDL2 backend
+
+/// Prints the received levels, until the end of the playback.
+fn print_levels(level_receiver: &Receiver<Levels>, end: Instant) {
+    while Instant::now() < end {
+        if let Ok(levels) = level_receiver.recv_timeout(Duration::from_millis(100)) {
+            let clip = if levels.peak > 1.0 { " CLIP" } else { "" };
+            println!(
+                "peak: {:7.1} dBFS   rms: {:7.1} dBFS{}",
+                to_db(levels.peak),
+                to_db(levels.rms),
+                clip
+            );
+        }
+    }
+}
+
+fn main() {
+    println!(
+        "scaleless_music v{} example: metering tap\n",
+        env!("CARGO_PKG_VERSION")
+    );
+    let (level_sender, level_receiver) = ::std::sync::mpsc::sync_channel(64);
+    let sound_generator = Box::new(
+        MeteredTone::new(48000.0, 220.0, level_sender)
+            .expect("MeteredTone construction shouldn't fail."),
+    );
+    let mut sound = SoundInterface::new(48000, BUFFER_SIZE_DEFAULT, 2, sound_generator)
+        .expect("SoundInterface construction shouldn't fail.");
+    sound.start().expect("sound.start() shouldn't fail.");
+
+    let half = Duration::from_secs(PLAYBACK_DURATION / 2);
+    print_levels(&level_receiver, Instant::now() + half);
+    sound
+        .send_command(GeneratorCommand::Restart)
+        .expect("send_command() shouldn't fail.");
+    print_levels(&level_receiver, Instant::now() + half);
+}
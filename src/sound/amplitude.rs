@@ -1,5 +1,5 @@
 use crate::sound::*;
-use std::cell::Cell;
+use std::cell::{Cell, RefCell};
 use std::rc::Rc;
 
 /// Provides time dependent amlitude changes.
@@ -37,6 +37,17 @@ pub fn is_valid_amplitude(amplitude: SampleCalc) -> SoundResult<()> {
     Ok(())
 }
 
+/// Multiplies each sample with the corresponding gain value of a precomputed `curve`.
+pub fn apply_gain_curve(samples: &mut [SampleCalc], curve: &[SampleCalc]) -> SoundResult<()> {
+    if samples.len() != curve.len() {
+        return Err(Error::BufferSize);
+    }
+    for (item, gain) in samples.iter_mut().zip(curve) {
+        *item *= *gain;
+    }
+    Ok(())
+}
+
 /// Constant amplitude.
 #[derive(Debug, Clone)]
 pub struct AmplitudeConst {
@@ -139,6 +150,8 @@ pub struct FadeLinear {
     progress: ProgressOption,
     amplitude_start: Cell<SampleCalc>,
     amplitude_end: SampleCalc,
+    /// The precomputed amplitudes for the actual buffer (of at most `BUFFER_SIZE_DEFAULT` size).
+    gain_buffer: RefCell<Vec<SampleCalc>>,
 }
 
 impl FadeLinear {
@@ -151,6 +164,7 @@ impl FadeLinear {
             progress,
             amplitude_start: Cell::new(amplitude_start),
             amplitude_end,
+            gain_buffer: RefCell::new(vec![0.0; BUFFER_SIZE_DEFAULT]),
         })
     }
 
@@ -174,22 +188,41 @@ impl FadeLinear {
         let progress = ProgressTempo::new(sample_rate, note_value)?;
         Self::new(ProgressOption::Tempo(progress), amplitude_end)
     }
+
+    /// Sets the shape of the fade (the default is linear), and restarts it. E.g.
+    /// `Easing::CubicInOut` gives a smooth, S-shaped fade.
+    pub fn set_easing(&self, easing: Easing) {
+        self.progress.set_easing(easing);
+    }
 }
 
 impl AmplitudeProvider for FadeLinear {
     fn apply(&self, samples: &mut [SampleCalc]) -> SoundResult<()> {
+        let mut gain_buffer_reserved = self.gain_buffer.borrow_mut();
+        if samples.len() > gain_buffer_reserved.len() {
+            return Err(Error::BufferSize);
+        }
+        let gain_buffer = &mut gain_buffer_reserved[..samples.len()];
+        let mut completed = samples.len();
         match self.progress {
             ProgressOption::Time(ref p) => {
-                for (index, item) in samples.iter_mut().enumerate() {
+                for (index, item) in gain_buffer.iter_mut().enumerate() {
                     match p.next_by_time() {
-                        Ok(phase) => *item *= phase,
-                        Err(Error::ProgressCompleted) => return Err(Error::ItemsCompleted(index)),
+                        Ok(phase) => *item = phase,
+                        Err(Error::ProgressCompleted) => {
+                            completed = index;
+                            break;
+                        }
                         Err(e) => return Err(e),
                     }
                 }
             }
             ProgressOption::Tempo(ref _p) => return Err(Error::ProgressInvalid),
         }
+        apply_gain_curve(&mut samples[..completed], &gain_buffer[..completed])?;
+        if completed < samples.len() {
+            return Err(Error::ItemsCompleted(completed));
+        }
         Ok(())
     }
 
@@ -197,18 +230,33 @@ impl AmplitudeProvider for FadeLinear {
         if tempo.len() != samples.len() {
             return Err(Error::BufferSize);
         }
+        let mut gain_buffer_reserved = self.gain_buffer.borrow_mut();
+        if samples.len() > gain_buffer_reserved.len() {
+            return Err(Error::BufferSize);
+        }
+        let gain_buffer = &mut gain_buffer_reserved[..samples.len()];
+        let mut completed = samples.len();
         match self.progress {
             ProgressOption::Tempo(ref p) => {
-                for ((index, item), beats_per_second) in samples.iter_mut().enumerate().zip(tempo) {
+                for ((index, item), beats_per_second) in
+                    gain_buffer.iter_mut().enumerate().zip(tempo)
+                {
                     match p.next_by_tempo(*beats_per_second) {
-                        Ok(phase) => *item *= phase,
-                        Err(Error::ProgressCompleted) => return Err(Error::ItemsCompleted(index)),
+                        Ok(phase) => *item = phase,
+                        Err(Error::ProgressCompleted) => {
+                            completed = index;
+                            break;
+                        }
                         Err(e) => return Err(e),
                     }
                 }
             }
             ProgressOption::Time(ref _p) => return Err(Error::ProgressInvalid),
         }
+        apply_gain_curve(&mut samples[..completed], &gain_buffer[..completed])?;
+        if completed < samples.len() {
+            return Err(Error::ItemsCompleted(completed));
+        }
         Ok(())
     }
 }
@@ -252,6 +300,421 @@ impl AmplitudeJoinable for FadeLinear {
     }
 }
 
+/// Exponentially changing amplitude. It sounds smoother than `FadeLinear`, because loudness
+/// perception is roughly logarithmic. Amplitudes below `FADE_EXPONENTIAL_AMPLITUDE_MIN` are
+/// clamped to it.
+#[derive(Debug, Clone)]
+pub struct FadeExponential {
+    /// Tempo or time based progress, from 0.0 to 1.0.
+    progress: ProgressOption,
+    amplitude_start: Cell<SampleCalc>,
+    amplitude_end: SampleCalc,
+    amplitude: Cell<SampleCalc>,
+    /// The precomputed amplitudes for the actual buffer (of at most `BUFFER_SIZE_DEFAULT` size).
+    gain_buffer: RefCell<Vec<SampleCalc>>,
+}
+
+impl FadeExponential {
+    /// Custom constructor.
+    pub fn new(
+        progress: ProgressOption,
+        amplitude_end: SampleCalc,
+    ) -> SoundResult<FadeExponential> {
+        is_valid_amplitude(amplitude_end)?;
+        progress.set_period_unit(1.0);
+        Ok(FadeExponential {
+            progress,
+            amplitude_start: Cell::new(FADE_EXPONENTIAL_AMPLITUDE_MIN),
+            amplitude_end: amplitude_end.max(FADE_EXPONENTIAL_AMPLITUDE_MIN),
+            amplitude: Cell::new(FADE_EXPONENTIAL_AMPLITUDE_MIN),
+            gain_buffer: RefCell::new(vec![0.0; BUFFER_SIZE_DEFAULT]),
+        })
+    }
+
+    /// Custom constructor with time based progress.
+    pub fn new_with_time(
+        sample_rate: SampleCalc,
+        duration: SampleCalc,
+        amplitude_end: SampleCalc,
+    ) -> SoundResult<FadeExponential> {
+        let progress = ProgressTime::new(sample_rate, duration)?;
+        Self::new(ProgressOption::Time(progress), amplitude_end)
+    }
+
+    /// Constructor with tempo based progress.
+    /// `note_value` is the tempo relative fade duration.
+    pub fn new_with_tempo(
+        sample_rate: SampleCalc,
+        note_value: NoteValue,
+        amplitude_end: SampleCalc,
+    ) -> SoundResult<FadeExponential> {
+        let progress = ProgressTempo::new(sample_rate, note_value)?;
+        Self::new(ProgressOption::Tempo(progress), amplitude_end)
+    }
+
+    /// Calculates the amplitude from the phase of the progress.
+    fn get_amplitude_at(&self, phase: SampleCalc) -> SampleCalc {
+        let amplitude_start = self.amplitude_start.get();
+        amplitude_start * (self.amplitude_end / amplitude_start).powf(phase)
+    }
+}
+
+impl AmplitudeProvider for FadeExponential {
+    fn apply(&self, samples: &mut [SampleCalc]) -> SoundResult<()> {
+        let mut gain_buffer_reserved = self.gain_buffer.borrow_mut();
+        if samples.len() > gain_buffer_reserved.len() {
+            return Err(Error::BufferSize);
+        }
+        let gain_buffer = &mut gain_buffer_reserved[..samples.len()];
+        let mut completed = samples.len();
+        match self.progress {
+            ProgressOption::Time(ref p) => {
+                for (index, item) in gain_buffer.iter_mut().enumerate() {
+                    match p.next_by_time() {
+                        Ok(phase) => *item = self.get_amplitude_at(phase),
+                        Err(Error::ProgressCompleted) => {
+                            completed = index;
+                            break;
+                        }
+                        Err(e) => return Err(e),
+                    }
+                }
+            }
+            ProgressOption::Tempo(ref _p) => return Err(Error::ProgressInvalid),
+        }
+        if completed > 0 {
+            self.amplitude.set(gain_buffer[completed - 1]);
+        }
+        apply_gain_curve(&mut samples[..completed], &gain_buffer[..completed])?;
+        if completed < samples.len() {
+            return Err(Error::ItemsCompleted(completed));
+        }
+        Ok(())
+    }
+
+    fn apply_rhythmic(&self, tempo: &[SampleCalc], samples: &mut [SampleCalc]) -> SoundResult<()> {
+        if tempo.len() != samples.len() {
+            return Err(Error::BufferSize);
+        }
+        let mut gain_buffer_reserved = self.gain_buffer.borrow_mut();
+        if samples.len() > gain_buffer_reserved.len() {
+            return Err(Error::BufferSize);
+        }
+        let gain_buffer = &mut gain_buffer_reserved[..samples.len()];
+        let mut completed = samples.len();
+        match self.progress {
+            ProgressOption::Tempo(ref p) => {
+                for ((index, item), beats_per_second) in
+                    gain_buffer.iter_mut().enumerate().zip(tempo)
+                {
+                    match p.next_by_tempo(*beats_per_second) {
+                        Ok(phase) => *item = self.get_amplitude_at(phase),
+                        Err(Error::ProgressCompleted) => {
+                            completed = index;
+                            break;
+                        }
+                        Err(e) => return Err(e),
+                    }
+                }
+            }
+            ProgressOption::Time(ref _p) => return Err(Error::ProgressInvalid),
+        }
+        if completed > 0 {
+            self.amplitude.set(gain_buffer[completed - 1]);
+        }
+        apply_gain_curve(&mut samples[..completed], &gain_buffer[..completed])?;
+        if completed < samples.len() {
+            return Err(Error::ItemsCompleted(completed));
+        }
+        Ok(())
+    }
+}
+
+impl HasTimer for FadeExponential {
+    fn set_timing(&self, timing: TimingOption) -> SoundResult<()> {
+        self.progress.set_timing(timing)?;
+        self.restart();
+        Ok(())
+    }
+
+    fn get_timing(&self) -> TimingOption {
+        self.progress.get_timing()
+    }
+
+    fn restart(&self) {
+        self.progress.restart();
+        self.amplitude.set(self.amplitude_start.get());
+    }
+
+    fn apply_parent_timing(&self, parent_timing: TimingOption) -> SoundResult<()> {
+        self.progress.apply_parent_timing(parent_timing)
+    }
+}
+
+impl AmplitudeJoinable for FadeExponential {
+    fn set_amplitude_start(&self, amplitude: SampleCalc) -> SoundResult<()> {
+        is_valid_amplitude(amplitude)?;
+        self.amplitude_start
+            .set(amplitude.max(FADE_EXPONENTIAL_AMPLITUDE_MIN));
+        self.restart();
+        Ok(())
+    }
+
+    fn get_amplitude(&self) -> SampleCalc {
+        self.amplitude.get()
+    }
+
+    fn get_max(&self) -> SampleCalc {
+        self.amplitude_start.get().max(self.amplitude_end)
+    }
+}
+
+/// The actual segment of the `AmplitudeADSR` envelope.
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum AdsrStage {
+    Attack,
+    Decay,
+    Sustain,
+    Release,
+    Finished,
+}
+
+/// [ADSR envelope](https://en.wikipedia.org/wiki/Envelope_(music)#ADSR): attack, decay,
+/// sustain, release. The release starts on `note_off()`, or when the timing (the duration of the
+/// held note) ends.
+#[derive(Debug, Clone)]
+pub struct AmplitudeADSR {
+    /// Measures the duration of the held note (until the release).
+    timer: Timer,
+    attack: ProgressOption,
+    decay: ProgressOption,
+    sustain: SampleCalc,
+    release: ProgressOption,
+    stage: Cell<AdsrStage>,
+    amplitude_start: Cell<SampleCalc>,
+    amplitude: Cell<SampleCalc>,
+    /// The precomputed amplitudes for the actual buffer (of at most `BUFFER_SIZE_DEFAULT` size).
+    gain_buffer: RefCell<Vec<SampleCalc>>,
+}
+
+impl AmplitudeADSR {
+    /// Custom constructor. The attack rises to amplitude 1.0, the decay falls to the `sustain`
+    /// level.
+    pub fn new(
+        sample_rate: SampleCalc,
+        attack: ProgressOption,
+        decay: ProgressOption,
+        sustain: SampleCalc,
+        release: ProgressOption,
+    ) -> SoundResult<AmplitudeADSR> {
+        is_valid_amplitude(sustain)?;
+        decay.set_phase_init(1.0);
+        decay.set_period_unit(sustain - 1.0);
+        let adsr = AmplitudeADSR {
+            timer: Timer::new(sample_rate)?,
+            attack,
+            decay,
+            sustain,
+            release,
+            stage: Cell::new(AdsrStage::Attack),
+            amplitude_start: Cell::new(0.0),
+            amplitude: Cell::new(0.0),
+            gain_buffer: RefCell::new(vec![0.0; BUFFER_SIZE_DEFAULT]),
+        };
+        adsr.restart();
+        Ok(adsr)
+    }
+
+    /// Custom constructor with time based progress. Durations are given in seconds.
+    pub fn new_with_time(
+        sample_rate: SampleCalc,
+        attack: SampleCalc,
+        decay: SampleCalc,
+        sustain: SampleCalc,
+        release: SampleCalc,
+    ) -> SoundResult<AmplitudeADSR> {
+        Self::new(
+            sample_rate,
+            ProgressOption::Time(ProgressTime::new(sample_rate, attack)?),
+            ProgressOption::Time(ProgressTime::new(sample_rate, decay)?),
+            sustain,
+            ProgressOption::Time(ProgressTime::new(sample_rate, release)?),
+        )
+    }
+
+    /// Constructor with tempo based progress. Durations are given as note values.
+    pub fn new_with_tempo(
+        sample_rate: SampleCalc,
+        attack: NoteValue,
+        decay: NoteValue,
+        sustain: SampleCalc,
+        release: NoteValue,
+    ) -> SoundResult<AmplitudeADSR> {
+        Self::new(
+            sample_rate,
+            ProgressOption::Tempo(ProgressTempo::new(sample_rate, attack)?),
+            ProgressOption::Tempo(ProgressTempo::new(sample_rate, decay)?),
+            sustain,
+            ProgressOption::Tempo(ProgressTempo::new(sample_rate, release)?),
+        )
+    }
+
+    /// Starts the release segment, from the actual amplitude.
+    pub fn note_off(&self) {
+        match self.stage.get() {
+            AdsrStage::Release | AdsrStage::Finished => {}
+            _ => {
+                self.release.set_phase_init(self.amplitude.get());
+                self.release.set_period_unit(-self.amplitude.get());
+                self.stage.set(AdsrStage::Release);
+            }
+        }
+    }
+
+    /// Provides the next amplitude value. Tempo is given in beats per second (`None` for time
+    /// based progress).
+    fn next_amplitude(&self, tempo: Option<SampleCalc>) -> SoundResult<SampleCalc> {
+        if let AdsrStage::Attack | AdsrStage::Decay | AdsrStage::Sustain = self.stage.get() {
+            let hold_result = match tempo {
+                None => self.timer.next_by_time(),
+                Some(beats_per_second) => self.timer.next_by_tempo(beats_per_second),
+            };
+            match hold_result {
+                Ok(()) => {}
+                Err(Error::ProgressCompleted) => self.note_off(),
+                Err(e) => return Err(e),
+            }
+        }
+        loop {
+            let (progress, stage_next) = match self.stage.get() {
+                AdsrStage::Attack => (&self.attack, AdsrStage::Decay),
+                AdsrStage::Decay => (&self.decay, AdsrStage::Sustain),
+                AdsrStage::Sustain => {
+                    self.amplitude.set(self.sustain);
+                    return Ok(self.sustain);
+                }
+                AdsrStage::Release => (&self.release, AdsrStage::Finished),
+                AdsrStage::Finished => return Err(Error::ProgressCompleted),
+            };
+            let next_result = match tempo {
+                None => progress.next_by_time(),
+                Some(beats_per_second) => progress.next_by_tempo(beats_per_second),
+            };
+            match next_result {
+                Ok(amplitude) => {
+                    self.amplitude.set(amplitude);
+                    return Ok(amplitude);
+                }
+                Err(Error::ProgressCompleted) => {
+                    if stage_next == AdsrStage::Sustain {
+                        self.amplitude.set(self.sustain);
+                    }
+                    self.stage.set(stage_next);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+impl AmplitudeProvider for AmplitudeADSR {
+    fn apply(&self, samples: &mut [SampleCalc]) -> SoundResult<()> {
+        let mut gain_buffer_reserved = self.gain_buffer.borrow_mut();
+        if samples.len() > gain_buffer_reserved.len() {
+            return Err(Error::BufferSize);
+        }
+        let gain_buffer = &mut gain_buffer_reserved[..samples.len()];
+        let mut completed = samples.len();
+        for (index, item) in gain_buffer.iter_mut().enumerate() {
+            match self.next_amplitude(None) {
+                Ok(amplitude) => *item = amplitude,
+                Err(Error::ProgressCompleted) => {
+                    completed = index;
+                    break;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        apply_gain_curve(&mut samples[..completed], &gain_buffer[..completed])?;
+        if completed < samples.len() {
+            return Err(Error::ItemsCompleted(completed));
+        }
+        Ok(())
+    }
+
+    fn apply_rhythmic(&self, tempo: &[SampleCalc], samples: &mut [SampleCalc]) -> SoundResult<()> {
+        if tempo.len() != samples.len() {
+            return Err(Error::BufferSize);
+        }
+        let mut gain_buffer_reserved = self.gain_buffer.borrow_mut();
+        if samples.len() > gain_buffer_reserved.len() {
+            return Err(Error::BufferSize);
+        }
+        let gain_buffer = &mut gain_buffer_reserved[..samples.len()];
+        let mut completed = samples.len();
+        for ((index, item), beats_per_second) in gain_buffer.iter_mut().enumerate().zip(tempo) {
+            match self.next_amplitude(Some(*beats_per_second)) {
+                Ok(amplitude) => *item = amplitude,
+                Err(Error::ProgressCompleted) => {
+                    completed = index;
+                    break;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        apply_gain_curve(&mut samples[..completed], &gain_buffer[..completed])?;
+        if completed < samples.len() {
+            return Err(Error::ItemsCompleted(completed));
+        }
+        Ok(())
+    }
+}
+
+impl HasTimer for AmplitudeADSR {
+    fn set_timing(&self, timing: TimingOption) -> SoundResult<()> {
+        self.timer.set_timing(timing)?;
+        self.restart();
+        Ok(())
+    }
+
+    fn get_timing(&self) -> TimingOption {
+        self.timer.get_timing()
+    }
+
+    fn restart(&self) {
+        self.timer.restart();
+        self.stage.set(AdsrStage::Attack);
+        self.amplitude.set(self.amplitude_start.get());
+        self.attack.set_phase_init(self.amplitude_start.get());
+        self.attack
+            .set_period_unit(1.0 - self.amplitude_start.get());
+        self.decay.restart();
+    }
+
+    fn apply_parent_timing(&self, parent_timing: TimingOption) -> SoundResult<()> {
+        self.timer.apply_parent_timing(parent_timing)?;
+        self.restart();
+        Ok(())
+    }
+}
+
+impl AmplitudeJoinable for AmplitudeADSR {
+    fn set_amplitude_start(&self, amplitude: SampleCalc) -> SoundResult<()> {
+        is_valid_amplitude(amplitude)?;
+        self.amplitude_start.set(amplitude);
+        self.restart();
+        Ok(())
+    }
+
+    fn get_amplitude(&self) -> SampleCalc {
+        self.amplitude.get()
+    }
+
+    fn get_max(&self) -> SampleCalc {
+        1.0
+    }
+}
+
 /// Amplitude is decaying exponentially. The decay rate only depends on time, even when the
 /// duration is tempo dependent.
 /// [Exponential decay](https://en.wikipedia.org/wiki/Exponential_decay)
@@ -291,14 +754,11 @@ impl AmplitudeProvider for AmplitudeDecayExp {
                     self.amplitude.set(self.amplitude.get() * self.multiplier);
                     *item *= self.amplitude.get();
                 }
-                for item in samples.iter_mut() {
-                    self.amplitude.set(self.amplitude.get() * self.multiplier);
-                    *item *= self.amplitude.get();
-                }
             }
             Err(Error::ItemsCompleted(completed)) => {
                 for item in samples.iter_mut().take(completed) {
-                    *item = self.amplitude.get();
+                    self.amplitude.set(self.amplitude.get() * self.multiplier);
+                    *item *= self.amplitude.get();
                 }
             }
             Err(ref _e) => {}
@@ -376,6 +836,8 @@ pub struct Tremolo {
     extent_ratio: SampleCalc,
     /// The average amplitude. It is calculated in a way that the peak amplitude will be 1.0.
     amplitude_normalized: SampleCalc,
+    /// The last amplitude value.
+    amplitude: Cell<SampleCalc>,
 }
 
 impl Tremolo {
@@ -391,6 +853,7 @@ impl Tremolo {
             progress,
             extent_ratio,
             amplitude_normalized,
+            amplitude: Cell::new(amplitude_normalized),
         })
     }
 
@@ -426,8 +889,10 @@ impl AmplitudeProvider for Tremolo {
                 for (index, item) in samples.iter_mut().enumerate() {
                     match p.next_by_time() {
                         Ok(phase) => {
-                            *item *=
-                                self.amplitude_normalized * (self.extent_ratio.powf(phase.sin()))
+                            self.amplitude.set(
+                                self.amplitude_normalized * (self.extent_ratio.powf(phase.sin())),
+                            );
+                            *item *= self.amplitude.get();
                         }
                         Err(Error::ProgressCompleted) => return Err(Error::ItemsCompleted(index)),
                         Err(e) => return Err(e),
@@ -449,8 +914,10 @@ impl AmplitudeProvider for Tremolo {
                 for ((index, item), beats_per_second) in samples.iter_mut().enumerate().zip(tempo) {
                     match p.next_by_tempo(*beats_per_second) {
                         Ok(phase) => {
-                            *item *=
-                                self.amplitude_normalized * (self.extent_ratio.powf(phase.sin()))
+                            self.amplitude.set(
+                                self.amplitude_normalized * (self.extent_ratio.powf(phase.sin())),
+                            );
+                            *item *= self.amplitude.get();
                         }
                         Err(Error::ProgressCompleted) => return Err(Error::ItemsCompleted(index)),
                         Err(e) => return Err(e),
@@ -477,6 +944,7 @@ impl HasTimer for Tremolo {
 
     fn restart(&self) {
         self.progress.restart();
+        self.amplitude.set(self.amplitude_normalized);
     }
 
     fn apply_parent_timing(&self, parent_timing: TimingOption) -> SoundResult<()> {
@@ -484,6 +952,24 @@ impl HasTimer for Tremolo {
     }
 }
 
+impl AmplitudeJoinable for Tremolo {
+    /// The tremolo is periodic around its own average amplitude, so the starting amplitude is
+    /// only validated, and the progress is restarted.
+    fn set_amplitude_start(&self, amplitude: SampleCalc) -> SoundResult<()> {
+        is_valid_amplitude(amplitude)?;
+        self.restart();
+        Ok(())
+    }
+
+    fn get_amplitude(&self) -> SampleCalc {
+        self.amplitude.get()
+    }
+
+    fn get_max(&self) -> SampleCalc {
+        1.0
+    }
+}
+
 /// Sequence of several amplitude functions.
 #[derive(Clone)]
 pub struct AmplitudeSequence {
@@ -491,6 +977,8 @@ pub struct AmplitudeSequence {
     amp_funct_array: Vec<Rc<dyn AmplitudeJoinable>>,
     array_index: Cell<usize>,
     amplitude: Cell<SampleCalc>, // the last amplitude value
+    /// If it is true, the sequence restarts from the first item after the last one.
+    looping: Cell<bool>,
 }
 
 impl AmplitudeSequence {
@@ -502,6 +990,7 @@ impl AmplitudeSequence {
             amp_funct_array: v,
             array_index: Cell::new(0),
             amplitude: Cell::new(1.0),
+            looping: Cell::new(false),
         })
     }
 
@@ -509,6 +998,34 @@ impl AmplitudeSequence {
     pub fn add(&mut self, amplitude: Rc<dyn AmplitudeJoinable>) {
         self.amp_funct_array.push(amplitude);
     }
+
+    /// Turns looping on or off. When looping, the sequence continues with the first item after
+    /// the last one, until its own timing ends.
+    pub fn set_loop(&self, enabled: bool) {
+        self.looping.set(enabled);
+    }
+
+    /// Steps to the next amplitude function, which continues from the last amplitude.
+    /// Returns true if it wrapped around to the first item.
+    fn step_next(&self) -> SoundResult<bool> {
+        let mut array_index = self.array_index.get() + 1;
+        let wrapped = array_index >= self.amp_funct_array.len();
+        if wrapped {
+            if !self.looping.get() {
+                return Err(Error::ItemInvalid);
+            }
+            array_index = 0;
+        }
+        self.array_index.set(array_index);
+        let amp_funct_next = self
+            .amp_funct_array
+            .get(array_index)
+            .ok_or(Error::ItemInvalid)?;
+        amp_funct_next.restart();
+        amp_funct_next.set_amplitude_start(self.amplitude.get())?;
+        amp_funct_next.apply_parent_timing(self.timer.get_timing())?;
+        Ok(wrapped)
+    }
 }
 
 impl AmplitudeProvider for AmplitudeSequence {
@@ -524,6 +1041,7 @@ impl AmplitudeProvider for AmplitudeSequence {
             Err(_) => return timer_result,
         }
         let mut index_from: usize = 0;
+        let mut wrapped_at: Option<usize> = None;
         loop {
             let amp_funct_act = self
                 .amp_funct_array
@@ -536,19 +1054,14 @@ impl AmplitudeProvider for AmplitudeSequence {
                     return timer_result;
                 }
                 Err(Error::ItemsCompleted(completed)) => {
-                    index_from = completed;
-                    let array_index = self.array_index.get() + 1;
-                    if array_index >= self.amp_funct_array.len() {
-                        return Err(Error::ItemInvalid);
-                    } else {
-                        self.array_index.set(array_index);
-                        self.amplitude.set(amp_funct_act.get_amplitude());
-                        let amp_funct_next = self
-                            .amp_funct_array
-                            .get(array_index)
-                            .ok_or(Error::ItemInvalid)?;
-                        amp_funct_next.set_amplitude_start(self.amplitude.get())?;
-                        amp_funct_next.apply_parent_timing(self.timer.get_timing())?;
+                    index_from += completed;
+                    self.amplitude.set(amp_funct_act.get_amplitude());
+                    if self.step_next()? {
+                        // a whole loop without any progress would never end
+                        if wrapped_at == Some(index_from) {
+                            return Err(Error::ItemInvalid);
+                        }
+                        wrapped_at = Some(index_from);
                     }
                 }
                 Err(_) => return child_result,
@@ -564,38 +1077,36 @@ impl AmplitudeProvider for AmplitudeSequence {
             return Err(Error::SequenceEmpty);
         }
         let buffer: &mut [SampleCalc];
-        let timer_result = self.timer.jump_by_time(samples.len());
+        let timer_result = self.timer.jump_by_tempo(tempo);
         match timer_result {
             Ok(()) => buffer = samples,
             Err(Error::ItemsCompleted(completed)) => buffer = &mut samples[0..completed],
             Err(_) => return timer_result,
         }
+        let buffer_size = buffer.len();
         let mut index_from: usize = 0;
+        let mut wrapped_at: Option<usize> = None;
         loop {
             let amp_funct_act = self
                 .amp_funct_array
                 .get(self.array_index.get())
                 .ok_or(Error::ItemInvalid)?;
-            let child_result = amp_funct_act.apply_rhythmic(tempo, &mut buffer[index_from..]);
+            let child_result = amp_funct_act
+                .apply_rhythmic(&tempo[index_from..buffer_size], &mut buffer[index_from..]);
             match child_result {
                 Ok(()) => {
                     self.amplitude.set(amp_funct_act.get_amplitude());
                     return timer_result;
                 }
                 Err(Error::ItemsCompleted(completed)) => {
-                    index_from = completed;
-                    let array_index = self.array_index.get() + 1;
-                    if array_index >= self.amp_funct_array.len() {
-                        return Err(Error::ItemInvalid);
-                    } else {
-                        self.array_index.set(array_index);
-                        self.amplitude.set(amp_funct_act.get_amplitude());
-                        let amp_funct_next = self
-                            .amp_funct_array
-                            .get(array_index)
-                            .ok_or(Error::ItemInvalid)?;
-                        amp_funct_next.set_amplitude_start(self.amplitude.get())?;
-                        amp_funct_next.apply_parent_timing(self.timer.get_timing())?;
+                    index_from += completed;
+                    self.amplitude.set(amp_funct_act.get_amplitude());
+                    if self.step_next()? {
+                        // a whole loop without any progress would never end
+                        if wrapped_at == Some(index_from) {
+                            return Err(Error::ItemInvalid);
+                        }
+                        wrapped_at = Some(index_from);
                     }
                 }
                 Err(_) => return child_result,
@@ -651,10 +1162,331 @@ impl AmplitudeJoinable for AmplitudeSequence {
     }
 }
 
-/// Combination of several amplitude functions.
-pub struct AmplitudeCombination;
+/// Amplitude function with timing, which can be a member of an `AmplitudeCombination`.
+pub trait AmplitudeTimed: AmplitudeProvider + HasTimer {}
+
+impl<T: AmplitudeProvider + HasTimer> AmplitudeTimed for T {}
+
+/// Combination of several amplitude functions. They are applied in sequence over the same
+/// samples (their amplitudes are multiplied). It ends, when the shortest one ends.
+#[derive(Clone)]
+pub struct AmplitudeCombination {
+    timer: Timer,
+    amp_funct_array: Vec<Rc<dyn AmplitudeTimed>>,
+}
+
+impl AmplitudeCombination {
+    /// Custom constructor.
+    pub fn new(sample_rate: SampleCalc) -> SoundResult<AmplitudeCombination> {
+        Ok(AmplitudeCombination {
+            timer: Timer::new(sample_rate)?,
+            amp_funct_array: Vec::new(),
+        })
+    }
+
+    /// Adds a new amplitude function to the combination.
+    pub fn add(&mut self, amplitude: Rc<dyn AmplitudeTimed>) -> &mut AmplitudeCombination {
+        self.amp_funct_array.push(amplitude);
+        self
+    }
+}
+
+impl AmplitudeProvider for AmplitudeCombination {
+    fn apply(&self, samples: &mut [SampleCalc]) -> SoundResult<()> {
+        if self.amp_funct_array.is_empty() {
+            return Err(Error::SequenceEmpty);
+        }
+        let mut completed = samples.len();
+        let mut result = self.timer.jump_by_time(samples.len());
+        match result {
+            Ok(()) => {}
+            Err(Error::ItemsCompleted(timer_completed)) => completed = timer_completed,
+            Err(_) => return result,
+        }
+        for amp_funct in &self.amp_funct_array {
+            match amp_funct.apply(&mut samples[0..completed]) {
+                Ok(()) => {}
+                Err(Error::ItemsCompleted(child_completed)) => {
+                    completed = child_completed;
+                    result = Err(Error::ItemsCompleted(completed));
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        result
+    }
+
+    fn apply_rhythmic(&self, tempo: &[SampleCalc], samples: &mut [SampleCalc]) -> SoundResult<()> {
+        if tempo.len() != samples.len() {
+            return Err(Error::BufferSize);
+        }
+        if self.amp_funct_array.is_empty() {
+            return Err(Error::SequenceEmpty);
+        }
+        let mut completed = samples.len();
+        let mut result = self.timer.jump_by_tempo(tempo);
+        match result {
+            Ok(()) => {}
+            Err(Error::ItemsCompleted(timer_completed)) => completed = timer_completed,
+            Err(_) => return result,
+        }
+        for amp_funct in &self.amp_funct_array {
+            match amp_funct.apply_rhythmic(&tempo[0..completed], &mut samples[0..completed]) {
+                Ok(()) => {}
+                Err(Error::ItemsCompleted(child_completed)) => {
+                    completed = child_completed;
+                    result = Err(Error::ItemsCompleted(completed));
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        result
+    }
+}
+
+impl HasTimer for AmplitudeCombination {
+    fn set_timing(&self, timing: TimingOption) -> SoundResult<()> {
+        self.timer.set_timing(timing)?;
+        self.restart();
+        Ok(())
+    }
+
+    fn get_timing(&self) -> TimingOption {
+        self.timer.get_timing()
+    }
+
+    fn restart(&self) {
+        self.timer.restart();
+        for amp_funct in &self.amp_funct_array {
+            amp_funct.restart();
+        }
+    }
+
+    fn apply_parent_timing(&self, parent_timing: TimingOption) -> SoundResult<()> {
+        self.timer.apply_parent_timing(parent_timing)?;
+        for amp_funct in &self.amp_funct_array {
+            amp_funct.apply_parent_timing(self.timer.get_timing())?;
+        }
+        self.restart();
+        Ok(())
+    }
+}
+
+/// The standard frequencies of ISO 226:2003.
+const ISO226_FREQUENCY: [SampleCalc; 29] = [
+    20.0, 25.0, 31.5, 40.0, 50.0, 63.0, 80.0, 100.0, 125.0, 160.0, 200.0, 250.0, 315.0, 400.0,
+    500.0, 630.0, 800.0, 1000.0, 1250.0, 1600.0, 2000.0, 2500.0, 3150.0, 4000.0, 5000.0, 6300.0,
+    8000.0, 10000.0, 12500.0,
+];
+/// Exponent for loudness perception (ISO 226:2003).
+const ISO226_AF: [SampleCalc; 29] = [
+    0.532, 0.506, 0.480, 0.455, 0.432, 0.409, 0.387, 0.367, 0.349, 0.330, 0.315, 0.301, 0.288,
+    0.276, 0.267, 0.259, 0.253, 0.250, 0.246, 0.244, 0.243, 0.243, 0.243, 0.242, 0.242, 0.245,
+    0.254, 0.271, 0.301,
+];
+/// Magnitude of the linear transfer function normalized at 1000 Hz (ISO 226:2003).
+const ISO226_LU: [SampleCalc; 29] = [
+    -31.6, -27.2, -23.0, -19.1, -15.9, -13.0, -10.3, -8.1, -6.2, -4.5, -3.1, -2.0, -1.1, -0.4, 0.0,
+    0.3, 0.5, 0.0, -2.7, -4.1, -1.0, 1.7, 2.5, 1.2, -2.1, -7.1, -11.2, -10.7, -3.1,
+];
+/// Threshold of hearing (ISO 226:2003).
+const ISO226_TF: [SampleCalc; 29] = [
+    78.5, 68.7, 59.5, 51.1, 44.0, 37.5, 31.5, 26.5, 22.1, 17.9, 14.4, 11.4, 8.6, 6.2, 4.4, 3.0,
+    2.2, 2.4, 3.5, 1.7, -1.3, -4.2, -6.0, -5.4, -1.5, 6.0, 12.6, 13.9, 12.3,
+];
+
+/// Calculates the sound pressure level (in dB) of the given loudness level (in phon), by the
+/// ISO 226:2003 coefficients.
+fn iso226_sound_pressure_level(
+    loudness: SampleCalc,
+    af: SampleCalc,
+    lu: SampleCalc,
+    tf: SampleCalc,
+) -> SampleCalc {
+    let ten: SampleCalc = 10.0;
+    let a_f = 4.47e-3 * (ten.powf(0.025 * loudness) - 1.15)
+        + (0.4 * ten.powf((tf + lu) / 10.0 - 9.0)).powf(af);
+    (10.0 / af) * a_f.log10() - lu + 94.0
+}
 
 /// [Equal-loudness contour](https://en.wikipedia.org/wiki/Equal-loudness_contour)
 /// data used is described by the ISO 226:2003 standard
 /// see also: <https://plot.ly/~mrlyule/16/equal-loudness-contours-iso-226-2003/>
-pub struct AmplitudeEqualLoudness;
+///
+/// The amplitude is set to produce the same perceived loudness at any frequency. The loudest
+/// point of the contour (the lowest standard frequency) gets amplitude 1.0.
+#[derive(Debug, Clone)]
+pub struct AmplitudeEqualLoudness {
+    amplitude: SampleCalc,
+}
+
+impl AmplitudeEqualLoudness {
+    /// Custom constructor. The `loudness` level is given in phons, the coefficients are
+    /// interpolated between the standard frequency points (frequencies outside the
+    /// [20 Hz, 12.5 kHz] range use the closest values).
+    pub fn new(frequency: SampleCalc, loudness: SampleCalc) -> SoundResult<AmplitudeEqualLoudness> {
+        if frequency <= 0.0 || !frequency.is_finite() {
+            return Err(Error::FrequencyInvalid);
+        }
+        if !(0.0..=90.0).contains(&loudness) {
+            return Err(Error::LoudnessInvalid);
+        }
+        let last = ISO226_FREQUENCY.len() - 1;
+        let (af, lu, tf) = if frequency <= ISO226_FREQUENCY[0] {
+            (ISO226_AF[0], ISO226_LU[0], ISO226_TF[0])
+        } else if frequency >= ISO226_FREQUENCY[last] {
+            (ISO226_AF[last], ISO226_LU[last], ISO226_TF[last])
+        } else {
+            let index = ISO226_FREQUENCY
+                .iter()
+                .position(|standard| *standard > frequency)
+                .unwrap_or(last)
+                - 1;
+            // interpolation on logarithmic frequency scale
+            let ratio = (frequency / ISO226_FREQUENCY[index]).ln()
+                / (ISO226_FREQUENCY[index + 1] / ISO226_FREQUENCY[index]).ln();
+            let interpolate =
+                |table: &[SampleCalc]| table[index] + (table[index + 1] - table[index]) * ratio;
+            (
+                interpolate(&ISO226_AF),
+                interpolate(&ISO226_LU),
+                interpolate(&ISO226_TF),
+            )
+        };
+        let level_max = ISO226_AF
+            .iter()
+            .zip(ISO226_LU.iter())
+            .zip(ISO226_TF.iter())
+            .map(|((af, lu), tf)| iso226_sound_pressure_level(loudness, *af, *lu, *tf))
+            .fold(SampleCalc::MIN, SampleCalc::max);
+        let level = iso226_sound_pressure_level(loudness, af, lu, tf);
+        let ten: SampleCalc = 10.0;
+        Ok(AmplitudeEqualLoudness {
+            amplitude: ten.powf((level - level_max) / 20.0),
+        })
+    }
+
+    /// Provides the amplitude.
+    pub fn get_amplitude(&self) -> SampleCalc {
+        self.amplitude
+    }
+}
+
+impl AmplitudeProvider for AmplitudeEqualLoudness {
+    fn apply(&self, samples: &mut [SampleCalc]) -> SoundResult<()> {
+        for item in samples.iter_mut() {
+            *item *= self.amplitude;
+        }
+        Ok(())
+    }
+
+    fn apply_rhythmic(&self, tempo: &[SampleCalc], samples: &mut [SampleCalc]) -> SoundResult<()> {
+        if tempo.len() != samples.len() {
+            return Err(Error::BufferSize);
+        }
+        self.apply(samples)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gain_curve_matches_manual_multiply() {
+        let samples: Vec<SampleCalc> = (0..64).map(|i| (i as SampleCalc * 0.3).sin()).collect();
+        let curve: Vec<SampleCalc> = (0..64).map(|i| i as SampleCalc / 63.0).collect();
+        let mut result = samples.clone();
+        apply_gain_curve(&mut result, &curve).unwrap();
+        for ((item, sample), gain) in result.iter().zip(&samples).zip(&curve) {
+            assert_eq!(*item, sample * gain);
+        }
+        assert!(matches!(
+            apply_gain_curve(&mut result, &curve[1..]),
+            Err(Error::BufferSize)
+        ));
+    }
+
+    #[test]
+    fn decay_exp_halves_after_half_life() {
+        let sample_rate: SampleCalc = 48000.0;
+        let half_life: SampleCalc = 0.1;
+        let amplitude = AmplitudeDecayExp::new(sample_rate, half_life).unwrap();
+        let mut samples = Vec::new();
+        let mut buffer = [1.0; BUFFER_SIZE_DEFAULT];
+        while samples.len() < 3 * 4800 {
+            buffer.iter_mut().for_each(|item| *item = 1.0);
+            amplitude.apply(&mut buffer).unwrap();
+            samples.extend_from_slice(&buffer);
+        }
+        let half: SampleCalc = 0.5;
+        // the n-th sample is multiplied by the amplitude after n + 1 steps
+        for (index, item) in samples.iter().enumerate() {
+            let time = (index + 1) as SampleCalc / sample_rate;
+            let expected = half.powf(time / half_life);
+            assert!((item - expected).abs() < 1e-3, "{} {}", index, item);
+        }
+        assert!((samples[4799] - 0.5).abs() < 1e-4);
+    }
+
+    /// Checks that the looping fade up and fade down envelope is applied on the first
+    /// `completed` samples, and the direction of the fades changes at least 3 times.
+    fn assert_looping_fades(result: SoundResult<()>, samples: &[SampleCalc], completed: usize) {
+        assert!(
+            matches!(result, Err(Error::ItemsCompleted(count)) if count == completed),
+            "{:?}",
+            result
+        );
+        let envelope = &samples[..completed];
+        assert!(envelope.iter().all(|item| *item >= 0.0 && *item <= 1.0));
+        let direction_changes = envelope
+            .windows(3)
+            .filter(|items| (items[1] - items[0]) * (items[2] - items[1]) < 0.0)
+            .count();
+        assert!(direction_changes >= 3, "{:?}", envelope);
+        // the samples after the end of the sequence are left unchanged
+        assert!(samples[completed..].iter().all(|item| *item == 1.0));
+    }
+
+    #[test]
+    fn amplitude_sequence_loops_until_its_timing_ends() {
+        let mut sequence = AmplitudeSequence::new(1000.0).unwrap();
+        sequence.add(Rc::new(
+            FadeLinear::new_with_time(1000.0, 0.004, 1.0).unwrap(),
+        ));
+        sequence.add(Rc::new(
+            FadeLinear::new_with_time(1000.0, 0.004, 0.0).unwrap(),
+        ));
+        sequence.set_loop(true);
+        sequence.set_timing(TimingOption::Samples(20)).unwrap();
+        let mut samples = [1.0; 32];
+        let result = sequence.apply(&mut samples);
+        assert_looping_fades(result, &samples, 20);
+        assert!(matches!(
+            sequence.apply(&mut samples),
+            Err(Error::ItemsCompleted(0))
+        ));
+    }
+
+    #[test]
+    fn amplitude_sequence_loops_until_its_tempo_timing_ends() {
+        // 125 beats per second, a half beat is 4 samples long
+        let half_beat = NoteValue::new(1, 2).unwrap();
+        let mut sequence = AmplitudeSequence::new(1000.0).unwrap();
+        sequence.add(Rc::new(
+            FadeLinear::new_with_tempo(1000.0, half_beat, 1.0).unwrap(),
+        ));
+        sequence.add(Rc::new(
+            FadeLinear::new_with_tempo(1000.0, half_beat, 0.0).unwrap(),
+        ));
+        sequence.set_loop(true);
+        sequence
+            .set_timing(TimingOption::TempoConst(NoteValue::new(5, 2).unwrap()))
+            .unwrap();
+        let tempo = [125.0; 32];
+        let mut samples = [1.0; 32];
+        let result = sequence.apply_rhythmic(&tempo, &mut samples);
+        // the tempo based timer ends at the sample which uses up its duration
+        assert_looping_fades(result, &samples, 19);
+    }
+}
@@ -304,6 +304,142 @@ impl AmplitudeOvertonesJoinable for AmplitudeDecayExpOvertones {
     }
 }
 
+/// [Tremolo](https://en.wikipedia.org/wiki/Tremolo) for the fundamental tone and for the
+/// overtones. Each overtone's phase is shifted by `phase_offset` relative to the previous one, so
+/// the higher partials can vary out of phase (like the rotating timbre of a Leslie speaker).
+#[derive(Debug, Clone)]
+pub struct TremoloOvertones {
+    /// Time based progress.
+    progress: ProgressOption,
+    /// The ratio of maximum shift away from the base amplitude (must be > 1.0).
+    extent_ratio: SampleCalc,
+    /// The normalized base amplitudes, divided by `extent_ratio`, so the peak amplitudes will
+    /// not exceed them.
+    amplitude: Vec<SampleCalc>,
+    /// Phase shift between neighbouring overtones.
+    phase_offset: SampleCalc,
+    /// The phases of the progress for the actual chunk.
+    phase_buffer: RefCell<Vec<SampleCalc>>,
+}
+
+impl TremoloOvertones {
+    /// custom constructor
+    /// It normalizes the amplitudes, so the sum of the peak amplitudes will be 1.0.
+    /// `overtone_count` is independent of the size of `amplitude`.
+    /// `extent_ratio` is the ratio of maximum shift away from the base amplitude (must be > 1.0).
+    pub fn new(
+        progress: ProgressOption,
+        overtone_count: usize,
+        amplitude: &[SampleCalc],
+        extent_ratio: SampleCalc,
+        phase_offset: SampleCalc,
+    ) -> SoundResult<TremoloOvertones> {
+        if extent_ratio <= 1.0 {
+            return Err(Error::AmplitudeInvalid);
+        }
+        let mut amplitude_sum: SampleCalc = 0.0;
+        for amplitude_check in amplitude.iter().take(overtone_count + 1) {
+            if *amplitude_check < 0.0 {
+                return Err(Error::AmplitudeInvalid);
+            };
+            amplitude_sum += *amplitude_check;
+        }
+        if amplitude_sum == 0.0 {
+            return Err(Error::AmplitudeInvalid);
+        };
+        // fundamental tone is included in size
+        let mut amplitude_new = vec![0.0; overtone_count + 1];
+        // normalization
+        for (item, amplitude_old) in amplitude_new.iter_mut().zip(amplitude) {
+            *item = amplitude_old / (amplitude_sum * extent_ratio);
+        }
+        Ok(TremoloOvertones {
+            progress,
+            extent_ratio,
+            amplitude: amplitude_new,
+            phase_offset,
+            phase_buffer: RefCell::new(Vec::new()),
+        })
+    }
+
+    /// Custom constructor with time based progress.
+    pub fn new_with_time(
+        sample_rate: SampleCalc,
+        timing: TimingOption,
+        period: SampleCalc,
+        overtone_count: usize,
+        amplitude: &[SampleCalc],
+        extent_ratio: SampleCalc,
+        phase_offset: SampleCalc,
+    ) -> SoundResult<TremoloOvertones> {
+        let progress = ProgressTime::new(sample_rate, period)?;
+        progress.set_timing(timing)?;
+        Self::new(
+            ProgressOption::Time(progress),
+            overtone_count,
+            amplitude,
+            extent_ratio,
+            phase_offset,
+        )
+    }
+}
+
+impl AmplitudeOvertonesProvider for TremoloOvertones {
+    fn next_chunk(&self, samples: usize) -> SoundResult<()> {
+        let mut phase_buffer = self.phase_buffer.borrow_mut();
+        phase_buffer.resize(samples, 0.0);
+        match self.progress {
+            ProgressOption::Time(ref p) => {
+                for (index, item) in phase_buffer.iter_mut().enumerate() {
+                    match p.next_by_time() {
+                        Ok(phase) => *item = phase,
+                        Err(Error::ProgressCompleted) => return Err(Error::ItemsCompleted(index)),
+                        Err(e) => return Err(e),
+                    }
+                }
+                p.simplify();
+            }
+            ProgressOption::Tempo(ref _p) => return Err(Error::ProgressInvalid),
+        }
+        Ok(())
+    }
+
+    fn apply(&self, overtone: usize, samples: &mut [SampleCalc]) -> SoundResult<()> {
+        if overtone >= self.amplitude.len() {
+            for item in samples.iter_mut() {
+                *item = 0.0;
+            }
+            return Ok(());
+        }
+        let amplitude = self.amplitude[overtone];
+        let phase_offset = self.phase_offset * overtone as SampleCalc;
+        for (item, phase) in samples.iter_mut().zip(self.phase_buffer.borrow().iter()) {
+            *item *= amplitude * self.extent_ratio.powf((phase + phase_offset).sin());
+        }
+        Ok(())
+    }
+}
+
+impl HasTimer for TremoloOvertones {
+    fn set_timing(&self, timing: TimingOption) -> SoundResult<()> {
+        self.progress.set_timing(timing)?;
+        self.restart();
+        Ok(())
+    }
+
+    fn get_timing(&self) -> TimingOption {
+        self.progress.get_timing()
+    }
+
+    fn restart(&self) {
+        self.progress.restart();
+    }
+
+    fn apply_parent_timing(&self, parent_timing: TimingOption) -> SoundResult<()> {
+        self.progress.apply_parent_timing(parent_timing)
+    }
+}
+
 /// A sequence of amplitude functions with overtones.
 #[derive(Clone)]
 pub struct AmplitudeOvertonesSequence {
@@ -351,7 +487,7 @@ impl AmplitudeOvertonesProvider for AmplitudeOvertonesSequence {
         // let amplitude_act =
         //    try!(self.amplitudes.get(self.amplitude_index.get()).ok_or(Error::ItemInvalid));
         //        let buffer_len: usize;
-        // match amplitude_act.get_timer().step_time(samples) {
+        // match amplitude_act.get_timer().jump_by_time(samples) {
         // Ok(()) => {
         // buffer_len = samples;
         // }
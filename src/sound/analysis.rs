@@ -0,0 +1,137 @@
+use crate::sound::*;
+#[cfg(feature = "analysis")]
+use rustfft::{num_complex::Complex, FftPlanner};
+
+/// Calculates the magnitude spectrum of the samples, with a
+/// [discrete Fourier transform](https://en.wikipedia.org/wiki/Discrete_Fourier_transform).
+/// The result contains the bins from 0 Hz up to the Nyquist frequency, the frequency of bin `k`
+/// is `k * sample_rate / samples.len()`.
+/// Note: the calculation time is proportional to the square of the sample count. With the
+/// `analysis` feature it is calculated by a fast Fourier transform instead.
+#[cfg(not(feature = "analysis"))]
+pub fn spectrum(samples: &[SampleCalc]) -> Vec<SampleCalc> {
+    let sample_count = samples.len();
+    let bin_count = sample_count / 2 + 1;
+    let mut magnitudes = Vec::with_capacity(bin_count);
+    for bin in 0..bin_count {
+        let phase_change = PI2 * bin as SampleCalc / sample_count as SampleCalc;
+        let (mut real, mut imaginary): (SampleCalc, SampleCalc) = (0.0, 0.0);
+        for (index, sample) in samples.iter().enumerate() {
+            // the phase is reduced to keep the precision for long buffers
+            let phase = (phase_change * index as SampleCalc) % PI2;
+            real += *sample * phase.cos();
+            imaginary -= *sample * phase.sin();
+        }
+        magnitudes.push(real.hypot(imaginary));
+    }
+    magnitudes
+}
+
+/// Calculates the magnitude spectrum of the samples, with a
+/// [fast Fourier transform](https://en.wikipedia.org/wiki/Fast_Fourier_transform).
+/// The result contains the bins from 0 Hz up to the Nyquist frequency, the frequency of bin `k`
+/// is `k * sample_rate / samples.len()`.
+#[cfg(feature = "analysis")]
+pub fn spectrum(samples: &[SampleCalc]) -> Vec<SampleCalc> {
+    let sample_count = samples.len();
+    if sample_count == 0 {
+        return vec![0.0];
+    }
+    let mut buffer: Vec<Complex<SampleCalc>> = samples
+        .iter()
+        .map(|sample| Complex::new(*sample, 0.0))
+        .collect();
+    FftPlanner::new()
+        .plan_fft_forward(sample_count)
+        .process(&mut buffer);
+    buffer
+        .iter()
+        .take(sample_count / 2 + 1)
+        .map(|bin| bin.norm())
+        .collect()
+}
+
+/// Returns the frequency of the strongest component of the samples (the 0 Hz bin is
+/// ignored). The peak bin is refined by parabolic interpolation of the neighbouring bins, so
+/// the result is more precise than the bin width (`sample_rate / samples.len()`).
+/// Returns 0.0 for silence.
+pub fn dominant_frequency(samples: &[SampleCalc], sample_rate: SampleCalc) -> SampleCalc {
+    let magnitudes = spectrum(samples);
+    let mut peak_bin = 0;
+    let mut peak_magnitude: SampleCalc = 0.0;
+    for (bin, magnitude) in magnitudes.iter().enumerate().skip(1) {
+        if *magnitude > peak_magnitude {
+            peak_bin = bin;
+            peak_magnitude = *magnitude;
+        }
+    }
+    if peak_bin == 0 {
+        return 0.0;
+    }
+    let mut offset = 0.0;
+    if let (Some(before), Some(after)) =
+        (magnitudes.get(peak_bin - 1), magnitudes.get(peak_bin + 1))
+    {
+        let curvature = before - 2.0 * peak_magnitude + after;
+        if curvature < 0.0 {
+            offset = 0.5 * (before - after) / curvature;
+        }
+    }
+    (peak_bin as SampleCalc + offset) * sample_rate / samples.len() as SampleCalc
+}
+
+/// Calculates the [spectral centroid](https://en.wikipedia.org/wiki/Spectral_centroid) of the
+/// samples: the amplitude-weighted mean frequency. It is a measure of the brightness of a sound.
+/// Returns 0.0 for silence.
+pub fn spectral_centroid(samples: &[SampleCalc], sample_rate: SampleCalc) -> SampleCalc {
+    let magnitudes = spectrum(samples);
+    let bin_width = sample_rate / samples.len() as SampleCalc;
+    let mut weighted_sum: SampleCalc = 0.0;
+    let mut magnitude_sum: SampleCalc = 0.0;
+    for (bin, magnitude) in magnitudes.iter().enumerate() {
+        weighted_sum += bin as SampleCalc * bin_width * *magnitude;
+        magnitude_sum += *magnitude;
+    }
+    if magnitude_sum == 0.0 {
+        return 0.0;
+    }
+    weighted_sum / magnitude_sum
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::rc::Rc;
+
+    const SAMPLE_RATE: SampleCalc = 48000.0;
+
+    /// 0.1 s of a 440 Hz tone with the given overtone amplitudes.
+    fn render_tone(amplitudes: &[SampleCalc]) -> Vec<SampleCalc> {
+        let overtone_count = amplitudes.len() - 1;
+        let amplitude =
+            AmplitudeConstOvertones::new(SAMPLE_RATE, overtone_count, amplitudes).unwrap();
+        let timbre = Timbre::new(
+            SAMPLE_RATE,
+            BUFFER_SIZE_DEFAULT,
+            Rc::new(amplitude),
+            overtone_count + 1,
+        )
+        .unwrap();
+        timbre.render(440.0, SAMPLE_RATE, 0.1).unwrap()
+    }
+
+    #[test]
+    fn bright_tone_has_higher_spectral_centroid() {
+        let near_sine = render_tone(&[1.0, 0.01, 0.01, 0.01, 0.01, 0.01, 0.01, 0.01]);
+        let bright = render_tone(&[1.0, 0.5, 0.5, 0.5, 1.0, 1.0, 1.0, 1.0]);
+        let centroid_near_sine = spectral_centroid(&near_sine, SAMPLE_RATE);
+        let centroid_bright = spectral_centroid(&bright, SAMPLE_RATE);
+        assert!(centroid_near_sine < 600.0, "{}", centroid_near_sine);
+        assert!(
+            centroid_bright > 2.0 * centroid_near_sine,
+            "{} {}",
+            centroid_bright,
+            centroid_near_sine
+        );
+    }
+}
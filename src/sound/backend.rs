@@ -0,0 +1,111 @@
+use crate::sound::*;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Receiver;
+use std::sync::Arc;
+
+/// The audio callback routine shared by the backends. It fills interleaved output buffers of any
+/// size, all channels get the same samples. The generator is always called with full buffers,
+/// the generated samples which do not fit in the output are kept for the next call.
+pub struct OutputCallback<T> {
+    channel_count: usize,
+    generator: Box<dyn SoundGenerator<Command = T>>,
+    generator_buffer: Vec<SampleCalc>,
+    /// The index of the next generated sample to output.
+    buffer_position: usize,
+    receiver: Receiver<T>,
+    /// Amplitude function applied when the playback starts. It is `None` when finished.
+    fade_in: Option<FadeLinear>,
+    telemetry_sender: TelemetrySender,
+    /// It is set when the generator returns an error, the output stays silent after it.
+    failed: bool,
+    /// The soft limiter is applied on the output, if it is set.
+    limiter: Arc<AtomicBool>,
+}
+
+impl<T> OutputCallback<T> {
+    /// custom constructor
+    pub fn new(
+        buffer_size: usize,
+        channel_count: u16,
+        generator: Box<dyn SoundGenerator<Command = T>>,
+        receiver: Receiver<T>,
+        fade_in: Option<FadeLinear>,
+        telemetry_sender: TelemetrySender,
+        limiter: Arc<AtomicBool>,
+    ) -> OutputCallback<T> {
+        OutputCallback {
+            channel_count: (channel_count as usize).max(1),
+            generator,
+            generator_buffer: vec![0.0; buffer_size],
+            buffer_position: buffer_size,
+            receiver,
+            fade_in,
+            telemetry_sender,
+            failed: false,
+            limiter,
+        }
+    }
+
+    /// Fills the interleaved `output` buffer. It does not allocate, so it can be called from the
+    /// audio thread.
+    pub fn process(&mut self, output: &mut [SampleOutput]) {
+        if self.failed {
+            for item in output.iter_mut() {
+                *item = 0.0;
+            }
+            return;
+        }
+        for output_frame in output.chunks_mut(self.channel_count) {
+            if self.buffer_position == self.generator_buffer.len() {
+                if !self.generate() {
+                    for item in output.iter_mut() {
+                        *item = 0.0;
+                    }
+                    return;
+                }
+                self.buffer_position = 0;
+            }
+            let sample = get_output_sample(self.generator_buffer[self.buffer_position]);
+            for channel_sample in output_frame {
+                *channel_sample = sample;
+            }
+            self.buffer_position += 1;
+        }
+    }
+
+    /// Applies the next command, and generates a new buffer. Returns `false` if the generator
+    /// failed.
+    fn generate(&mut self) -> bool {
+        if let Ok(command) = self.receiver.try_recv() {
+            self.generator.process_command(command);
+            self.telemetry_sender.send(TelemetryEvent::CommandApplied);
+        }
+        let sample_count = self.generator_buffer.len();
+        if self
+            .generator
+            .get_samples(sample_count, &mut self.generator_buffer)
+            .is_err()
+        {
+            self.failed = true;
+            self.telemetry_sender.send(TelemetryEvent::GeneratorFailed);
+            return false;
+        }
+        self.telemetry_sender
+            .send_clip_count(&self.generator_buffer);
+        apply_fade_in(&mut self.fade_in, &mut self.generator_buffer);
+        if self.limiter.load(Ordering::Relaxed) {
+            soft_limit(&mut self.generator_buffer);
+        }
+        true
+    }
+
+    /// Sends an `Underrun` telemetry event, for the backends which can detect it.
+    pub fn report_underrun(&self) {
+        self.telemetry_sender.send(TelemetryEvent::Underrun);
+    }
+
+    /// Returns `true` if the generator has failed, and the output is silenced.
+    pub fn is_failed(&self) -> bool {
+        self.failed
+    }
+}
@@ -0,0 +1,180 @@
+// Help: https://docs.rs/cpal
+//
+// cpal is written in pure Rust, only the system's own audio libraries are needed (e.g. ALSA on
+// Linux).
+//
+
+use crate::sound::*;
+
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Sender;
+use std::sync::Arc;
+
+use thiserror::Error;
+
+/// This is a wrapper around the sound output backend
+pub struct SoundInterface<T: 'static> {
+    sample_rate: u32,
+    channel_count: u16,
+    stream: cpal::Stream,
+    sender: Option<Sender<T>>,
+    telemetry: TelemetryQueue,
+    limiter: Arc<AtomicBool>,
+}
+
+impl<T> SoundInterface<T>
+where
+    T: Send,
+{
+    /// Creates a new backend for sound playback.
+    /// At the moment all channels output the same sound.
+    pub fn new(
+        sample_rate: u32,
+        buffer_size: usize,
+        channel_count: u16,
+        generator: Box<dyn SoundGenerator<Command = T>>,
+    ) -> BackendResult<SoundInterface<T>> {
+        Self::new_with_fade_in(
+            sample_rate,
+            buffer_size,
+            channel_count,
+            FADE_IN_DURATION_DEFAULT,
+            generator,
+        )
+    }
+
+    /// Creates a new backend for sound playback, with a custom fade-in duration (in seconds)
+    /// applied when the playback starts. A `fade_in` of 0.0 turns the fade-in off.
+    pub fn new_with_fade_in(
+        sample_rate: u32,
+        buffer_size: usize,
+        channel_count: u16,
+        fade_in: SampleCalc,
+        generator: Box<dyn SoundGenerator<Command = T>>,
+    ) -> BackendResult<SoundInterface<T>> {
+        let fade_in = get_fade_in(sample_rate, fade_in).map_err(|_| BackendError::FadeInInvalid)?;
+        let host = cpal::default_host();
+        println!("cpal host: {:?}", host.id());
+        let device = host
+            .default_output_device()
+            .ok_or(BackendError::DeviceNotFound)?;
+        // The device chooses the callback buffer size, the shared callback calls the generator
+        // with full buffers anyway.
+        let config = cpal::StreamConfig {
+            channels: channel_count,
+            sample_rate: cpal::SampleRate(sample_rate),
+            buffer_size: cpal::BufferSize::Default,
+        };
+
+        let limiter = Arc::new(AtomicBool::new(false));
+        let (sender, receiver) = ::std::sync::mpsc::channel();
+        let (telemetry_sender, telemetry) = TelemetryQueue::new();
+        let error_telemetry_sender = telemetry_sender.clone();
+        let mut output_callback = OutputCallback::new(
+            buffer_size,
+            channel_count,
+            generator,
+            receiver,
+            fade_in,
+            telemetry_sender,
+            limiter.clone(),
+        );
+        // This routine will be called by cpal's audio thread when audio is needed, so don't do
+        // anything that could block it, like dynamic resource allocation or IO.
+        let callback_fn = move |buffer: &mut [SampleOutput], _: &cpal::OutputCallbackInfo| {
+            output_callback.process(buffer);
+        };
+        let error_fn = move |_: cpal::StreamError| {
+            error_telemetry_sender.send(TelemetryEvent::StreamError);
+        };
+
+        let stream = device.build_output_stream(&config, callback_fn, error_fn, None)?;
+        println!("Stream is created.");
+
+        Ok(SoundInterface {
+            sample_rate,
+            channel_count,
+            stream,
+            sender: Some(sender),
+            telemetry,
+            limiter,
+        })
+    }
+    /// Starts the sound output stream.
+    pub fn start(&mut self) -> BackendResult<()> {
+        self.stream.play()?;
+        println!("Successfully started the stream.");
+        Ok(())
+    }
+    /// Sends a command to the sound generator.
+    pub fn send_command(&mut self, command: T) -> BackendResult<()> {
+        if let Some(ref sender) = self.sender {
+            match sender.send(command) {
+                Ok(_) => Ok(()),
+                Err(_) => Err(BackendError::Disconnected),
+            }
+        } else {
+            Err(BackendError::Disconnected)
+        }
+    }
+
+    /// Turns the soft limiter of the output on or off (it is off by default). It prevents harsh
+    /// digital clipping, see `soft_limit()`.
+    pub fn set_limiter(&mut self, enabled: bool) {
+        self.limiter.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Sets the function receiving the telemetry events. The events are delivered on the host
+    /// thread, by `process_telemetry()`.
+    pub fn set_telemetry_callback(&mut self, callback: fn(TelemetryEvent)) {
+        self.telemetry.set_callback(callback);
+    }
+
+    /// Delivers the queued telemetry events to the registered callback. Without a callback the
+    /// events are dropped. It shall be called periodically from the host thread.
+    pub fn process_telemetry(&self) {
+        self.telemetry.process();
+    }
+
+    /// Returns the sample rate of the sond output
+    pub fn get_sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+    /// Returns the channel count of the sond output
+    pub fn get_channel_count(&self) -> u16 {
+        self.channel_count
+    }
+}
+
+/// Return type for the backend functions.
+pub type BackendResult<T> = Result<T, BackendError>;
+
+/// Wrapper for the propagation of backend specific errors.
+#[derive(Debug, Clone, Error)]
+pub enum BackendError {
+    /// Errors of the cpal backend.
+    #[error("cpal backend error: {0}")]
+    Cpal(String),
+    /// There is no default output device.
+    #[error("No output device is available")]
+    DeviceNotFound,
+    /// The SoundGenerator is disconnected, could not recieve the command
+    #[error("The SoundGenerator is disconnected")]
+    Disconnected,
+    /// The duration of the fade-in must not be negative.
+    #[error("Invalid fade-in duration")]
+    FadeInInvalid,
+}
+
+impl From<cpal::BuildStreamError> for BackendError {
+    fn from(err: cpal::BuildStreamError) -> Self {
+        Self::Cpal(err.to_string())
+    }
+}
+
+impl From<cpal::PlayStreamError> for BackendError {
+    fn from(err: cpal::PlayStreamError) -> Self {
+        Self::Cpal(err.to_string())
+    }
+}
@@ -0,0 +1,202 @@
+use crate::sound::*;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Sender;
+use std::sync::Arc;
+
+/// Sound output backend without a device: the host pulls the output with `render()`, instead of
+/// an audio thread. It has the same audio callback as the other backends, but it needs no
+/// feature or system library, so it is suitable for testing.
+pub struct NullSoundInterface<T: 'static> {
+    sample_rate: u32,
+    channel_count: u16,
+    callback: OutputCallback<T>,
+    started: bool,
+    sender: Sender<T>,
+    telemetry: TelemetryQueue,
+    limiter: Arc<AtomicBool>,
+}
+
+impl<T> NullSoundInterface<T> {
+    /// Creates a new backend for sound playback.
+    /// All channels output the same sound.
+    pub fn new(
+        sample_rate: u32,
+        buffer_size: usize,
+        channel_count: u16,
+        generator: Box<dyn SoundGenerator<Command = T>>,
+    ) -> SoundResult<NullSoundInterface<T>> {
+        Self::new_with_fade_in(
+            sample_rate,
+            buffer_size,
+            channel_count,
+            FADE_IN_DURATION_DEFAULT,
+            generator,
+        )
+    }
+
+    /// Creates a new backend for sound playback, with a custom fade-in duration (in seconds)
+    /// applied when the playback starts. A `fade_in` of 0.0 turns the fade-in off.
+    pub fn new_with_fade_in(
+        sample_rate: u32,
+        buffer_size: usize,
+        channel_count: u16,
+        fade_in: SampleCalc,
+        generator: Box<dyn SoundGenerator<Command = T>>,
+    ) -> SoundResult<NullSoundInterface<T>> {
+        if buffer_size == 0 {
+            return Err(Error::BufferSize);
+        }
+        if channel_count == 0 {
+            return Err(Error::ChannelInvalid);
+        }
+        let fade_in = get_fade_in(sample_rate, fade_in)?;
+        let (sender, receiver) = ::std::sync::mpsc::channel();
+        let (telemetry_sender, telemetry) = TelemetryQueue::new();
+        let limiter = Arc::new(AtomicBool::new(false));
+        let callback = OutputCallback::new(
+            buffer_size,
+            channel_count,
+            generator,
+            receiver,
+            fade_in,
+            telemetry_sender,
+            limiter.clone(),
+        );
+        Ok(NullSoundInterface {
+            sample_rate,
+            channel_count,
+            callback,
+            started: false,
+            sender,
+            telemetry,
+            limiter,
+        })
+    }
+
+    /// Starts the sound output stream.
+    pub fn start(&mut self) -> SoundResult<()> {
+        self.started = true;
+        Ok(())
+    }
+
+    /// Fills the interleaved `output` buffer, like the audio callback of a device. The output is
+    /// silent until the stream is started.
+    pub fn render(&mut self, output: &mut [SampleOutput]) {
+        if self.started {
+            self.callback.process(output);
+        } else {
+            for item in output.iter_mut() {
+                *item = 0.0;
+            }
+        }
+    }
+
+    /// Sends a command to the sound generator.
+    pub fn send_command(&mut self, command: T) -> SoundResult<()> {
+        // the receiver is owned by the interface, so it can not be disconnected
+        let _ = self.sender.send(command);
+        Ok(())
+    }
+
+    /// Turns the soft limiter of the output on or off (it is off by default). It prevents harsh
+    /// digital clipping, see `soft_limit()`.
+    pub fn set_limiter(&mut self, enabled: bool) {
+        self.limiter.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Sets the function receiving the telemetry events. The events are delivered on the host
+    /// thread, by `process_telemetry()`.
+    pub fn set_telemetry_callback(&mut self, callback: fn(TelemetryEvent)) {
+        self.telemetry.set_callback(callback);
+    }
+
+    /// Delivers the queued telemetry events to the registered callback. Without a callback the
+    /// events are dropped. It shall be called periodically from the host thread.
+    pub fn process_telemetry(&self) {
+        self.telemetry.process();
+    }
+
+    /// Returns the sample rate of the sond output
+    pub fn get_sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+    /// Returns the channel count of the sond output
+    pub fn get_channel_count(&self) -> u16 {
+        self.channel_count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sound::test_utils::*;
+    use std::sync::atomic::AtomicUsize;
+
+    #[test]
+    fn first_buffer_after_start_fades_in() {
+        let generator = ConstantGenerator::new(48000.0, 1.0);
+        let mut sound = NullSoundInterface::new(48000, BUFFER_SIZE_DEFAULT, 2, generator).unwrap();
+        let mut output = vec![1.0; BUFFER_SIZE_DEFAULT * 2];
+        sound.render(&mut output);
+        assert!(output.iter().all(|item| *item == 0.0));
+
+        sound.start().unwrap();
+        sound.render(&mut output);
+        assert!(output[0] < 0.01, "{}", output[0]);
+        for frame in output.chunks(2) {
+            assert_eq!(frame[0], frame[1]);
+        }
+        let left: Vec<SampleOutput> = output.iter().step_by(2).copied().collect();
+        assert!(left.windows(2).all(|pair| pair[0] <= pair[1]));
+        // the default fade-in reaches the full level in 240 samples at 48 kHz
+        assert!(left[..239].iter().all(|item| *item < 1.0));
+        assert!(left[240..].iter().all(|item| *item == 1.0));
+    }
+
+    #[test]
+    fn output_buffer_size_differs_from_the_generator_buffer() {
+        let generator = ConstantGenerator::new(48000.0, 0.5);
+        let mut sound = NullSoundInterface::new_with_fade_in(48000, 64, 1, 0.0, generator).unwrap();
+        sound.start().unwrap();
+        let mut output = vec![0.0; 100];
+        sound.render(&mut output);
+        assert!(output.iter().all(|item| *item == 0.5));
+        // the command is applied when the generator is called next, after the leftover samples
+        sound.send_command(0.25).unwrap();
+        sound.render(&mut output);
+        assert!(output[..28].iter().all(|item| *item == 0.5));
+        assert!(output[28..].iter().all(|item| *item == 0.25));
+    }
+
+    static CLIP_COUNT: AtomicUsize = AtomicUsize::new(0);
+    static COMMAND_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+    fn count_events(event: TelemetryEvent) {
+        match event {
+            TelemetryEvent::Clip(clip_count) => {
+                let _ = CLIP_COUNT.fetch_add(clip_count, Ordering::Relaxed);
+            }
+            TelemetryEvent::CommandApplied => {
+                let _ = COMMAND_COUNT.fetch_add(1, Ordering::Relaxed);
+            }
+            _ => {}
+        }
+    }
+
+    #[test]
+    fn telemetry_callback_receives_clip_event() {
+        let generator = ConstantGenerator::new(48000.0, 0.5);
+        let mut sound = NullSoundInterface::new_with_fade_in(48000, 64, 2, 0.0, generator).unwrap();
+        sound.set_telemetry_callback(count_events);
+        sound.start().unwrap();
+        let mut output = vec![0.0; 64 * 2];
+        sound.render(&mut output);
+        sound.send_command(1.5).unwrap();
+        sound.render(&mut output);
+        assert_eq!(CLIP_COUNT.load(Ordering::Relaxed), 0);
+        sound.process_telemetry();
+        // the clipped samples are counted once, not per output channel
+        assert_eq!(CLIP_COUNT.load(Ordering::Relaxed), 64);
+        assert_eq!(COMMAND_COUNT.load(Ordering::Relaxed), 1);
+    }
+}
@@ -2,7 +2,9 @@ use crate::portaudio as pa;
 use crate::sound::*;
 // use std::thread;
 // use std::sync::mpsc::channel;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc::Sender;
+use std::sync::Arc;
 
 use thiserror::Error;
 
@@ -12,6 +14,8 @@ pub struct SoundInterface<T: 'static> {
     channel_count: u16,
     stream: pa::Stream<pa::NonBlocking, pa::stream::Output<SampleOutput>>,
     sender: Option<Sender<T>>, // receiver: Option<Receiver<T>,
+    telemetry: TelemetryQueue,
+    limiter: Arc<AtomicBool>,
 }
 
 impl<T> SoundInterface<T> {
@@ -21,8 +25,27 @@ impl<T> SoundInterface<T> {
         sample_rate: u32,
         buffer_size: usize,
         channel_count: u16,
-        mut generator: Box<dyn SoundGenerator<Command = T>>,
+        generator: Box<dyn SoundGenerator<Command = T>>,
     ) -> BackendResult<SoundInterface<T>> {
+        Self::new_with_fade_in(
+            sample_rate,
+            buffer_size,
+            channel_count,
+            FADE_IN_DURATION_DEFAULT,
+            generator,
+        )
+    }
+
+    /// Creates a new backend for sound playback, with a custom fade-in duration (in seconds)
+    /// applied when the playback starts. A `fade_in` of 0.0 turns the fade-in off.
+    pub fn new_with_fade_in(
+        sample_rate: u32,
+        buffer_size: usize,
+        channel_count: u16,
+        fade_in: SampleCalc,
+        generator: Box<dyn SoundGenerator<Command = T>>,
+    ) -> BackendResult<SoundInterface<T>> {
+        let fade_in = get_fade_in(sample_rate, fade_in).map_err(|_| BackendError::FadeInInvalid)?;
         println!("PortAudio version : {}", pa::version());
         println!("PortAudio version text : {:?}", pa::version_text());
         let pa = pa::PortAudio::new()?;
@@ -35,29 +58,31 @@ impl<T> SoundInterface<T> {
         // we won't output out of range samples so don't bother clipping them.
         settings.flags = pa::stream_flags::CLIP_OFF;
 
-        let mut generator_buffer: Vec<SampleCalc> = vec![0.0; buffer_size];
-
+        let limiter = Arc::new(AtomicBool::new(false));
         let (sender, receiver) = ::std::sync::mpsc::channel();
+        let (telemetry_sender, telemetry) = TelemetryQueue::new();
+        let mut output_callback = OutputCallback::new(
+            buffer_size,
+            channel_count,
+            generator,
+            receiver,
+            fade_in,
+            telemetry_sender,
+            limiter.clone(),
+        );
         // This routine will be called by the PortAudio engine when audio is needed. It may
         // called at interrupt level on some machines so don't do anything that could mess
         // up the system like dynamic resource allocation or IO.
-        let callback_fn = move |pa::OutputStreamCallbackArgs { buffer, frames, .. }| {
-            if let Ok(command) = receiver.try_recv() {
-                generator.process_command(command);
+        let callback_fn = move |pa::OutputStreamCallbackArgs { buffer, flags, .. }| {
+            if flags.contains(pa::stream_callback_flags::OUTPUT_UNDERFLOW) {
+                output_callback.report_underrun();
             }
-            generator.get_samples(frames, &mut generator_buffer);
-            let mut idx = 0;
-            for item in generator_buffer.iter().take(frames) {
-                for _ in 0..(channel_count as usize) {
-                    buffer[idx] = *item; // as SampleOutput;
-                    idx += 1;
-                }
+            output_callback.process(buffer);
+            if output_callback.is_failed() {
+                pa::Complete
+            } else {
+                pa::Continue
             }
-            //            for output_frame in buffer.chunks_mut(channel_count) {
-            //                for channel_sample in output_frame {
-            //                    *channel_sample = synthesizer.sample_next();
-            // sender.send(time_start).ok();
-            pa::Continue
         };
 
         // Open a non-blocking stream.
@@ -69,6 +94,8 @@ impl<T> SoundInterface<T> {
             channel_count: channel_count,
             stream: stream,
             sender: Some(sender),
+            telemetry,
+            limiter,
         })
     }
     /// Starts the sound output stream.
@@ -89,6 +116,24 @@ impl<T> SoundInterface<T> {
         }
     }
 
+    /// Turns the soft limiter of the output on or off (it is off by default). It prevents harsh
+    /// digital clipping, see `soft_limit()`.
+    pub fn set_limiter(&mut self, enabled: bool) {
+        self.limiter.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Sets the function receiving the telemetry events. The events are delivered on the host
+    /// thread, by `process_telemetry()`.
+    pub fn set_telemetry_callback(&mut self, callback: fn(TelemetryEvent)) {
+        self.telemetry.set_callback(callback);
+    }
+
+    /// Delivers the queued telemetry events to the registered callback. Without a callback the
+    /// events are dropped. It shall be called periodically from the host thread.
+    pub fn process_telemetry(&self) {
+        self.telemetry.process();
+    }
+
     /// Returns the sample rate of the sond output
     pub fn get_sample_rate(&self) -> u32 {
         self.sample_rate
@@ -124,4 +169,7 @@ pub enum BackendError {
     /// The SoundGenerator is disconnected, could not recieve the command
     #[error("SoundGenerator is disconnected")]
     Disconnected,
+    /// The duration of the fade-in must not be negative.
+    #[error("Invalid fade-in duration")]
+    FadeInInvalid,
 }
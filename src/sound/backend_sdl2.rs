@@ -6,59 +6,21 @@
 use crate::sound::*;
 
 use sdl2::audio::*;
-use std::sync::mpsc::{Receiver, Sender};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Sender;
+use std::sync::Arc;
 
 use thiserror::Error;
 
-/// Struct containing the settings of the callback routine for SDL2
-struct Player<T: 'static + Send> {
-    channel_count: usize,
-    frame_size: usize,
-    generator_buffer: Vec<SampleCalc>,
-    generator: Box<dyn SoundGenerator<Command = T>>,
-    receiver: Receiver<T>,
-}
-
-impl<T> Player<T>
-where
-    T: Send,
-{
-    /// Custom constructor.
-    fn new(
-        spec: AudioSpec,
-        buffer_size: usize,
-        generator: Box<dyn SoundGenerator<Command = T>>,
-        receiver: Receiver<T>,
-    ) -> Player<T> {
-        Player {
-            channel_count: spec.channels as usize,
-            frame_size: buffer_size,
-            generator_buffer: vec![0.0; buffer_size],
-            generator: generator,
-            receiver: receiver,
-        }
-    }
-}
-
-impl<T> AudioCallback for Player<T>
+/// Callback routine for SDL2
+impl<T> AudioCallback for OutputCallback<T>
 where
     T: Send,
 {
     type Channel = f32;
-    /// Callback routine for SDL2
+
     fn callback(&mut self, out: &mut [f32]) {
-        if let Ok(command) = self.receiver.try_recv() {
-            self.generator.process_command(command);
-        }
-        self.generator
-            .get_samples(self.frame_size, &mut self.generator_buffer);
-        let mut idx = 0;
-        for item in self.generator_buffer.iter().take(self.frame_size) {
-            for _ in 0..(self.channel_count) {
-                out[idx] = *item; // as SampleOutput;
-                idx += 1;
-            }
-        }
+        self.process(out);
     }
 }
 
@@ -68,8 +30,10 @@ pub struct SoundInterface<T: 'static + Send> {
     channel_count: u16,
     // sdl_context: ::sdl2::Sdl,
     // sdl_audio_subsystem: ::sdl2::AudioSubsystem,
-    sdl_device: AudioDevice<Player<T>>,
+    sdl_device: AudioDevice<OutputCallback<T>>,
     sender: Option<Sender<T>>, // receiver: Option<Receiver<T>,
+    telemetry: TelemetryQueue,
+    limiter: Arc<AtomicBool>,
 }
 
 impl<T> SoundInterface<T>
@@ -84,6 +48,25 @@ where
         channel_count: u16,
         generator: Box<dyn SoundGenerator<Command = T>>,
     ) -> BackendResult<SoundInterface<T>> {
+        Self::new_with_fade_in(
+            sample_rate,
+            buffer_size,
+            channel_count,
+            FADE_IN_DURATION_DEFAULT,
+            generator,
+        )
+    }
+
+    /// Creates a new backend for sound playback, with a custom fade-in duration (in seconds)
+    /// applied when the playback starts. A `fade_in` of 0.0 turns the fade-in off.
+    pub fn new_with_fade_in(
+        sample_rate: u32,
+        buffer_size: usize,
+        channel_count: u16,
+        fade_in: SampleCalc,
+        generator: Box<dyn SoundGenerator<Command = T>>,
+    ) -> BackendResult<SoundInterface<T>> {
+        let fade_in = get_fade_in(sample_rate, fade_in).map_err(|_| BackendError::FadeInInvalid)?;
         let sdl_context = ::sdl2::init()?;
         let sdl_audio_subsystem = sdl_context.audio()?;
 
@@ -94,9 +77,20 @@ where
         };
 
         let (sender, receiver) = ::std::sync::mpsc::channel();
+        let (telemetry_sender, telemetry) = TelemetryQueue::new();
+        let limiter = Arc::new(AtomicBool::new(false));
+        let limiter_callback = limiter.clone();
 
         let sdl_device = sdl_audio_subsystem.open_playback(None, &desired_spec, |spec| {
-            Player::new(spec, buffer_size, generator, receiver)
+            OutputCallback::new(
+                buffer_size,
+                u16::from(spec.channels),
+                generator,
+                receiver,
+                fade_in,
+                telemetry_sender,
+                limiter_callback,
+            )
         })?;
 
         println!("Stream is created.");
@@ -108,6 +102,8 @@ where
             // sdl_audio_subsystem: sdl_audio_subsystem,
             sdl_device: sdl_device,
             sender: Some(sender),
+            telemetry,
+            limiter,
         })
     }
     /// Starts the sound output stream.
@@ -128,6 +124,24 @@ where
         }
     }
 
+    /// Turns the soft limiter of the output on or off (it is off by default). It prevents harsh
+    /// digital clipping, see `soft_limit()`.
+    pub fn set_limiter(&mut self, enabled: bool) {
+        self.limiter.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Sets the function receiving the telemetry events. The events are delivered on the host
+    /// thread, by `process_telemetry()`.
+    pub fn set_telemetry_callback(&mut self, callback: fn(TelemetryEvent)) {
+        self.telemetry.set_callback(callback);
+    }
+
+    /// Delivers the queued telemetry events to the registered callback. Without a callback the
+    /// events are dropped. It shall be called periodically from the host thread.
+    pub fn process_telemetry(&self) {
+        self.telemetry.process();
+    }
+
     /// Returns the sample rate of the sond output
     pub fn get_sample_rate(&self) -> u32 {
         self.sample_rate
@@ -150,6 +164,9 @@ pub enum BackendError {
     /// The SoundGenerator is disconnected, could not recieve the command
     #[error("The SoundGenerator is disconnected")]
     Disconnected,
+    /// The duration of the fade-in must not be negative.
+    #[error("Invalid fade-in duration")]
+    FadeInInvalid,
 }
 
 impl From<String> for BackendError {
@@ -1,3 +1,5 @@
+#[cfg(feature = "be-cpal")]
+use crate::sound::backend_cpal::*;
 #[cfg(feature = "be-portaudio")]
 use crate::sound::backend_portaudio::*;
 #[cfg(feature = "be-rsoundio")]
@@ -13,7 +15,12 @@ pub type SoundResult<T> = Result<T, Error>;
 /// Error types of the sound module.
 #[derive(Debug, Clone, Error)]
 pub enum Error {
-    #[cfg(any(feature = "be-portaudio", feature = "be-rsoundio", feature = "be-sdl2"))]
+    #[cfg(any(
+        feature = "be-cpal",
+        feature = "be-portaudio",
+        feature = "be-rsoundio",
+        feature = "be-sdl2"
+    ))]
     /// Sound output backend error.
     #[error("Backend error: {0}")]
     Backend(#[from] BackendError),
@@ -50,6 +57,9 @@ pub enum Error {
     /// Amplitude cannot be negative.
     #[error("Invalid amplitude")]
     AmplitudeInvalid,
+    /// Duty cycle must be in the (0.0, 1.0) range.
+    #[error("Invalid duty cycle")]
+    DutyCycleInvalid,
     /// Amplitude change time is not positive.
     #[error("Invalid amplitude change time")]
     AmplitudeTimeInvalid,
@@ -86,6 +96,57 @@ pub enum Error {
     /// Item at the given index does not exist.
     #[error("The item does not exist")]
     ItemInvalid,
+    /// The dry/wet mix ratio must be in the range [0.0, 1.0].
+    #[error("Invalid dry/wet mix")]
+    MixInvalid,
+    /// The line of the given number is malformed in the Scala file.
+    #[error("Invalid Scala file, at line: {0}")]
+    ScalaLineInvalid(usize),
+    /// The inharmonicity coefficient must be non-negative.
+    #[error("Invalid inharmonicity")]
+    InharmonicityInvalid,
+    /// The loudness level must be in the [0.0, 90.0] phon range.
+    #[error("Invalid loudness level")]
+    LoudnessInvalid,
+    /// The modulation index cannot be negative.
+    #[error("Invalid modulation index")]
+    ModulationIndexInvalid,
+    /// The interval is out of the range allowed for the operation.
+    #[error("Interval is out of range")]
+    IntervalInvalid,
+    /// The text can not be parsed into the requested type.
+    #[error("Invalid text format")]
+    ParseInvalid,
+    /// The stereo position must be in the [-1.0, 1.0] range.
+    #[error("Invalid pan")]
+    PanInvalid,
+    /// The Q (quality) factor of a filter must be positive.
+    #[error("Invalid Q factor")]
+    QualityInvalid,
+    /// The feedback ratio must be in the range [0.0, 1.0).
+    #[error("Invalid feedback")]
+    FeedbackInvalid,
+    /// The drive of a waveshaper must be positive.
+    #[error("Invalid drive")]
+    DriveInvalid,
+    /// Reading or writing a file failed.
+    #[error("File error: {0}")]
+    File(String),
+    /// The loop must be a non-empty range inside the sample buffer.
+    #[error("Invalid loop range")]
+    LoopInvalid,
+    /// The MIDI file could not be parsed, or it is not supported.
+    #[error("MIDI error: {0}")]
+    Midi(String),
+    /// The threshold of a dynamics processor must be a finite, non-positive dB value.
+    #[error("Invalid threshold")]
+    ThresholdInvalid,
+    /// The compression ratio must be at least 1.0.
+    #[error("Invalid ratio")]
+    RatioInvalid,
+    /// Bars and beats are counted from 1, and the beat must be inside the bar.
+    #[error("Invalid bar or beat position")]
+    PositionInvalid,
     /// Overflow occured during calculations.
     #[error("Overflow")]
     Overflow,
@@ -0,0 +1,1219 @@
+use crate::sound::*;
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+/// Common interface of the effects, which process already existing samples. The output is a
+/// blend of the processed (wet) and the unprocessed (dry) signal.
+pub trait Effect {
+    /// Sets the ratio of the processed (wet) signal in the output. It must be in the range
+    /// [0.0, 1.0], where 0.0 leaves the signal unchanged.
+    fn set_mix(&mut self, wet: SampleCalc) -> SoundResult<()>;
+
+    /// Processes the samples in place.
+    fn process(&mut self, samples: &mut [SampleCalc]) -> SoundResult<()>;
+
+    /// Provides the latency of the effect, in samples.
+    fn get_latency(&self) -> usize {
+        0
+    }
+}
+
+/// Checks if the given value is a valid dry/wet mix ratio.
+pub fn is_valid_mix(wet: SampleCalc) -> SoundResult<()> {
+    if !(0.0..=1.0).contains(&wet) {
+        return Err(Error::MixInvalid);
+    }
+    Ok(())
+}
+
+/// Blends the processed (wet) `samples` with the unprocessed (`dry`) ones, according to the
+/// `wet` ratio. The result is written back into `samples`.
+pub fn mix_dry_wet(
+    dry: &[SampleCalc],
+    wet: SampleCalc,
+    samples: &mut [SampleCalc],
+) -> SoundResult<()> {
+    if dry.len() != samples.len() {
+        return Err(Error::BufferSize);
+    }
+    for (item, dry_item) in samples.iter_mut().zip(dry) {
+        *item = *dry_item + wet * (*item - *dry_item);
+    }
+    Ok(())
+}
+
+/// Processes the `samples` in place with `process_wet`, and blends the result with the
+/// unprocessed samples according to the `wet` ratio. The unprocessed samples are copied into the
+/// `dry_buffer`, which is skipped for a fully wet mix.
+fn process_dry_wet<F>(
+    samples: &mut [SampleCalc],
+    wet: SampleCalc,
+    dry_buffer: &RefCell<Vec<SampleCalc>>,
+    process_wet: F,
+) -> SoundResult<()>
+where
+    F: FnOnce(&mut [SampleCalc]) -> SoundResult<()>,
+{
+    if wet >= 1.0 {
+        return process_wet(samples);
+    }
+    let mut dry = dry_buffer.borrow_mut();
+    dry.clear();
+    dry.extend_from_slice(samples);
+    process_wet(samples)?;
+    mix_dry_wet(&dry, wet, samples)
+}
+
+/// An ordered chain of effects. The samples are processed by each effect in sequence.
+#[derive(Default)]
+pub struct EffectChain {
+    effects: Vec<Box<dyn Effect>>,
+}
+
+impl EffectChain {
+    /// custom constructor
+    pub fn new() -> EffectChain {
+        EffectChain {
+            effects: Vec::new(),
+        }
+    }
+
+    /// Adds a new effect to the end of the chain.
+    pub fn add(&mut self, effect: Box<dyn Effect>) -> &mut EffectChain {
+        self.effects.push(effect);
+        self
+    }
+
+    /// Provides the number of effects in the chain.
+    pub fn len(&self) -> usize {
+        self.effects.len()
+    }
+
+    /// True, if there is no effect in the chain.
+    pub fn is_empty(&self) -> bool {
+        self.effects.is_empty()
+    }
+}
+
+impl Effect for EffectChain {
+    /// Sets the dry/wet mix of all the effects in the chain.
+    fn set_mix(&mut self, wet: SampleCalc) -> SoundResult<()> {
+        is_valid_mix(wet)?;
+        for effect in self.effects.iter_mut() {
+            effect.set_mix(wet)?;
+        }
+        Ok(())
+    }
+
+    fn process(&mut self, samples: &mut [SampleCalc]) -> SoundResult<()> {
+        for effect in self.effects.iter_mut() {
+            effect.process(samples)?;
+        }
+        Ok(())
+    }
+
+    fn get_latency(&self) -> usize {
+        self.effects.iter().map(|effect| effect.get_latency()).sum()
+    }
+}
+
+/// Checks if the cutoff frequency is positive and below the Nyquist frequency.
+fn is_valid_cutoff(sample_rate: SampleCalc, cutoff: SampleCalc) -> SoundResult<()> {
+    if cutoff <= 0.0 {
+        return Err(Error::FrequencyInvalid);
+    }
+    if cutoff >= sample_rate * 0.5 {
+        return Err(Error::FrequencyTooHigh);
+    }
+    Ok(())
+}
+
+/// One-pole low-pass filter: `y[n] = y[n-1] + a * (x[n] - y[n-1])`. It attenuates the
+/// frequencies above the cutoff by 6 dB per octave.
+#[derive(Clone)]
+pub struct LowPassOnePole {
+    timer: Timer,
+    sample_rate: SampleCalc,
+    /// The filtered sound structure.
+    inner: Rc<dyn SoundStructure>,
+    /// The smoothing coefficient `a`, derived from the cutoff frequency.
+    coefficient: Cell<SampleCalc>,
+    /// The last output sample, `y[n-1]`.
+    output_last: Cell<SampleCalc>,
+    /// The ratio of the processed (wet) signal in the output.
+    mix: Cell<SampleCalc>,
+    /// The unprocessed samples, for the dry/wet mix.
+    dry_buffer: RefCell<Vec<SampleCalc>>,
+}
+
+impl LowPassOnePole {
+    /// custom constructor
+    pub fn new(
+        sample_rate: SampleCalc,
+        buffer_size: usize,
+        cutoff: SampleCalc,
+        inner: Rc<dyn SoundStructure>,
+    ) -> SoundResult<LowPassOnePole> {
+        let filter = LowPassOnePole {
+            timer: Timer::new(sample_rate)?,
+            sample_rate,
+            inner,
+            coefficient: Cell::new(1.0),
+            output_last: Cell::new(0.0),
+            mix: Cell::new(1.0),
+            dry_buffer: RefCell::new(Vec::with_capacity(buffer_size)),
+        };
+        filter.set_cutoff(cutoff)?;
+        Ok(filter)
+    }
+
+    /// Sets the cutoff frequency, which must be below the Nyquist frequency.
+    pub fn set_cutoff(&self, cutoff: SampleCalc) -> SoundResult<()> {
+        is_valid_cutoff(self.sample_rate, cutoff)?;
+        self.coefficient
+            .set(1.0 - (-PI2 * cutoff / self.sample_rate).exp());
+        Ok(())
+    }
+
+    /// Filters the samples in place.
+    fn process_wet(&self, samples: &mut [SampleCalc]) -> SoundResult<()> {
+        let coefficient = self.coefficient.get();
+        let mut output_last = self.output_last.get();
+        for item in samples.iter_mut() {
+            output_last += coefficient * (*item - output_last);
+            *item = output_last;
+        }
+        self.output_last.set(output_last);
+        Ok(())
+    }
+}
+
+impl HasTimer for LowPassOnePole {
+    fn set_timing(&self, timing: TimingOption) -> SoundResult<()> {
+        self.timer.set_timing(timing)?;
+        self.restart();
+        Ok(())
+    }
+
+    fn get_timing(&self) -> TimingOption {
+        self.timer.get_timing()
+    }
+
+    fn restart(&self) {
+        self.timer.restart();
+        self.output_last.set(0.0);
+        self.inner.restart();
+    }
+
+    fn apply_parent_timing(&self, parent_timing: TimingOption) -> SoundResult<()> {
+        self.timer.apply_parent_timing(parent_timing)?;
+        self.inner.apply_parent_timing(self.timer.get_timing())
+    }
+}
+
+impl SoundStructure for LowPassOnePole {
+    fn get(&self, base_frequency: &[SampleCalc], result: &mut [SampleCalc]) -> SoundResult<()> {
+        if base_frequency.len() != result.len() {
+            return Err(Error::BufferSize);
+        }
+        let inner_result = self.inner.get(base_frequency, result);
+        let completed = match inner_result {
+            Ok(()) => result.len(),
+            Err(Error::ItemsCompleted(completed)) => completed,
+            Err(e) => return Err(e),
+        };
+        process_dry_wet(
+            &mut result[..completed],
+            self.mix.get(),
+            &self.dry_buffer,
+            |samples| self.process_wet(samples),
+        )?;
+        let timer_result = self.timer.jump_by_time(completed);
+        inner_result.and(timer_result)
+    }
+}
+
+impl Effect for LowPassOnePole {
+    fn set_mix(&mut self, wet: SampleCalc) -> SoundResult<()> {
+        is_valid_mix(wet)?;
+        self.mix.set(wet);
+        Ok(())
+    }
+
+    fn process(&mut self, samples: &mut [SampleCalc]) -> SoundResult<()> {
+        process_dry_wet(samples, self.mix.get(), &self.dry_buffer, |samples| {
+            self.process_wet(samples)
+        })
+    }
+}
+
+/// The filter types of `Biquad`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum BiquadMode {
+    /// Passes the frequencies below the cutoff.
+    LowPass,
+    /// Passes the frequencies above the cutoff.
+    HighPass,
+    /// Passes the frequencies around the cutoff (with 0 dB peak gain).
+    BandPass,
+    /// Rejects the frequencies around the cutoff.
+    Notch,
+}
+
+/// Normalized coefficients of the biquad difference equation.
+#[derive(Debug, Copy, Clone)]
+struct BiquadCoefficients {
+    b0: SampleCalc,
+    b1: SampleCalc,
+    b2: SampleCalc,
+    a1: SampleCalc,
+    a2: SampleCalc,
+}
+
+/// Second order (biquad) filter, with the coefficients of the
+/// [Audio EQ Cookbook](https://www.w3.org/TR/audio-eq-cookbook/) by Robert Bristow-Johnson.
+#[derive(Clone)]
+pub struct Biquad {
+    timer: Timer,
+    sample_rate: SampleCalc,
+    /// The filtered sound structure.
+    inner: Rc<dyn SoundStructure>,
+    mode: Cell<BiquadMode>,
+    cutoff: Cell<SampleCalc>,
+    q: Cell<SampleCalc>,
+    coefficients: Cell<BiquadCoefficients>,
+    /// The two delay states (transposed direct form II).
+    state: RefCell<[SampleCalc; 2]>,
+    /// The ratio of the processed (wet) signal in the output.
+    mix: Cell<SampleCalc>,
+    /// The unprocessed samples, for the dry/wet mix.
+    dry_buffer: RefCell<Vec<SampleCalc>>,
+}
+
+impl Biquad {
+    /// custom constructor
+    pub fn new(
+        sample_rate: SampleCalc,
+        buffer_size: usize,
+        mode: BiquadMode,
+        cutoff: SampleCalc,
+        q: SampleCalc,
+        inner: Rc<dyn SoundStructure>,
+    ) -> SoundResult<Biquad> {
+        is_valid_cutoff(sample_rate, cutoff)?;
+        if q <= 0.0 {
+            return Err(Error::QualityInvalid);
+        }
+        let filter = Biquad {
+            timer: Timer::new(sample_rate)?,
+            sample_rate,
+            inner,
+            mode: Cell::new(mode),
+            cutoff: Cell::new(cutoff),
+            q: Cell::new(q),
+            coefficients: Cell::new(BiquadCoefficients {
+                b0: 1.0,
+                b1: 0.0,
+                b2: 0.0,
+                a1: 0.0,
+                a2: 0.0,
+            }),
+            state: RefCell::new([0.0; 2]),
+            mix: Cell::new(1.0),
+            dry_buffer: RefCell::new(Vec::with_capacity(buffer_size)),
+        };
+        filter.calculate_coefficients();
+        Ok(filter)
+    }
+
+    /// Sets the cutoff (or center) frequency, which must be below the Nyquist frequency.
+    pub fn set_cutoff(&self, cutoff: SampleCalc) -> SoundResult<()> {
+        is_valid_cutoff(self.sample_rate, cutoff)?;
+        self.cutoff.set(cutoff);
+        self.calculate_coefficients();
+        Ok(())
+    }
+
+    /// Sets the Q (quality) factor, which must be positive. Higher values give a narrower band,
+    /// or a resonant peak at the cutoff.
+    pub fn set_q(&self, q: SampleCalc) -> SoundResult<()> {
+        if q <= 0.0 {
+            return Err(Error::QualityInvalid);
+        }
+        self.q.set(q);
+        self.calculate_coefficients();
+        Ok(())
+    }
+
+    /// Sets the filter type.
+    pub fn set_mode(&self, mode: BiquadMode) {
+        self.mode.set(mode);
+        self.calculate_coefficients();
+    }
+
+    fn calculate_coefficients(&self) {
+        let omega = PI2 * self.cutoff.get() / self.sample_rate;
+        let cos_omega = omega.cos();
+        let alpha = omega.sin() / (2.0 * self.q.get());
+        let (b0, b1, b2) = match self.mode.get() {
+            BiquadMode::LowPass => (
+                (1.0 - cos_omega) * 0.5,
+                1.0 - cos_omega,
+                (1.0 - cos_omega) * 0.5,
+            ),
+            BiquadMode::HighPass => (
+                (1.0 + cos_omega) * 0.5,
+                -(1.0 + cos_omega),
+                (1.0 + cos_omega) * 0.5,
+            ),
+            BiquadMode::BandPass => (alpha, 0.0, -alpha),
+            BiquadMode::Notch => (1.0, -2.0 * cos_omega, 1.0),
+        };
+        let a0 = 1.0 + alpha;
+        self.coefficients.set(BiquadCoefficients {
+            b0: b0 / a0,
+            b1: b1 / a0,
+            b2: b2 / a0,
+            a1: -2.0 * cos_omega / a0,
+            a2: (1.0 - alpha) / a0,
+        });
+    }
+
+    /// Filters the samples in place.
+    fn process_wet(&self, samples: &mut [SampleCalc]) -> SoundResult<()> {
+        let c = self.coefficients.get();
+        let mut state = self.state.borrow_mut();
+        for item in samples.iter_mut() {
+            let input = *item;
+            let output = c.b0 * input + state[0];
+            state[0] = c.b1 * input - c.a1 * output + state[1];
+            state[1] = c.b2 * input - c.a2 * output;
+            *item = output;
+        }
+        Ok(())
+    }
+}
+
+impl HasTimer for Biquad {
+    fn set_timing(&self, timing: TimingOption) -> SoundResult<()> {
+        self.timer.set_timing(timing)?;
+        self.restart();
+        Ok(())
+    }
+
+    fn get_timing(&self) -> TimingOption {
+        self.timer.get_timing()
+    }
+
+    fn restart(&self) {
+        self.timer.restart();
+        *self.state.borrow_mut() = [0.0; 2];
+        self.inner.restart();
+    }
+
+    fn apply_parent_timing(&self, parent_timing: TimingOption) -> SoundResult<()> {
+        self.timer.apply_parent_timing(parent_timing)?;
+        self.inner.apply_parent_timing(self.timer.get_timing())
+    }
+}
+
+impl SoundStructure for Biquad {
+    fn get(&self, base_frequency: &[SampleCalc], result: &mut [SampleCalc]) -> SoundResult<()> {
+        if base_frequency.len() != result.len() {
+            return Err(Error::BufferSize);
+        }
+        let inner_result = self.inner.get(base_frequency, result);
+        let completed = match inner_result {
+            Ok(()) => result.len(),
+            Err(Error::ItemsCompleted(completed)) => completed,
+            Err(e) => return Err(e),
+        };
+        process_dry_wet(
+            &mut result[..completed],
+            self.mix.get(),
+            &self.dry_buffer,
+            |samples| self.process_wet(samples),
+        )?;
+        let timer_result = self.timer.jump_by_time(completed);
+        inner_result.and(timer_result)
+    }
+}
+
+impl Effect for Biquad {
+    fn set_mix(&mut self, wet: SampleCalc) -> SoundResult<()> {
+        is_valid_mix(wet)?;
+        self.mix.set(wet);
+        Ok(())
+    }
+
+    fn process(&mut self, samples: &mut [SampleCalc]) -> SoundResult<()> {
+        process_dry_wet(samples, self.mix.get(), &self.dry_buffer, |samples| {
+            self.process_wet(samples)
+        })
+    }
+}
+
+/// Checks if the given value is a valid feedback ratio. At 1.0 or above the gain would grow
+/// without limit.
+fn is_valid_feedback(feedback: SampleCalc) -> SoundResult<()> {
+    if !(0.0..1.0).contains(&feedback) {
+        return Err(Error::FeedbackInvalid);
+    }
+    Ok(())
+}
+
+/// Delay (echo) effect. The delayed copy of the sound is fed back into the delay line, so the
+/// echoes repeat with decreasing amplitude.
+#[derive(Clone)]
+pub struct Delay {
+    timer: Timer,
+    /// The delayed sound structure.
+    inner: Rc<dyn SoundStructure>,
+    feedback: Cell<SampleCalc>,
+    /// The ratio of the delayed (wet) signal in the output.
+    mix: Cell<SampleCalc>,
+    /// The undelayed samples, for the dry/wet mix.
+    dry_buffer: RefCell<Vec<SampleCalc>>,
+    /// Circular buffer of the delayed samples.
+    delay_line: RefCell<Vec<SampleCalc>>,
+    position: Cell<usize>,
+}
+
+impl Delay {
+    /// custom constructor
+    pub fn new(
+        sample_rate: SampleCalc,
+        delay: SampleCalc,
+        feedback: SampleCalc,
+        mix: SampleCalc,
+        inner: Rc<dyn SoundStructure>,
+    ) -> SoundResult<Delay> {
+        let sample_time = get_sample_time(sample_rate)?;
+        if delay < sample_time {
+            return Err(Error::DurationInvalid);
+        }
+        is_valid_feedback(feedback)?;
+        is_valid_mix(mix)?;
+        Ok(Delay {
+            timer: Timer::new(sample_rate)?,
+            inner,
+            feedback: Cell::new(feedback),
+            mix: Cell::new(mix),
+            dry_buffer: RefCell::new(Vec::with_capacity(BUFFER_SIZE_DEFAULT)),
+            delay_line: RefCell::new(vec![0.0; (delay / sample_time).round() as usize]),
+            position: Cell::new(0),
+        })
+    }
+
+    /// Sets the feedback ratio, it must be in the range [0.0, 1.0).
+    pub fn set_feedback(&self, feedback: SampleCalc) -> SoundResult<()> {
+        is_valid_feedback(feedback)?;
+        self.feedback.set(feedback);
+        Ok(())
+    }
+
+    /// Replaces the samples with the delayed ones, and feeds them into the delay line.
+    fn process_wet(&self, samples: &mut [SampleCalc]) -> SoundResult<()> {
+        let feedback = self.feedback.get();
+        let mut delay_line = self.delay_line.borrow_mut();
+        let mut position = self.position.get();
+        for item in samples.iter_mut() {
+            let delayed = delay_line[position];
+            delay_line[position] = *item + feedback * delayed;
+            position = (position + 1) % delay_line.len();
+            *item = delayed;
+        }
+        self.position.set(position);
+        Ok(())
+    }
+}
+
+impl HasTimer for Delay {
+    fn set_timing(&self, timing: TimingOption) -> SoundResult<()> {
+        self.timer.set_timing(timing)?;
+        self.restart();
+        Ok(())
+    }
+
+    fn get_timing(&self) -> TimingOption {
+        self.timer.get_timing()
+    }
+
+    fn restart(&self) {
+        self.timer.restart();
+        for item in self.delay_line.borrow_mut().iter_mut() {
+            *item = 0.0;
+        }
+        self.position.set(0);
+        self.inner.restart();
+    }
+
+    fn apply_parent_timing(&self, parent_timing: TimingOption) -> SoundResult<()> {
+        self.timer.apply_parent_timing(parent_timing)?;
+        self.inner.apply_parent_timing(self.timer.get_timing())
+    }
+}
+
+impl SoundStructure for Delay {
+    fn get(&self, base_frequency: &[SampleCalc], result: &mut [SampleCalc]) -> SoundResult<()> {
+        if base_frequency.len() != result.len() {
+            return Err(Error::BufferSize);
+        }
+        let inner_result = self.inner.get(base_frequency, result);
+        let completed = match inner_result {
+            Ok(()) => result.len(),
+            Err(Error::ItemsCompleted(completed)) => completed,
+            Err(e) => return Err(e),
+        };
+        process_dry_wet(
+            &mut result[..completed],
+            self.mix.get(),
+            &self.dry_buffer,
+            |samples| self.process_wet(samples),
+        )?;
+        let timer_result = self.timer.jump_by_time(completed);
+        inner_result.and(timer_result)
+    }
+}
+
+impl Effect for Delay {
+    fn set_mix(&mut self, wet: SampleCalc) -> SoundResult<()> {
+        is_valid_mix(wet)?;
+        self.mix.set(wet);
+        Ok(())
+    }
+
+    fn process(&mut self, samples: &mut [SampleCalc]) -> SoundResult<()> {
+        process_dry_wet(samples, self.mix.get(), &self.dry_buffer, |samples| {
+            self.process_wet(samples)
+        })
+    }
+}
+
+/// The channels of a stereo sound.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum StereoSide {
+    /// Left channel.
+    Left,
+    /// Right channel.
+    Right,
+}
+
+/// Stereo widening with the [Haas effect](https://en.wikipedia.org/wiki/Precedence_effect):
+/// one channel is delayed by a few milliseconds (up to ~35 ms), so the sound is perceived as
+/// wide, but still coming from the direction of the other channel.
+#[derive(Clone)]
+pub struct Haas {
+    timer: Timer,
+    /// The widened sound structure (e.g. a `MonoToStereo`).
+    inner: Rc<dyn StereoStructure>,
+    /// The delayed channel.
+    side: Cell<StereoSide>,
+    /// Circular buffer of the delayed samples.
+    delay_line: RefCell<Vec<SampleCalc>>,
+    position: Cell<usize>,
+}
+
+impl Haas {
+    /// custom constructor
+    /// The `delay` is given in seconds.
+    pub fn new(
+        sample_rate: SampleCalc,
+        delay: SampleCalc,
+        side: StereoSide,
+        inner: Rc<dyn StereoStructure>,
+    ) -> SoundResult<Haas> {
+        let sample_time = get_sample_time(sample_rate)?;
+        if delay < sample_time || !delay.is_finite() {
+            return Err(Error::DurationInvalid);
+        }
+        Ok(Haas {
+            timer: Timer::new(sample_rate)?,
+            inner,
+            side: Cell::new(side),
+            delay_line: RefCell::new(vec![0.0; (delay / sample_time).round() as usize]),
+            position: Cell::new(0),
+        })
+    }
+
+    /// Sets the delayed channel, and clears the delay line.
+    pub fn set_side(&self, side: StereoSide) {
+        self.side.set(side);
+        self.restart();
+    }
+
+    /// Returns the delayed channel.
+    pub fn get_side(&self) -> StereoSide {
+        self.side.get()
+    }
+}
+
+impl HasTimer for Haas {
+    fn set_timing(&self, timing: TimingOption) -> SoundResult<()> {
+        self.timer.set_timing(timing)?;
+        self.restart();
+        Ok(())
+    }
+
+    fn get_timing(&self) -> TimingOption {
+        self.timer.get_timing()
+    }
+
+    fn restart(&self) {
+        self.timer.restart();
+        for item in self.delay_line.borrow_mut().iter_mut() {
+            *item = 0.0;
+        }
+        self.position.set(0);
+        self.inner.restart();
+    }
+
+    fn apply_parent_timing(&self, parent_timing: TimingOption) -> SoundResult<()> {
+        self.timer.apply_parent_timing(parent_timing)?;
+        self.inner.apply_parent_timing(self.timer.get_timing())
+    }
+}
+
+impl StereoStructure for Haas {
+    fn get_stereo(
+        &self,
+        base_frequency: &[SampleCalc],
+        left: &mut [SampleCalc],
+        right: &mut [SampleCalc],
+    ) -> SoundResult<()> {
+        let inner_result = self.inner.get_stereo(base_frequency, left, right);
+        let completed = match inner_result {
+            Ok(()) => base_frequency.len(),
+            Err(Error::ItemsCompleted(completed)) => completed,
+            Err(e) => return Err(e),
+        };
+        let delayed_channel = match self.side.get() {
+            StereoSide::Left => left,
+            StereoSide::Right => right,
+        };
+        let mut delay_line = self.delay_line.borrow_mut();
+        let mut position = self.position.get();
+        for item in delayed_channel[..completed].iter_mut() {
+            let delayed = delay_line[position];
+            delay_line[position] = *item;
+            position = (position + 1) % delay_line.len();
+            *item = delayed;
+        }
+        self.position.set(position);
+        let timer_result = self.timer.jump_by_time(completed);
+        inner_result.and(timer_result)
+    }
+}
+
+/// Soft clipper, which saturates the sound with the `tanh(drive * x) / tanh(drive)` function.
+/// The renormalization keeps the full scale at ±1.0, and larger peaks are limited to
+/// `±1.0 / tanh(drive)`. Low drive values are nearly transparent, high ones give a distorted
+/// sound.
+#[derive(Clone)]
+pub struct Waveshaper {
+    /// The shaped sound structure.
+    inner: Rc<dyn SoundStructure>,
+    drive: Cell<SampleCalc>,
+    /// `1.0 / tanh(drive)`
+    normalizer: Cell<SampleCalc>,
+    /// The ratio of the processed (wet) signal in the output.
+    mix: Cell<SampleCalc>,
+    /// The unprocessed samples, for the dry/wet mix.
+    dry_buffer: RefCell<Vec<SampleCalc>>,
+}
+
+impl Waveshaper {
+    /// custom constructor
+    pub fn new(drive: SampleCalc, inner: Rc<dyn SoundStructure>) -> SoundResult<Waveshaper> {
+        let waveshaper = Waveshaper {
+            inner,
+            drive: Cell::new(1.0),
+            normalizer: Cell::new(1.0),
+            mix: Cell::new(1.0),
+            dry_buffer: RefCell::new(Vec::with_capacity(BUFFER_SIZE_DEFAULT)),
+        };
+        waveshaper.set_drive(drive)?;
+        Ok(waveshaper)
+    }
+
+    /// Sets the drive, which must be positive.
+    pub fn set_drive(&self, drive: SampleCalc) -> SoundResult<()> {
+        if drive <= 0.0 || !drive.is_finite() {
+            return Err(Error::DriveInvalid);
+        }
+        self.drive.set(drive);
+        self.normalizer.set(1.0 / drive.tanh());
+        Ok(())
+    }
+
+    /// Shapes the samples in place.
+    fn process_wet(&self, samples: &mut [SampleCalc]) -> SoundResult<()> {
+        let drive = self.drive.get();
+        let normalizer = self.normalizer.get();
+        for item in samples.iter_mut() {
+            *item = (drive * *item).tanh() * normalizer;
+        }
+        Ok(())
+    }
+}
+
+/// The waveshaper has no timing on its own, it uses the timing of the inner sound structure.
+impl HasTimer for Waveshaper {
+    fn set_timing(&self, timing: TimingOption) -> SoundResult<()> {
+        self.inner.set_timing(timing)
+    }
+
+    fn get_timing(&self) -> TimingOption {
+        self.inner.get_timing()
+    }
+
+    fn restart(&self) {
+        self.inner.restart();
+    }
+
+    fn apply_parent_timing(&self, parent_timing: TimingOption) -> SoundResult<()> {
+        self.inner.apply_parent_timing(parent_timing)
+    }
+}
+
+impl SoundStructure for Waveshaper {
+    fn get(&self, base_frequency: &[SampleCalc], result: &mut [SampleCalc]) -> SoundResult<()> {
+        let inner_result = self.inner.get(base_frequency, result);
+        let completed = match inner_result {
+            Ok(()) => result.len(),
+            Err(Error::ItemsCompleted(completed)) => completed,
+            Err(e) => return Err(e),
+        };
+        process_dry_wet(
+            &mut result[..completed],
+            self.mix.get(),
+            &self.dry_buffer,
+            |samples| self.process_wet(samples),
+        )?;
+        inner_result
+    }
+}
+
+impl Effect for Waveshaper {
+    fn set_mix(&mut self, wet: SampleCalc) -> SoundResult<()> {
+        is_valid_mix(wet)?;
+        self.mix.set(wet);
+        Ok(())
+    }
+
+    fn process(&mut self, samples: &mut [SampleCalc]) -> SoundResult<()> {
+        process_dry_wet(samples, self.mix.get(), &self.dry_buffer, |samples| {
+            self.process_wet(samples)
+        })
+    }
+}
+
+/// [DC blocker](https://en.wikipedia.org/wiki/DC_bias): `y[n] = x[n] - x[n-1] + R * y[n-1]`.
+/// It removes the constant offset, which can accumulate in additive or asymmetrically
+/// shaped sounds. The pole `R` sets the cutoff frequency (about 38 Hz for 0.995 at 48 kHz).
+#[derive(Clone)]
+pub struct DcBlocker {
+    /// The filtered sound structure.
+    inner: Rc<dyn SoundStructure>,
+    /// The pole `R`.
+    pole: Cell<SampleCalc>,
+    /// The last input sample, `x[n-1]`.
+    input_last: Cell<SampleCalc>,
+    /// The last output sample, `y[n-1]`.
+    output_last: Cell<SampleCalc>,
+    /// The ratio of the processed (wet) signal in the output.
+    mix: Cell<SampleCalc>,
+    /// The unprocessed samples, for the dry/wet mix.
+    dry_buffer: RefCell<Vec<SampleCalc>>,
+}
+
+impl DcBlocker {
+    /// custom constructor
+    /// The pole is `DC_BLOCKER_POLE_DEFAULT`.
+    pub fn new(inner: Rc<dyn SoundStructure>) -> DcBlocker {
+        DcBlocker {
+            inner,
+            pole: Cell::new(DC_BLOCKER_POLE_DEFAULT),
+            input_last: Cell::new(0.0),
+            output_last: Cell::new(0.0),
+            mix: Cell::new(1.0),
+            dry_buffer: RefCell::new(Vec::with_capacity(BUFFER_SIZE_DEFAULT)),
+        }
+    }
+
+    /// Sets the pole, which must be in the range [0.0, 1.0).
+    pub fn set_pole(&self, pole: SampleCalc) -> SoundResult<()> {
+        is_valid_feedback(pole)?;
+        self.pole.set(pole);
+        Ok(())
+    }
+
+    /// Filters the samples in place.
+    fn process_wet(&self, samples: &mut [SampleCalc]) -> SoundResult<()> {
+        let pole = self.pole.get();
+        let mut input_last = self.input_last.get();
+        let mut output_last = self.output_last.get();
+        for item in samples.iter_mut() {
+            output_last = *item - input_last + pole * output_last;
+            input_last = *item;
+            *item = output_last;
+        }
+        self.input_last.set(input_last);
+        self.output_last.set(output_last);
+        Ok(())
+    }
+}
+
+/// The DC blocker has no timing on its own, it uses the timing of the inner sound structure.
+impl HasTimer for DcBlocker {
+    fn set_timing(&self, timing: TimingOption) -> SoundResult<()> {
+        self.inner.set_timing(timing)
+    }
+
+    fn get_timing(&self) -> TimingOption {
+        self.inner.get_timing()
+    }
+
+    fn restart(&self) {
+        self.input_last.set(0.0);
+        self.output_last.set(0.0);
+        self.inner.restart();
+    }
+
+    fn apply_parent_timing(&self, parent_timing: TimingOption) -> SoundResult<()> {
+        self.inner.apply_parent_timing(parent_timing)
+    }
+}
+
+impl SoundStructure for DcBlocker {
+    fn get(&self, base_frequency: &[SampleCalc], result: &mut [SampleCalc]) -> SoundResult<()> {
+        let inner_result = self.inner.get(base_frequency, result);
+        let completed = match inner_result {
+            Ok(()) => result.len(),
+            Err(Error::ItemsCompleted(completed)) => completed,
+            Err(e) => return Err(e),
+        };
+        process_dry_wet(
+            &mut result[..completed],
+            self.mix.get(),
+            &self.dry_buffer,
+            |samples| self.process_wet(samples),
+        )?;
+        inner_result
+    }
+}
+
+impl Effect for DcBlocker {
+    fn set_mix(&mut self, wet: SampleCalc) -> SoundResult<()> {
+        is_valid_mix(wet)?;
+        self.mix.set(wet);
+        Ok(())
+    }
+
+    fn process(&mut self, samples: &mut [SampleCalc]) -> SoundResult<()> {
+        process_dry_wet(samples, self.mix.get(), &self.dry_buffer, |samples| {
+            self.process_wet(samples)
+        })
+    }
+}
+
+/// [Dynamic range compressor](https://en.wikipedia.org/wiki/Dynamic_range_compression): the
+/// level above the threshold is reduced by the ratio. The level is detected by an
+/// `EnvelopeFollower`, so the attack and release times shape the transients too.
+#[derive(Clone)]
+pub struct Compressor {
+    /// The compressed sound structure.
+    inner: Rc<dyn SoundStructure>,
+    follower: EnvelopeFollower,
+    /// The threshold as amplitude (converted from dB).
+    threshold: Cell<SampleCalc>,
+    /// The exponent of the gain reduction: `1.0 / ratio - 1.0`.
+    exponent: Cell<SampleCalc>,
+    envelope_buffer: RefCell<Vec<SampleCalc>>,
+    /// The ratio of the processed (wet) signal in the output.
+    mix: Cell<SampleCalc>,
+    /// The unprocessed samples, for the dry/wet mix.
+    dry_buffer: RefCell<Vec<SampleCalc>>,
+}
+
+impl Compressor {
+    /// custom constructor
+    /// The `threshold` is given in dBFS, the `attack` and `release` are the time constants of
+    /// the level detection (in seconds).
+    pub fn new(
+        sample_rate: SampleCalc,
+        buffer_size: usize,
+        threshold: SampleCalc,
+        ratio: SampleCalc,
+        attack: SampleCalc,
+        release: SampleCalc,
+        inner: Rc<dyn SoundStructure>,
+    ) -> SoundResult<Compressor> {
+        let compressor = Compressor {
+            inner,
+            follower: EnvelopeFollower::new(sample_rate, attack, release)?,
+            threshold: Cell::new(1.0),
+            exponent: Cell::new(0.0),
+            envelope_buffer: RefCell::new(vec![0.0; buffer_size]),
+            mix: Cell::new(1.0),
+            dry_buffer: RefCell::new(Vec::with_capacity(buffer_size)),
+        };
+        compressor.set_threshold(threshold)?;
+        compressor.set_ratio(ratio)?;
+        Ok(compressor)
+    }
+
+    /// Sets the threshold (in dBFS), the level above it is compressed.
+    pub fn set_threshold(&self, threshold: SampleCalc) -> SoundResult<()> {
+        if threshold > 0.0 || !threshold.is_finite() {
+            return Err(Error::ThresholdInvalid);
+        }
+        let ten: SampleCalc = 10.0;
+        self.threshold.set(ten.powf(threshold / 20.0));
+        Ok(())
+    }
+
+    /// Sets the compression ratio, e.g. 4.0 means 4 dB level increase above the threshold
+    /// results in 1 dB output increase.
+    pub fn set_ratio(&self, ratio: SampleCalc) -> SoundResult<()> {
+        if ratio < 1.0 || ratio.is_nan() {
+            return Err(Error::RatioInvalid);
+        }
+        self.exponent.set(1.0 / ratio - 1.0);
+        Ok(())
+    }
+
+    /// Sets the attack time constant of the level detection (in seconds).
+    pub fn set_attack(&self, attack: SampleCalc) -> SoundResult<()> {
+        self.follower.set_attack(attack)
+    }
+
+    /// Sets the release time constant of the level detection (in seconds).
+    pub fn set_release(&self, release: SampleCalc) -> SoundResult<()> {
+        self.follower.set_release(release)
+    }
+
+    /// Compresses the samples in place.
+    fn process_wet(&self, samples: &mut [SampleCalc]) -> SoundResult<()> {
+        let mut envelope_reserved = self.envelope_buffer.borrow_mut();
+        if samples.len() > envelope_reserved.len() {
+            return Err(Error::BufferSize);
+        }
+        let envelope = &mut envelope_reserved[..samples.len()];
+        self.follower.process(samples, envelope)?;
+        let threshold = self.threshold.get();
+        let exponent = self.exponent.get();
+        for (item, level) in samples.iter_mut().zip(envelope.iter()) {
+            if *level > threshold {
+                *item *= (*level / threshold).powf(exponent);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The compressor has no timing on its own, it uses the timing of the inner sound structure.
+impl HasTimer for Compressor {
+    fn set_timing(&self, timing: TimingOption) -> SoundResult<()> {
+        self.inner.set_timing(timing)?;
+        self.follower.reset();
+        Ok(())
+    }
+
+    fn get_timing(&self) -> TimingOption {
+        self.inner.get_timing()
+    }
+
+    fn restart(&self) {
+        self.inner.restart();
+        self.follower.reset();
+    }
+
+    fn apply_parent_timing(&self, parent_timing: TimingOption) -> SoundResult<()> {
+        self.inner.apply_parent_timing(parent_timing)
+    }
+}
+
+impl SoundStructure for Compressor {
+    fn get(&self, base_frequency: &[SampleCalc], result: &mut [SampleCalc]) -> SoundResult<()> {
+        if result.len() > self.envelope_buffer.borrow().len() {
+            return Err(Error::BufferSize);
+        }
+        let inner_result = self.inner.get(base_frequency, result);
+        let completed = match inner_result {
+            Ok(()) => result.len(),
+            Err(Error::ItemsCompleted(completed)) => completed,
+            Err(e) => return Err(e),
+        };
+        process_dry_wet(
+            &mut result[..completed],
+            self.mix.get(),
+            &self.dry_buffer,
+            |samples| self.process_wet(samples),
+        )?;
+        inner_result
+    }
+}
+
+impl Effect for Compressor {
+    fn set_mix(&mut self, wet: SampleCalc) -> SoundResult<()> {
+        is_valid_mix(wet)?;
+        self.mix.set(wet);
+        Ok(())
+    }
+
+    fn process(&mut self, samples: &mut [SampleCalc]) -> SoundResult<()> {
+        process_dry_wet(samples, self.mix.get(), &self.dry_buffer, |samples| {
+            self.process_wet(samples)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sound::test_utils::*;
+
+    const SAMPLE_RATE: SampleCalc = 48000.0;
+
+    /// The peak level of the filtered sine wave, after the transients.
+    fn filtered_peak(mode: BiquadMode, cutoff: SampleCalc, frequency: SampleCalc) -> SampleCalc {
+        let filter = Biquad::new(
+            SAMPLE_RATE,
+            BUFFER_SIZE_DEFAULT,
+            mode,
+            cutoff,
+            0.707,
+            sine(SAMPLE_RATE),
+        )
+        .unwrap();
+        let samples = filter.render(frequency, SAMPLE_RATE, 0.5).unwrap();
+        peak(&samples[samples.len() / 2..])
+    }
+
+    #[test]
+    fn biquad_lowpass_is_transparent_below_cutoff() {
+        let level = filtered_peak(BiquadMode::LowPass, 10000.0, 440.0);
+        assert!((level - 1.0).abs() < 0.01, "{}", level);
+    }
+
+    #[test]
+    fn biquad_notch_attenuates_its_center() {
+        let level = filtered_peak(BiquadMode::Notch, 1000.0, 1000.0);
+        assert!(level < 0.01, "{}", level);
+    }
+
+    #[test]
+    fn dc_blocker_removes_constant_offset() {
+        let dc_blocker = DcBlocker::new(TestSignal::new(SAMPLE_RATE, 0.5));
+        let samples = dc_blocker.render(440.0, SAMPLE_RATE, 1.0).unwrap();
+        let tail = &samples[samples.len() - 4800..];
+        let mean = tail.iter().sum::<SampleCalc>() / tail.len() as SampleCalc;
+        assert!(samples[0] > 0.4);
+        assert!(mean.abs() < 1e-3, "{}", mean);
+    }
+
+    #[test]
+    fn haas_delays_the_last_partial_buffer() {
+        let inner = TestSignal::new(SAMPLE_RATE, 1.0);
+        inner.set_timing(TimingOption::Samples(20)).unwrap();
+        let haas = Haas::new(
+            SAMPLE_RATE,
+            10.0 / SAMPLE_RATE,
+            StereoSide::Right,
+            Rc::new(MonoToStereo::new(inner)),
+        )
+        .unwrap();
+        let frequency = [440.0; 32];
+        let mut left = [9.0; 32];
+        let mut right = [9.0; 32];
+        let completed = haas.get_stereo(&frequency, &mut left, &mut right);
+        assert!(matches!(completed, Err(Error::ItemsCompleted(20))));
+        assert_eq!(left[..20], [1.0; 20]);
+        assert_eq!(right[..10], [0.0; 10]);
+        assert_eq!(right[10..20], [1.0; 10]);
+    }
+
+    #[test]
+    fn delay_writes_output_when_inner_completes() {
+        let inner = TestSignal::new(SAMPLE_RATE, 1.0);
+        inner.set_timing(TimingOption::Samples(36)).unwrap();
+        let delay = Delay::new(SAMPLE_RATE, 0.01, 0.5, 0.5, inner).unwrap();
+        let frequency = [440.0; 64];
+        let mut result = [9.0; 64];
+        let completed = delay.get(&frequency, &mut result);
+        assert!(matches!(completed, Err(Error::ItemsCompleted(36))));
+        for item in result[..36].iter() {
+            assert_eq!(*item, 0.5);
+        }
+    }
+
+    #[test]
+    fn effects_pass_the_signal_through_with_zero_mix() {
+        let signal: Vec<SampleCalc> = (0..BUFFER_SIZE_DEFAULT)
+            .map(|i| (i as SampleCalc * 0.1).sin() + 0.25)
+            .collect();
+        let effects: Vec<Box<dyn Effect>> = vec![
+            Box::new(
+                LowPassOnePole::new(SAMPLE_RATE, BUFFER_SIZE_DEFAULT, 1000.0, sine(SAMPLE_RATE))
+                    .unwrap(),
+            ),
+            Box::new(
+                Biquad::new(
+                    SAMPLE_RATE,
+                    BUFFER_SIZE_DEFAULT,
+                    BiquadMode::HighPass,
+                    1000.0,
+                    0.707,
+                    sine(SAMPLE_RATE),
+                )
+                .unwrap(),
+            ),
+            Box::new(Delay::new(SAMPLE_RATE, 0.001, 0.5, 1.0, sine(SAMPLE_RATE)).unwrap()),
+            Box::new(Waveshaper::new(4.0, sine(SAMPLE_RATE)).unwrap()),
+            Box::new(DcBlocker::new(sine(SAMPLE_RATE))),
+            Box::new(
+                Compressor::new(
+                    SAMPLE_RATE,
+                    BUFFER_SIZE_DEFAULT,
+                    -20.0,
+                    4.0,
+                    0.001,
+                    0.01,
+                    sine(SAMPLE_RATE),
+                )
+                .unwrap(),
+            ),
+        ];
+        for mut effect in effects {
+            effect.set_mix(0.0).unwrap();
+            let mut samples = signal.clone();
+            effect.process(&mut samples).unwrap();
+            assert_eq!(samples, signal);
+        }
+    }
+
+    #[test]
+    fn effect_chain_processes_in_order() {
+        let low_pass =
+            || LowPassOnePole::new(SAMPLE_RATE, BUFFER_SIZE_DEFAULT, 500.0, sine(SAMPLE_RATE));
+        let delay = || Delay::new(SAMPLE_RATE, 0.001, 0.5, 0.5, sine(SAMPLE_RATE));
+        let signal: Vec<SampleCalc> = (0..BUFFER_SIZE_DEFAULT)
+            .map(|i| if i % 32 < 16 { 1.0 } else { -1.0 })
+            .collect();
+
+        let mut chain = EffectChain::new();
+        let _ = chain
+            .add(Box::new(low_pass().unwrap()))
+            .add(Box::new(delay().unwrap()));
+        let mut chained = signal.clone();
+        chain.process(&mut chained).unwrap();
+
+        let mut manual = signal.clone();
+        low_pass().unwrap().process(&mut manual).unwrap();
+        delay().unwrap().process(&mut manual).unwrap();
+
+        assert_eq!(chained, manual);
+        assert_ne!(chained, signal);
+    }
+}
@@ -1,5 +1,8 @@
 use crate::sound::*;
-use std::cell::Cell;
+use rand::rngs::SmallRng;
+use rand::{Rng, SeedableRng};
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
 // use std::fmt;
 // use rayon::prelude::*;
 
@@ -18,6 +21,10 @@ pub trait FrequencyFunction {
 #[derive(Debug, Clone)]
 pub struct FrequencyConst {
     frequency: Cell<SampleCalc>,
+    /// The lowest playable frequency.
+    frequency_min: Cell<SampleCalc>,
+    /// The highest playable frequency.
+    frequency_max: Cell<SampleCalc>,
 }
 
 impl FrequencyConst {
@@ -25,13 +32,42 @@ impl FrequencyConst {
     pub fn new(frequency: SampleCalc) -> SoundResult<FrequencyConst> {
         Ok(FrequencyConst {
             frequency: Cell::new(frequency),
+            frequency_min: Cell::new(TONE_FREQUENCY_MIN),
+            frequency_max: Cell::new(TONE_FREQUENCY_MAX),
         })
     }
 
+    /// Sets the playable frequency range (e.g. of an instrument). It must be inside the hearing
+    /// range. Changes leading out of this range are rejected.
+    pub fn set_frequency_range(
+        &self,
+        frequency_min: SampleCalc,
+        frequency_max: SampleCalc,
+    ) -> SoundResult<()> {
+        if frequency_min < TONE_FREQUENCY_MIN {
+            return Err(Error::FrequencyTooLow);
+        }
+        if frequency_max > TONE_FREQUENCY_MAX {
+            return Err(Error::FrequencyTooHigh);
+        }
+        if frequency_min > frequency_max {
+            return Err(Error::FrequencyInvalid);
+        }
+        self.frequency_min.set(frequency_min);
+        self.frequency_max.set(frequency_max);
+        Ok(())
+    }
+
     /// Change frequency in harmony with it's previous value.
     pub fn change(&self, interval: Interval) -> SoundResult<&FrequencyConst> {
-        self.frequency
-            .set(interval.change_frequency(self.frequency.get())?);
+        let frequency = interval.change_frequency(self.frequency.get())?;
+        if frequency < self.frequency_min.get() {
+            return Err(Error::FrequencyTooLow);
+        }
+        if frequency > self.frequency_max.get() {
+            return Err(Error::FrequencyTooHigh);
+        }
+        self.frequency.set(frequency);
         Ok(self)
     }
 }
@@ -53,14 +89,653 @@ impl FrequencyFunction for FrequencyConst {
     }
 }
 
+/// Gives the frequency of a [MIDI](https://en.wikipedia.org/wiki/MIDI_tuning_standard) note
+/// number in 12 tone equal temperament, e.g. note 69 is the A4 `reference_a4` pitch (usually
+/// 440 Hz), note 60 is the middle C.
+pub fn frequency_from_midi(note: u8, reference_a4: SampleCalc) -> SampleCalc {
+    let octave: SampleCalc = 2.0;
+    reference_a4 * octave.powf((note as SampleCalc - MIDI_NOTE_REFERENCE as SampleCalc) / 12.0)
+}
+
+/// Constant frequency, set by MIDI note numbers (e.g. from a keyboard). Without a tuning table
+/// the notes are in 12 tone equal temperament, see `frequency_from_midi()`. With a tuning table
+/// the notes are mapped by `Interval::from_midi_note()`.
+#[derive(Debug, Clone)]
+pub struct FrequencyFromMidi {
+    reference_a4: SampleCalc,
+    tuning: Option<Vec<Interval>>,
+    note: Cell<u8>,
+    frequency: Cell<SampleCalc>,
+}
+
+impl FrequencyFromMidi {
+    /// custom constructor
+    pub fn new(note: u8, reference_a4: SampleCalc) -> SoundResult<FrequencyFromMidi> {
+        Self::new_with_option(note, reference_a4, None)
+    }
+
+    /// custom constructor, with tuning table (in the format of `scale_from_scala()`)
+    pub fn new_with_tuning(
+        note: u8,
+        reference_a4: SampleCalc,
+        tuning: &[Interval],
+    ) -> SoundResult<FrequencyFromMidi> {
+        if tuning.is_empty() {
+            return Err(Error::SequenceEmpty);
+        }
+        Self::new_with_option(note, reference_a4, Some(tuning.to_vec()))
+    }
+
+    fn new_with_option(
+        note: u8,
+        reference_a4: SampleCalc,
+        tuning: Option<Vec<Interval>>,
+    ) -> SoundResult<FrequencyFromMidi> {
+        if reference_a4 <= 0.0 || !reference_a4.is_finite() {
+            return Err(Error::FrequencyInvalid);
+        }
+        let frequency_from_midi = FrequencyFromMidi {
+            reference_a4,
+            tuning,
+            note: Cell::new(note),
+            frequency: Cell::new(reference_a4),
+        };
+        frequency_from_midi.set_note(note)?;
+        Ok(frequency_from_midi)
+    }
+
+    /// Changes the note. Notes out of the hearing range are rejected.
+    pub fn set_note(&self, note: u8) -> SoundResult<()> {
+        let frequency = match self.tuning {
+            Some(ref tuning) => {
+                self.reference_a4 * Interval::from_midi_note(note, Some(tuning))?.get_ratio()
+            }
+            None => frequency_from_midi(note, self.reference_a4),
+        };
+        if frequency < TONE_FREQUENCY_MIN {
+            return Err(Error::FrequencyTooLow);
+        }
+        if frequency > TONE_FREQUENCY_MAX {
+            return Err(Error::FrequencyTooHigh);
+        }
+        self.note.set(note);
+        self.frequency.set(frequency);
+        Ok(())
+    }
+
+    /// Returns the current note.
+    pub fn get_note(&self) -> u8 {
+        self.note.get()
+    }
+
+    /// Returns the frequency of the current note.
+    pub fn get_frequency(&self) -> SampleCalc {
+        self.frequency.get()
+    }
+}
+
+impl FrequencyFunction for FrequencyFromMidi {
+    fn get(
+        &self,
+        _time_begin: SampleCalc,
+        base_frequency: Option<&[SampleCalc]>,
+        result: &mut [SampleCalc],
+    ) -> SoundResult<()> {
+        if base_frequency.is_some() {
+            return Err(Error::FrequencySource);
+        }
+        for item in result.iter_mut() {
+            *item = self.frequency.get();
+        }
+        Ok(())
+    }
+}
+
+/// Constant frequency, a member of the harmonic series of the fundamental: the `n`-th harmonic
+/// is `n * fundamental` (the 1st one is the fundamental itself). If a base frequency is given
+/// to `get()`, its harmonic is calculated instead.
+/// See: [harmonic series](https://en.wikipedia.org/wiki/Harmonic_series_(music))
+#[derive(Debug, Clone)]
+pub struct HarmonicSeries {
+    fundamental: SampleCalc,
+    harmonic: Cell<usize>,
+}
+
+impl HarmonicSeries {
+    /// custom constructor
+    pub fn new(fundamental: SampleCalc, harmonic: usize) -> SoundResult<HarmonicSeries> {
+        if fundamental <= 0.0 || !fundamental.is_finite() {
+            return Err(Error::FrequencyInvalid);
+        }
+        let harmonic_series = HarmonicSeries {
+            fundamental,
+            harmonic: Cell::new(1),
+        };
+        harmonic_series.set_harmonic(harmonic)?;
+        Ok(harmonic_series)
+    }
+
+    /// Changes the index of the harmonic. Harmonics out of the hearing range are rejected.
+    pub fn set_harmonic(&self, harmonic: usize) -> SoundResult<()> {
+        let frequency = harmonic as SampleCalc * self.fundamental;
+        if frequency < TONE_FREQUENCY_MIN {
+            return Err(Error::FrequencyTooLow);
+        }
+        if frequency > TONE_FREQUENCY_MAX {
+            return Err(Error::FrequencyTooHigh);
+        }
+        self.harmonic.set(harmonic);
+        Ok(())
+    }
+
+    /// Returns the index of the current harmonic.
+    pub fn get_harmonic(&self) -> usize {
+        self.harmonic.get()
+    }
+
+    /// Returns the frequency of the current harmonic.
+    pub fn get_frequency(&self) -> SampleCalc {
+        self.harmonic.get() as SampleCalc * self.fundamental
+    }
+
+    /// Returns the number of harmonics up to `TONE_FREQUENCY_MAX`.
+    pub fn get_harmonic_count(&self) -> usize {
+        (TONE_FREQUENCY_MAX / self.fundamental) as usize
+    }
+
+    /// Enumerates the frequencies of the harmonics which are inside the hearing range, in
+    /// increasing order.
+    pub fn harmonics(&self) -> impl Iterator<Item = SampleCalc> {
+        let fundamental = self.fundamental;
+        (1..=self.get_harmonic_count())
+            .map(move |harmonic| harmonic as SampleCalc * fundamental)
+            .filter(|frequency| *frequency >= TONE_FREQUENCY_MIN)
+    }
+}
+
+impl FrequencyFunction for HarmonicSeries {
+    fn get(
+        &self,
+        _time_begin: SampleCalc,
+        base_frequency: Option<&[SampleCalc]>,
+        result: &mut [SampleCalc],
+    ) -> SoundResult<()> {
+        let harmonic = self.harmonic.get() as SampleCalc;
+        match base_frequency {
+            Some(base) => {
+                if base.len() != result.len() {
+                    return Err(Error::BufferSize);
+                }
+                for (item, frequency) in result.iter_mut().zip(base) {
+                    *item = harmonic * *frequency;
+                    if *item > TONE_FREQUENCY_MAX {
+                        return Err(Error::FrequencyTooHigh);
+                    }
+                }
+            }
+            None => {
+                for item in result.iter_mut() {
+                    *item = harmonic * self.fundamental;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
 /// Changing frequency linearly. Linearity means constant multiplication over time slices.
-#[allow(dead_code)]
+/// After the `timeframe`, the frequency stays at `frequency_end`.
+#[derive(Debug, Clone)]
 pub struct FrequencyChangeLinear {
     sample_time: SampleCalc,
     frequency_begin: SampleCalc,
     frequency_end: SampleCalc,
     timeframe: SampleCalc,
-    time: SampleCalc,
+    /// Frequency multiplier for one sample.
+    multiplier: SampleCalc,
+}
+
+impl FrequencyChangeLinear {
+    /// custom constructor
+    /// `timeframe` is the duration of the change in seconds.
+    pub fn new(
+        sample_rate: SampleCalc,
+        frequency_begin: SampleCalc,
+        frequency_end: SampleCalc,
+        timeframe: SampleCalc,
+    ) -> SoundResult<FrequencyChangeLinear> {
+        let sample_time = get_sample_time(sample_rate)?;
+        if frequency_begin <= 0.0 || frequency_end <= 0.0 {
+            return Err(Error::FrequencyInvalid);
+        }
+        if timeframe <= 0.0 {
+            return Err(Error::DurationInvalid);
+        }
+        Ok(FrequencyChangeLinear {
+            sample_time,
+            frequency_begin,
+            frequency_end,
+            timeframe,
+            multiplier: (frequency_end / frequency_begin).powf(sample_time / timeframe),
+        })
+    }
+}
+
+impl FrequencyFunction for FrequencyChangeLinear {
+    fn get(
+        &self,
+        time_begin: SampleCalc,
+        base_frequency: Option<&[SampleCalc]>,
+        result: &mut [SampleCalc],
+    ) -> SoundResult<()> {
+        if base_frequency.is_some() {
+            return Err(Error::FrequencySource);
+        }
+        let samples_remaining = ((self.timeframe - time_begin) / self.sample_time).max(0.0);
+        let changing = (samples_remaining.ceil() as usize).min(result.len());
+        let (result_changing, result_end) = result.split_at_mut(changing);
+        let mut frequency = self.frequency_begin
+            * (self.frequency_end / self.frequency_begin).powf(time_begin / self.timeframe);
+        for item in result_changing.iter_mut() {
+            *item = frequency;
+            frequency *= self.multiplier;
+        }
+        for item in result_end.iter_mut() {
+            *item = self.frequency_end;
+        }
+        Ok(())
+    }
+}
+
+/// Glides ([portamento](https://en.wikipedia.org/wiki/Portamento)) from the actual frequency
+/// to the target frequency, by a one-pole smoothing filter (on logarithmic scale, so the pitch
+/// changes evenly). A new target can be set anytime, the glide continues from the actual value.
+#[derive(Debug, Clone)]
+pub struct FrequencyGlide {
+    sample_time: SampleCalc,
+    /// The ratio of the remaining pitch distance covered in one sample.
+    coefficient: Cell<SampleCalc>,
+    frequency: Cell<SampleCalc>,
+    frequency_target: Cell<SampleCalc>,
+}
+
+impl FrequencyGlide {
+    /// custom constructor
+    /// During `glide_time` (in seconds), 99.9% of the pitch distance is covered.
+    pub fn new(
+        sample_rate: SampleCalc,
+        frequency: SampleCalc,
+        glide_time: SampleCalc,
+    ) -> SoundResult<FrequencyGlide> {
+        let sample_time = get_sample_time(sample_rate)?;
+        if frequency <= 0.0 {
+            return Err(Error::FrequencyInvalid);
+        }
+        let glide = FrequencyGlide {
+            sample_time,
+            coefficient: Cell::new(1.0),
+            frequency: Cell::new(frequency),
+            frequency_target: Cell::new(frequency),
+        };
+        glide.set_glide_time(glide_time)?;
+        Ok(glide)
+    }
+
+    /// Sets the glide time (in seconds). 0.0 means jumping to the target immediately.
+    pub fn set_glide_time(&self, glide_time: SampleCalc) -> SoundResult<()> {
+        if glide_time < 0.0 {
+            return Err(Error::DurationInvalid);
+        }
+        if glide_time == 0.0 {
+            self.coefficient.set(1.0);
+        } else {
+            let remaining: SampleCalc = 0.001;
+            self.coefficient
+                .set(1.0 - remaining.powf(self.sample_time / glide_time));
+        }
+        Ok(())
+    }
+
+    /// Sets the frequency to glide to.
+    pub fn set_target(&self, frequency: SampleCalc) -> SoundResult<()> {
+        if frequency <= 0.0 {
+            return Err(Error::FrequencyInvalid);
+        }
+        self.frequency_target.set(frequency);
+        Ok(())
+    }
+
+    /// Provides the actual frequency.
+    pub fn get_frequency(&self) -> SampleCalc {
+        self.frequency.get()
+    }
+}
+
+impl FrequencyFunction for FrequencyGlide {
+    fn get(
+        &self,
+        _time_begin: SampleCalc,
+        base_frequency: Option<&[SampleCalc]>,
+        result: &mut [SampleCalc],
+    ) -> SoundResult<()> {
+        if base_frequency.is_some() {
+            return Err(Error::FrequencySource);
+        }
+        let frequency_target = self.frequency_target.get();
+        let mut frequency = self.frequency.get();
+        for item in result.iter_mut() {
+            frequency *= (frequency_target / frequency).powf(self.coefficient.get());
+            *item = frequency;
+        }
+        self.frequency.set(frequency);
+        Ok(())
+    }
+}
+
+/// Sequence of frequency functions. Each of them lasts for its own time based timing, then the
+/// next one follows. When the last one ends, the sequence is completed.
+#[derive(Clone)]
+pub struct FrequencySequence {
+    sample_rate: SampleCalc,
+    timer: Timer,
+    /// The frequency functions with their own timers.
+    freq_funct_array: Vec<(Rc<dyn FrequencyFunction>, Timer)>,
+    array_index: Cell<usize>,
+    /// The time elapsed since the actual frequency function started.
+    item_time: Cell<SampleCalc>,
+}
+
+impl FrequencySequence {
+    /// custom constructor
+    pub fn new(sample_rate: SampleCalc) -> SoundResult<FrequencySequence> {
+        Ok(FrequencySequence {
+            sample_rate,
+            timer: Timer::new(sample_rate)?,
+            freq_funct_array: Vec::new(),
+            array_index: Cell::new(0),
+            item_time: Cell::new(0.0),
+        })
+    }
+
+    /// Adds a new frequency function to the sequence, with the given duration.
+    pub fn add(
+        &mut self,
+        frequency: Rc<dyn FrequencyFunction>,
+        timing: TimingOption,
+    ) -> SoundResult<&mut FrequencySequence> {
+        let timer = Timer::new(self.sample_rate)?;
+        timer.set_timing(timing)?;
+        timer.apply_parent_timing(self.timer.get_timing())?;
+        self.freq_funct_array.push((frequency, timer));
+        Ok(self)
+    }
+}
+
+impl FrequencyFunction for FrequencySequence {
+    fn get(
+        &self,
+        _time_begin: SampleCalc,
+        base_frequency: Option<&[SampleCalc]>,
+        result: &mut [SampleCalc],
+    ) -> SoundResult<()> {
+        if self.freq_funct_array.is_empty() {
+            return Err(Error::SequenceEmpty);
+        }
+        if let Some(base) = base_frequency {
+            if base.len() != result.len() {
+                return Err(Error::BufferSize);
+            }
+        }
+        let timer_result = self.timer.jump_by_time(result.len());
+        let buffer: &mut [SampleCalc] = match timer_result {
+            Ok(()) => result,
+            Err(Error::ItemsCompleted(completed)) => &mut result[0..completed],
+            Err(_) => return timer_result,
+        };
+        let sample_time = self.timer.get_sample_time();
+        let mut index_from: usize = 0;
+        loop {
+            let (freq_funct_act, item_timer) = self
+                .freq_funct_array
+                .get(self.array_index.get())
+                .ok_or(Error::ItemInvalid)?;
+            let remaining = buffer.len() - index_from;
+            let (item_samples, item_result) = match item_timer.jump_by_time(remaining) {
+                Ok(()) => (remaining, Ok(())),
+                Err(Error::ItemsCompleted(completed)) => {
+                    (completed, Err(Error::ItemsCompleted(completed)))
+                }
+                Err(e) => return Err(e),
+            };
+            let index_to = index_from + item_samples;
+            freq_funct_act.get(
+                self.item_time.get(),
+                base_frequency.map(|base| &base[index_from..index_to]),
+                &mut buffer[index_from..index_to],
+            )?;
+            self.item_time
+                .set(self.item_time.get() + item_samples as SampleCalc * sample_time);
+            index_from = index_to;
+            if item_result.is_ok() {
+                return timer_result;
+            }
+            let array_index = self.array_index.get() + 1;
+            if array_index >= self.freq_funct_array.len() {
+                return Err(Error::ItemsCompleted(index_from));
+            }
+            self.array_index.set(array_index);
+            self.item_time.set(0.0);
+            let (_, item_timer_next) = &self.freq_funct_array[array_index];
+            item_timer_next.apply_parent_timing(self.timer.get_timing())?;
+            item_timer_next.restart();
+        }
+    }
+}
+
+impl HasTimer for FrequencySequence {
+    fn set_timing(&self, timing: TimingOption) -> SoundResult<()> {
+        self.timer.set_timing(timing)?;
+        self.restart();
+        Ok(())
+    }
+
+    fn get_timing(&self) -> TimingOption {
+        self.timer.get_timing()
+    }
+
+    fn restart(&self) {
+        self.timer.restart();
+        self.array_index.set(0);
+        self.item_time.set(0.0);
+        if let Some((_, item_timer)) = self.freq_funct_array.first() {
+            item_timer.restart();
+        }
+    }
+
+    fn apply_parent_timing(&self, parent_timing: TimingOption) -> SoundResult<()> {
+        self.timer.apply_parent_timing(parent_timing)?;
+        if let Some((_, item_timer)) = self.freq_funct_array.first() {
+            item_timer.apply_parent_timing(self.timer.get_timing())?;
+        }
+        self.restart();
+        Ok(())
+    }
+}
+
+/// The order of the steps of an `Arpeggiator`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ArpeggioMode {
+    /// From the first interval to the last one, then again from the first.
+    Up,
+    /// From the last interval to the first one, then again from the last.
+    Down,
+    /// Up then down, without repeating the first and last intervals.
+    UpDown,
+    /// A randomly chosen interval at each step.
+    Random,
+}
+
+/// [Arpeggio](https://en.wikipedia.org/wiki/Arpeggio): it cycles through a list of intervals
+/// relative to the base frequency, each step lasts for a note value at the given tempo.
+/// If a base frequency is given to `get()`, the intervals are applied to it, otherwise to the
+/// arpeggiator's own frequency.
+#[derive(Clone)]
+pub struct Arpeggiator {
+    frequency: Cell<SampleCalc>,
+    intervals: Vec<Interval>,
+    mode: Cell<ArpeggioMode>,
+    tempo: Cell<Tempo>,
+    /// The timer of the actual step.
+    step_timer: Timer,
+    /// The number of steps since the (re)start.
+    step_count: Cell<usize>,
+    /// The index of the actual interval.
+    interval_index: Cell<usize>,
+    rng: RefCell<SmallRng>,
+}
+
+impl Arpeggiator {
+    /// custom constructor
+    /// `step` is the duration of one step.
+    pub fn new(
+        sample_rate: SampleCalc,
+        frequency: SampleCalc,
+        intervals: Vec<Interval>,
+        step: NoteValue,
+        tempo: Tempo,
+        mode: ArpeggioMode,
+    ) -> SoundResult<Arpeggiator> {
+        if frequency <= 0.0 || !frequency.is_finite() {
+            return Err(Error::FrequencyInvalid);
+        }
+        if intervals.is_empty() {
+            return Err(Error::SequenceEmpty);
+        }
+        let step_timer = Timer::new(sample_rate)?;
+        step_timer.set_timing(TimingOption::TempoConst(step))?;
+        let arpeggiator = Arpeggiator {
+            frequency: Cell::new(frequency),
+            intervals,
+            mode: Cell::new(mode),
+            tempo: Cell::new(tempo),
+            step_timer,
+            step_count: Cell::new(0),
+            interval_index: Cell::new(0),
+            rng: RefCell::new(SmallRng::seed_from_u64(0)),
+        };
+        arpeggiator.restart();
+        Ok(arpeggiator)
+    }
+
+    /// Changes the base frequency.
+    pub fn set_frequency(&self, frequency: SampleCalc) -> SoundResult<()> {
+        if frequency <= 0.0 || !frequency.is_finite() {
+            return Err(Error::FrequencyInvalid);
+        }
+        self.frequency.set(frequency);
+        Ok(())
+    }
+
+    /// Changes the order of the steps. The pattern starts again.
+    pub fn set_mode(&self, mode: ArpeggioMode) {
+        self.mode.set(mode);
+        self.restart();
+    }
+
+    /// Changes the duration of one step. The actual step starts again with the new duration.
+    pub fn set_step(&self, step: NoteValue) -> SoundResult<()> {
+        self.step_timer.set_timing(TimingOption::TempoConst(step))
+    }
+
+    /// Changes the tempo.
+    pub fn set_tempo(&self, tempo: Tempo) {
+        self.tempo.set(tempo);
+    }
+
+    /// Seeds the random number generator of `ArpeggioMode::Random`. The same `seed` always
+    /// produces the same pattern.
+    pub fn set_seed(&self, seed: u64) {
+        *self.rng.borrow_mut() = SmallRng::seed_from_u64(seed);
+    }
+
+    /// Starts the pattern again, from its first step.
+    pub fn restart(&self) {
+        self.step_timer.restart();
+        self.step_count.set(0);
+        self.interval_index.set(self.get_interval_index(0));
+    }
+
+    /// Provides the interval of the actual step.
+    pub fn get_interval(&self) -> Interval {
+        self.intervals[self.interval_index.get()]
+    }
+
+    /// Calculates the index of the interval of the given step.
+    fn get_interval_index(&self, step_count: usize) -> usize {
+        let length = self.intervals.len();
+        match self.mode.get() {
+            ArpeggioMode::Up => step_count % length,
+            ArpeggioMode::Down => length - 1 - step_count % length,
+            ArpeggioMode::UpDown => {
+                if length < 2 {
+                    return 0;
+                }
+                let period = 2 * length - 2;
+                let position = step_count % period;
+                if position < length {
+                    position
+                } else {
+                    period - position
+                }
+            }
+            ArpeggioMode::Random => self.rng.borrow_mut().gen_range(0..length),
+        }
+    }
+
+    /// Moves forward one sample in time, it steps to the next interval when needed.
+    fn next_sample(&self, beats_per_second: SampleCalc) -> SoundResult<()> {
+        match self.step_timer.next_by_tempo(beats_per_second) {
+            Ok(()) => Ok(()),
+            Err(Error::ProgressCompleted) => {
+                self.step_timer.restart();
+                let step_count = self.step_count.get().wrapping_add(1);
+                self.step_count.set(step_count);
+                self.interval_index.set(self.get_interval_index(step_count));
+                Ok(())
+            }
+            Err(e) => Err(e),
+        }
+    }
+}
+
+impl FrequencyFunction for Arpeggiator {
+    fn get(
+        &self,
+        _time_begin: SampleCalc,
+        base_frequency: Option<&[SampleCalc]>,
+        result: &mut [SampleCalc],
+    ) -> SoundResult<()> {
+        let beats_per_second = 1.0 / self.tempo.get().get_duration();
+        match base_frequency {
+            Some(base) => {
+                if base.len() != result.len() {
+                    return Err(Error::BufferSize);
+                }
+                for (item, frequency) in result.iter_mut().zip(base) {
+                    *item = self.get_interval().change_frequency(*frequency)?;
+                    self.next_sample(beats_per_second)?;
+                }
+            }
+            None => {
+                let frequency = self.frequency.get();
+                for item in result.iter_mut() {
+                    *item = self.get_interval().change_frequency(frequency)?;
+                    self.next_sample(beats_per_second)?;
+                }
+            }
+        }
+        Ok(())
+    }
 }
 
 /// Provides rhythmic frequency changes. As phase depends on the integral of tempo, only
@@ -90,6 +765,10 @@ pub struct Vibrato {
     /// The phase of the sine function.
     phase: SampleCalc,
     phase_change: SampleCalc,
+    /// The duration while the extent grows from none to `extent_ratio`, from the start.
+    fade_in: SampleCalc,
+    /// The time elapsed since the start.
+    time: SampleCalc,
 }
 
 impl Vibrato {
@@ -110,9 +789,37 @@ impl Vibrato {
             extent_ratio,
             phase: 0.0,
             phase_change,
+            fade_in: 0.0,
+            time: 0.0,
         })
     }
 
+    /// Sets the duration (in seconds) while the extent grows linearly from none to full, after
+    /// the start. 0.0 means no fade-in.
+    pub fn set_fade_in(&mut self, seconds: SampleCalc) -> SoundResult<()> {
+        if seconds < 0.0 {
+            return Err(Error::DurationInvalid);
+        }
+        self.fade_in = seconds;
+        Ok(())
+    }
+
+    /// Restarts the vibrato (e.g. at the start of a new note), including the fade-in.
+    pub fn restart(&mut self) {
+        self.phase = 0.0;
+        self.time = 0.0;
+    }
+
+    /// Provides the extent ratio for the next sample, considering the fade-in.
+    fn next_extent_ratio(&mut self) -> SampleCalc {
+        if self.time >= self.fade_in {
+            return self.extent_ratio;
+        }
+        let ramp = self.time / self.fade_in;
+        self.time += self.sample_time;
+        1.0 + (self.extent_ratio - 1.0) * ramp
+    }
+
     /// Sets a new phase value.
     pub fn set_phase(&mut self, phase: SampleCalc) -> SoundResult<()> {
         self.phase = phase % PI2;
@@ -137,7 +844,8 @@ impl FrequencyModulator for Vibrato {
             result.iter_mut().zip(base_frequency).zip(tempo)
         {
             self.phase += self.phase_change * beats_per_second;
-            *item = *frequency * (self.extent_ratio.powf(self.phase.sin()));
+            let extent_ratio = self.next_extent_ratio();
+            *item = *frequency * (extent_ratio.powf(self.phase.sin()));
         }
         self.phase %= PI2;
         Ok(())
@@ -149,9 +857,318 @@ impl FrequencyModulator for Vibrato {
         }
         for (item, beats_per_second) in samples.iter_mut().zip(tempo) {
             self.phase += self.phase_change * beats_per_second;
-            *item *= self.extent_ratio.powf(self.phase.sin());
+            let extent_ratio = self.next_extent_ratio();
+            *item *= extent_ratio.powf(self.phase.sin());
         }
         self.phase %= PI2;
         Ok(())
     }
 }
+
+/// Random walk ("drunk walk") of the base frequency, for the natural pitch instability of human
+/// or analog sources. At every note value (at the given tempo) a random step is taken, and the
+/// pitch glides linearly to it. The walk stays within `range` around the base frequency, and
+/// the results are kept within [`TONE_FREQUENCY_MIN`, `TONE_FREQUENCY_MAX`]. Negative, infinite
+/// or NaN tempo values are rejected with `Error::TempoInvalid`.
+#[derive(Debug, Clone)]
+pub struct RandomWalk {
+    /// The change of the step progress in one sample, at one beat per second.
+    progress_change: SampleCalc,
+    /// The progress of the actual step, in the [0.0, 1.0) range.
+    progress: SampleCalc,
+    /// The maximal shift away from the base frequency (in octaves).
+    range: SampleCalc,
+    /// The maximal size of a step (in octaves).
+    step: SampleCalc,
+    /// The shift at the beginning of the actual step (in octaves).
+    shift_from: SampleCalc,
+    /// The shift at the end of the actual step (in octaves).
+    shift_to: SampleCalc,
+    rng: SmallRng,
+}
+
+impl RandomWalk {
+    /// custom constructor
+    /// `note_value` is the duration of one step, `range` is the maximal shift and `step` is the
+    /// maximal change in one step (both in either direction). The same `seed` always produces
+    /// the same walk.
+    pub fn new(
+        sample_rate: SampleCalc,
+        note_value: NoteValue,
+        range: Interval,
+        step: Interval,
+        seed: u64,
+    ) -> SoundResult<RandomWalk> {
+        let sample_time = get_sample_time(sample_rate)?;
+        let mut random_walk = RandomWalk {
+            progress_change: sample_time * note_value.get_notes_per_beat(),
+            progress: 0.0,
+            range: 0.0,
+            step: 0.0,
+            shift_from: 0.0,
+            shift_to: 0.0,
+            rng: SmallRng::seed_from_u64(seed),
+        };
+        random_walk.set_range(range);
+        random_walk.set_step(step);
+        random_walk.restart();
+        Ok(random_walk)
+    }
+
+    /// Sets the maximal shift away from the base frequency, in either direction.
+    pub fn set_range(&mut self, range: Interval) {
+        self.range = range.get_ratio().log2().abs();
+        self.shift_from = self.shift_from.clamp(-self.range, self.range);
+        self.shift_to = self.shift_to.clamp(-self.range, self.range);
+    }
+
+    /// Sets the maximal change in one step, in either direction.
+    pub fn set_step(&mut self, step: Interval) {
+        self.step = step.get_ratio().log2().abs();
+    }
+
+    /// Reseeds the random number generator.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng = SmallRng::seed_from_u64(seed);
+    }
+
+    /// Restarts the walk from the base frequency.
+    pub fn restart(&mut self) {
+        self.progress = 0.0;
+        self.shift_from = 0.0;
+        self.shift_to = self.next_shift(0.0);
+    }
+
+    /// Provides a random shift at most one step away from `shift`, within the range.
+    fn next_shift(&mut self, shift: SampleCalc) -> SampleCalc {
+        let change = self.rng.gen_range(-1.0..=1.0) * self.step;
+        (shift + change).clamp(-self.range, self.range)
+    }
+
+    /// Provides the frequency ratio for the next sample.
+    fn next_ratio(&mut self, beats_per_second: SampleCalc) -> SoundResult<SampleCalc> {
+        if beats_per_second < 0.0 || !beats_per_second.is_finite() {
+            return Err(Error::TempoInvalid);
+        }
+        self.progress += self.progress_change * beats_per_second;
+        while self.progress >= 1.0 {
+            self.progress -= 1.0;
+            self.shift_from = self.shift_to;
+            self.shift_to = self.next_shift(self.shift_from);
+        }
+        let shift = self.shift_from + (self.shift_to - self.shift_from) * self.progress;
+        Ok(shift.exp2())
+    }
+}
+
+impl FrequencyModulator for RandomWalk {
+    fn get(
+        &mut self,
+        tempo: &[SampleCalc],
+        base_frequency: &[SampleCalc],
+        result: &mut [SampleCalc],
+    ) -> SoundResult<()> {
+        if tempo.len() != result.len() {
+            return Err(Error::BufferSize);
+        }
+        if base_frequency.len() != result.len() {
+            return Err(Error::BufferSize);
+        }
+        for ((item, frequency), beats_per_second) in
+            result.iter_mut().zip(base_frequency).zip(tempo)
+        {
+            *item = (*frequency * self.next_ratio(*beats_per_second)?)
+                .clamp(TONE_FREQUENCY_MIN, TONE_FREQUENCY_MAX);
+        }
+        Ok(())
+    }
+
+    fn apply(&mut self, tempo: &[SampleCalc], samples: &mut [SampleCalc]) -> SoundResult<()> {
+        if tempo.len() != samples.len() {
+            return Err(Error::BufferSize);
+        }
+        for (item, beats_per_second) in samples.iter_mut().zip(tempo) {
+            *item = (*item * self.next_ratio(*beats_per_second)?)
+                .clamp(TONE_FREQUENCY_MIN, TONE_FREQUENCY_MAX);
+        }
+        Ok(())
+    }
+}
+
+/// Two-operator [frequency modulation](https://en.wikipedia.org/wiki/Frequency_modulation_synthesis):
+/// f_out = f_carrier + index * f_carrier * sin(modulator_phase). The modulator's frequency is
+/// given by its `Wave` relative to the carrier's (base) frequency (by it's overtone and
+/// interval). The result is meant to be the frequency input of a carrier `Wave` or `Timbre`.
+#[derive(Debug, Clone)]
+pub struct FrequencyModulatorFM {
+    modulator: Wave,
+    /// The modulation index (the peak frequency deviation relative to the carrier frequency).
+    index: SampleCalc,
+    modulator_buffer: Vec<SampleCalc>,
+}
+
+impl FrequencyModulatorFM {
+    /// custom constructor
+    pub fn new(modulator: Wave, index: SampleCalc) -> SoundResult<FrequencyModulatorFM> {
+        if index < 0.0 {
+            return Err(Error::ModulationIndexInvalid);
+        }
+        Ok(FrequencyModulatorFM {
+            modulator,
+            index,
+            modulator_buffer: Vec::new(),
+        })
+    }
+
+    /// Sets a new modulation index.
+    pub fn set_index(&mut self, index: SampleCalc) -> SoundResult<()> {
+        if index < 0.0 {
+            return Err(Error::ModulationIndexInvalid);
+        }
+        self.index = index;
+        Ok(())
+    }
+}
+
+impl FrequencyModulator for FrequencyModulatorFM {
+    fn get(
+        &mut self,
+        tempo: &[SampleCalc],
+        base_frequency: &[SampleCalc],
+        result: &mut [SampleCalc],
+    ) -> SoundResult<()> {
+        if tempo.len() != result.len() {
+            return Err(Error::BufferSize);
+        }
+        if base_frequency.len() != result.len() {
+            return Err(Error::BufferSize);
+        }
+        self.modulator_buffer.resize(base_frequency.len(), 0.0);
+        self.modulator
+            .get(base_frequency, &mut self.modulator_buffer)?;
+        for ((item, frequency), modulation) in result
+            .iter_mut()
+            .zip(base_frequency)
+            .zip(self.modulator_buffer.iter())
+        {
+            *item = *frequency * (1.0 + self.index * *modulation);
+        }
+        Ok(())
+    }
+
+    fn apply(&mut self, tempo: &[SampleCalc], samples: &mut [SampleCalc]) -> SoundResult<()> {
+        if tempo.len() != samples.len() {
+            return Err(Error::BufferSize);
+        }
+        self.modulator_buffer.resize(samples.len(), 0.0);
+        self.modulator.get(samples, &mut self.modulator_buffer)?;
+        for (item, modulation) in samples.iter_mut().zip(self.modulator_buffer.iter()) {
+            *item *= 1.0 + self.index * *modulation;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: SampleCalc = 48000.0;
+
+    #[test]
+    fn fm_with_zero_index_reproduces_the_carrier() {
+        let modulator = Wave::new(SAMPLE_RATE, 1).unwrap();
+        let mut fm = FrequencyModulatorFM::new(modulator, 0.0).unwrap();
+        let tempo = [2.0; BUFFER_SIZE_DEFAULT];
+        let base_frequency = [440.0; BUFFER_SIZE_DEFAULT];
+        let mut frequency = [0.0; BUFFER_SIZE_DEFAULT];
+        let mut carrier = Wave::new(SAMPLE_RATE, 0).unwrap();
+        let mut reference = Wave::new(SAMPLE_RATE, 0).unwrap();
+        let mut samples = [0.0; BUFFER_SIZE_DEFAULT];
+        let mut samples_reference = [0.0; BUFFER_SIZE_DEFAULT];
+        for _ in 0..4 {
+            fm.get(&tempo, &base_frequency, &mut frequency).unwrap();
+            assert_eq!(frequency, base_frequency);
+            carrier.get(&frequency, &mut samples).unwrap();
+            reference
+                .get(&base_frequency, &mut samples_reference)
+                .unwrap();
+            assert_eq!(samples, samples_reference);
+        }
+    }
+
+    #[test]
+    fn frequency_range_of_the_instrument() {
+        let bass = FrequencyConst::new(200.0).unwrap();
+        bass.set_frequency_range(30.0, 400.0).unwrap();
+        let fifth = Interval::new(3, 2).unwrap();
+        let tenth = Interval::new(5, 2).unwrap();
+        // 500 Hz is within the hearing range, but above the range of the instrument
+        assert!(matches!(bass.change(tenth), Err(Error::FrequencyTooHigh)));
+        let mut frequency = [0.0; 4];
+        bass.get(0.0, None, &mut frequency).unwrap();
+        assert_eq!(frequency, [200.0; 4]);
+        let _ = bass.change(fifth).unwrap();
+        bass.get(0.0, None, &mut frequency).unwrap();
+        assert_eq!(frequency, [300.0; 4]);
+        assert!(bass.set_frequency_range(400.0, 30.0).is_err());
+        assert!(bass
+            .set_frequency_range(30.0, TONE_FREQUENCY_MAX * 2.0)
+            .is_err());
+    }
+
+    /// Two seconds of random walk around `frequency`, at 120 BPM.
+    fn render_random_walk(seed: u64, frequency: SampleCalc) -> Vec<SampleCalc> {
+        let mut random_walk = RandomWalk::new(
+            SAMPLE_RATE,
+            NoteValue::new(1, 4).unwrap(),
+            Interval::new(25, 24).unwrap(),
+            Interval::new(81, 80).unwrap(),
+            seed,
+        )
+        .unwrap();
+        let tempo = vec![2.0; 2 * SAMPLE_RATE as usize];
+        let base_frequency = vec![frequency; tempo.len()];
+        let mut result = vec![0.0; tempo.len()];
+        random_walk
+            .get(&tempo, &base_frequency, &mut result)
+            .unwrap();
+        result
+    }
+
+    #[test]
+    fn random_walk_is_deterministic_and_bounded() {
+        let walk = render_random_walk(7, 440.0);
+        assert_eq!(walk, render_random_walk(7, 440.0));
+        assert_ne!(walk, render_random_walk(8, 440.0));
+        let (low, high) = (440.0 * 24.0 / 25.0, 440.0 * 25.0 / 24.0);
+        for frequency in walk.iter() {
+            assert!(*frequency >= low - 0.01 && *frequency <= high + 0.01);
+        }
+        for frequency in render_random_walk(7, TONE_FREQUENCY_MAX)
+            .iter()
+            .chain(render_random_walk(7, TONE_FREQUENCY_MIN).iter())
+        {
+            assert!(*frequency >= TONE_FREQUENCY_MIN && *frequency <= TONE_FREQUENCY_MAX);
+        }
+    }
+
+    #[test]
+    fn random_walk_rejects_invalid_tempo() {
+        let mut random_walk = RandomWalk::new(
+            SAMPLE_RATE,
+            NoteValue::new(1, 4).unwrap(),
+            Interval::new(25, 24).unwrap(),
+            Interval::new(81, 80).unwrap(),
+            7,
+        )
+        .unwrap();
+        let mut samples = [440.0; 4];
+        for tempo in [SampleCalc::INFINITY, SampleCalc::NAN] {
+            assert!(matches!(
+                random_walk.apply(&[tempo; 4], &mut samples),
+                Err(Error::TempoInvalid)
+            ));
+        }
+    }
+}
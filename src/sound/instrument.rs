@@ -0,0 +1,321 @@
+use crate::sound::*;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::rc::Rc;
+
+/// One voice of the `Polyphony`: a sound structure shaped by an ADSR envelope.
+#[derive(Clone)]
+struct Voice {
+    sound: Rc<dyn SoundStructure>,
+    envelope: Rc<AmplitudeADSR>,
+    interval: Interval,
+    /// The key which started the note, `None` if the voice is free.
+    key: Option<u32>,
+    /// The note is released, but the envelope has not finished yet.
+    released: bool,
+    /// The serial number of the note-on, for finding the oldest voice.
+    started: u64,
+    frequency_buffer: Vec<SampleCalc>,
+    wave_buffer: Vec<SampleCalc>,
+}
+
+/// Plays several notes at the same time, with a pool of voices. Each note-on gets a free voice,
+/// if there is none, the oldest voice is stolen. The outputs of the voices are summed without
+/// normalization, so chords can exceed the [-1.0, 1.0] range (see `soft_limit()`).
+#[derive(Clone)]
+pub struct Polyphony {
+    timer: Timer,
+    buffer_size: usize,
+    voices: RefCell<Vec<Voice>>,
+    note_count: Cell<u64>,
+}
+
+impl Polyphony {
+    /// custom constructor
+    pub fn new(sample_rate: SampleCalc, buffer_size: usize) -> SoundResult<Polyphony> {
+        Ok(Polyphony {
+            timer: Timer::new(sample_rate)?,
+            buffer_size,
+            voices: RefCell::new(Vec::new()),
+            note_count: Cell::new(0),
+        })
+    }
+
+    /// Adds a new voice to the pool. Each voice needs its own sound structure and envelope, as
+    /// they are playing different notes. The envelope shall not have a finite timing, the
+    /// release is started by `note_off()`.
+    pub fn add_voice(
+        &self,
+        sound: Rc<dyn SoundStructure>,
+        envelope: Rc<AmplitudeADSR>,
+    ) -> SoundResult<&Polyphony> {
+        sound.apply_parent_timing(self.timer.get_timing())?;
+        self.voices.borrow_mut().push(Voice {
+            sound,
+            envelope,
+            interval: INTERVAL_UNISON,
+            key: None,
+            released: false,
+            started: 0,
+            frequency_buffer: vec![1.0; self.buffer_size],
+            wave_buffer: vec![0.0; self.buffer_size],
+        });
+        Ok(self)
+    }
+
+    /// Returns the number of voices in the pool.
+    pub fn get_voice_count(&self) -> usize {
+        self.voices.borrow().len()
+    }
+
+    /// Returns the number of voices playing a note (including the released ones).
+    pub fn get_active_count(&self) -> usize {
+        self.voices
+            .borrow()
+            .iter()
+            .filter(|voice| voice.key.is_some())
+            .count()
+    }
+
+    /// Starts a note on a free voice, or on the oldest one if all of them are playing. The
+    /// `interval` is relative to the base frequency, the `key` identifies the note for
+    /// `note_off()`. Returns the index of the voice.
+    pub fn note_on(&self, key: u32, interval: Interval) -> SoundResult<usize> {
+        let mut voices = self.voices.borrow_mut();
+        let index = match voices.iter().position(|voice| voice.key.is_none()) {
+            Some(index) => index,
+            None => voices
+                .iter()
+                .enumerate()
+                .min_by_key(|(_, voice)| voice.started)
+                .map(|(index, _)| index)
+                .ok_or(Error::SequenceEmpty)?,
+        };
+        let voice = &mut voices[index];
+        // a stolen voice starts from its actual amplitude, to avoid clicks
+        let amplitude_start = if voice.key.is_some() {
+            voice.envelope.get_amplitude()
+        } else {
+            0.0
+        };
+        voice.envelope.set_amplitude_start(amplitude_start)?;
+        voice.sound.restart();
+        voice.interval = interval;
+        voice.key = Some(key);
+        voice.released = false;
+        self.note_count.set(self.note_count.get() + 1);
+        voice.started = self.note_count.get();
+        Ok(index)
+    }
+
+    /// Releases the notes of the key, their envelopes continue with the release stage.
+    pub fn note_off(&self, key: u32) {
+        for voice in self.voices.borrow_mut().iter_mut() {
+            if voice.key == Some(key) && !voice.released {
+                voice.envelope.note_off();
+                voice.released = true;
+            }
+        }
+    }
+
+    /// Releases all the notes.
+    pub fn all_notes_off(&self) {
+        for voice in self.voices.borrow_mut().iter_mut() {
+            if voice.key.is_some() && !voice.released {
+                voice.envelope.note_off();
+                voice.released = true;
+            }
+        }
+    }
+}
+
+impl HasTimer for Polyphony {
+    fn set_timing(&self, timing: TimingOption) -> SoundResult<()> {
+        self.timer.set_timing(timing)?;
+        for voice in self.voices.borrow().iter() {
+            voice.sound.apply_parent_timing(self.timer.get_timing())?;
+        }
+        self.restart();
+        Ok(())
+    }
+
+    fn get_timing(&self) -> TimingOption {
+        self.timer.get_timing()
+    }
+
+    /// Silences all the voices.
+    fn restart(&self) {
+        self.timer.restart();
+        for voice in self.voices.borrow_mut().iter_mut() {
+            voice.key = None;
+            voice.released = false;
+            voice.sound.restart();
+            voice.envelope.restart();
+        }
+    }
+
+    fn apply_parent_timing(&self, parent_timing: TimingOption) -> SoundResult<()> {
+        self.timer.apply_parent_timing(parent_timing)?;
+        for voice in self.voices.borrow().iter() {
+            voice.sound.apply_parent_timing(self.timer.get_timing())?;
+        }
+        Ok(())
+    }
+}
+
+impl SoundStructure for Polyphony {
+    fn get(&self, base_frequency: &[SampleCalc], result: &mut [SampleCalc]) -> SoundResult<()> {
+        if base_frequency.len() != result.len() {
+            return Err(Error::BufferSize);
+        }
+        let buffer_size = base_frequency.len();
+        if buffer_size > self.buffer_size {
+            return Err(Error::BufferSize);
+        }
+        for item in result.iter_mut() {
+            *item = 0.0;
+        }
+        for voice in self.voices.borrow_mut().iter_mut() {
+            if voice.key.is_none() {
+                continue;
+            }
+            voice
+                .interval
+                .transpose(base_frequency, &mut voice.frequency_buffer[..buffer_size])?;
+            let wave_buffer = &mut voice.wave_buffer[..buffer_size];
+            let mut completed = match voice
+                .sound
+                .get(&voice.frequency_buffer[..buffer_size], wave_buffer)
+            {
+                Ok(()) => buffer_size,
+                Err(Error::ItemsCompleted(completed)) => completed,
+                Err(e) => return Err(e),
+            };
+            match voice.envelope.apply(&mut wave_buffer[..completed]) {
+                Ok(()) => {}
+                Err(Error::ItemsCompleted(envelope_completed)) => completed = envelope_completed,
+                Err(e) => return Err(e),
+            }
+            if completed < buffer_size {
+                voice.key = None;
+            }
+            for (item, wave) in result.iter_mut().zip(wave_buffer.iter()).take(completed) {
+                *item += *wave;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Maps the keys of an input device (e.g. a computer keyboard) to intervals, so the layout can
+/// be changed without modifying the instrument.
+#[derive(Debug, Clone)]
+pub struct KeyMap<K: Eq + Hash> {
+    intervals: HashMap<K, Interval>,
+}
+
+impl<K: Eq + Hash> KeyMap<K> {
+    /// custom constructor
+    pub fn new(intervals: HashMap<K, Interval>) -> KeyMap<K> {
+        KeyMap { intervals }
+    }
+
+    /// Constructor from a list of `(key, numerator, denominator)` items.
+    pub fn from_ratios(ratios: &[(K, u16, u16)]) -> SoundResult<KeyMap<K>>
+    where
+        K: Copy,
+    {
+        let mut intervals = HashMap::with_capacity(ratios.len());
+        for &(key, numerator, denominator) in ratios {
+            let _ = intervals.insert(key, Interval::new(numerator, denominator)?);
+        }
+        Ok(KeyMap { intervals })
+    }
+
+    /// Returns the interval of the key, or `None` if the key is not mapped.
+    pub fn get(&self, key: &K) -> Option<Interval> {
+        self.intervals.get(key).copied()
+    }
+
+    /// Maps the key to the interval. Returns the previous interval of the key.
+    pub fn set(&mut self, key: K, interval: Interval) -> Option<Interval> {
+        self.intervals.insert(key, interval)
+    }
+
+    /// Removes the key from the map. Returns its interval.
+    pub fn remove(&mut self, key: &K) -> Option<Interval> {
+        self.intervals.remove(key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sound::test_utils::*;
+
+    const SAMPLE_RATE: SampleCalc = 1000.0;
+
+    /// A pool of voices with 10 ms long envelope stages.
+    fn polyphony(voice_count: usize) -> (Polyphony, Vec<Rc<AmplitudeADSR>>) {
+        let polyphony = Polyphony::new(SAMPLE_RATE, BUFFER_SIZE_DEFAULT).unwrap();
+        let mut envelopes = Vec::new();
+        for _ in 0..voice_count {
+            let envelope =
+                Rc::new(AmplitudeADSR::new_with_time(SAMPLE_RATE, 0.01, 0.01, 0.5, 0.01).unwrap());
+            let _ = polyphony
+                .add_voice(TestSignal::new(SAMPLE_RATE, 1.0), envelope.clone())
+                .unwrap();
+            envelopes.push(envelope);
+        }
+        (polyphony, envelopes)
+    }
+
+    #[test]
+    fn polyphony_steals_the_oldest_voice() {
+        let (polyphony, _) = polyphony(2);
+        assert_eq!(polyphony.note_on(1, INTERVAL_UNISON).unwrap(), 0);
+        assert_eq!(polyphony.note_on(2, INTERVAL_UNISON).unwrap(), 1);
+        assert_eq!(polyphony.note_on(3, INTERVAL_UNISON).unwrap(), 0);
+        assert_eq!(polyphony.get_active_count(), 2);
+        // the stolen note can not be released any more
+        polyphony.note_off(1);
+        assert_eq!(polyphony.note_on(4, INTERVAL_UNISON).unwrap(), 1);
+        assert_eq!(polyphony.note_on(5, INTERVAL_UNISON).unwrap(), 0);
+    }
+
+    #[test]
+    fn polyphony_frees_the_voice_after_the_release() {
+        let (polyphony, _) = polyphony(2);
+        let frequency = [440.0; 50];
+        let mut samples = [0.0; 50];
+        let _ = polyphony.note_on(1, INTERVAL_UNISON).unwrap();
+        polyphony.get(&frequency, &mut samples).unwrap();
+        assert_eq!(samples[49], 0.5);
+        polyphony.note_off(1);
+        assert_eq!(polyphony.get_active_count(), 1);
+        polyphony.get(&frequency, &mut samples).unwrap();
+        assert_eq!(polyphony.get_active_count(), 0);
+        // the release fades out in 10 ms
+        assert!(samples
+            .windows(2)
+            .all(|pair| pair[1] < pair[0] || pair[1] == 0.0));
+        assert!(samples[..9].iter().all(|item| *item > 0.0));
+        assert!(samples[10..].iter().all(|item| *item == 0.0));
+        // the free voice is used for the next note
+        assert_eq!(polyphony.note_on(2, INTERVAL_UNISON).unwrap(), 0);
+    }
+
+    #[test]
+    fn polyphony_restart_restarts_the_envelopes() {
+        let (polyphony, envelopes) = polyphony(1);
+        let frequency = [440.0; 50];
+        let mut samples = [0.0; 50];
+        let _ = polyphony.note_on(1, INTERVAL_UNISON).unwrap();
+        polyphony.get(&frequency, &mut samples).unwrap();
+        assert_eq!(envelopes[0].get_amplitude(), 0.5);
+        polyphony.restart();
+        assert_eq!(polyphony.get_active_count(), 0);
+        assert_eq!(envelopes[0].get_amplitude(), 0.0);
+    }
+}
@@ -1,7 +1,9 @@
 use crate::sound::*;
 use num::*;
+use std::cmp::Ordering;
 use std::fmt;
 use std::ops::{Div, Mul};
+use std::str::FromStr;
 
 /// unison (1:1)
 pub const INTERVAL_UNISON: Interval = Interval {
@@ -11,6 +13,57 @@ pub const INTERVAL_UNISON: Interval = Interval {
     reciprocal: 1.0,
 };
 
+/// octave (2:1)
+pub const INTERVAL_OCTAVE: Interval = Interval {
+    numerator: 2,
+    denominator: 1,
+    ratio: 2.0,
+    reciprocal: 0.5,
+};
+
+/// = 69, the MIDI note number of the A4 reference pitch.
+pub const MIDI_NOTE_REFERENCE: u8 = 69;
+
+/// The maximal difference (in cents) between a cent value and the interval approximating it.
+/// It is below the [just-noticeable difference](https://en.wikipedia.org/wiki/Cent_(music)) of
+/// pitch.
+pub const INTERVAL_CENTS_TOLERANCE: SampleCalc = 0.5;
+
+/// The common names of the intervals (numerator, denominator, name).
+/// See: <https://en.wikipedia.org/wiki/List_of_pitch_intervals>,
+/// <https://gist.github.com/endolith/3098720>
+const INTERVAL_NAMES: [(u16, u16, &str); 25] = [
+    (1, 1, "unison"),
+    (2, 1, "octave"),
+    (3, 2, "perfect fifth"),
+    (4, 3, "perfect fourth"),
+    (5, 4, "major third"),
+    (5, 3, "major sixth"),
+    (6, 5, "minor third"),
+    (7, 6, "septimal minor third"),
+    (7, 5, "lesser septimal tritone"),
+    (7, 4, "augmented sixth"), // "harmonic seventh", "septimal minor seventh" too
+    (8, 7, "septimal major second"),
+    (8, 5, "minor sixth"),
+    (9, 8, "major second"), // "major tone" too
+    (9, 7, "septimal major third"),
+    (9, 5, "minor seventh"),
+    (10, 9, "minor tone"),
+    // (10, 8, ""),
+    (10, 7, "greater septimal tritone"),
+    // (11, 6, "major seventh"),
+    (11, 8, "lesser undecimal tritone"),
+    // (12, 11, "minor second"),
+    (13, 8, "acute minor sixth"),
+    (15, 8, "major seventh"),
+    (16, 15, "semitone"), // "minor second" too
+    (16, 9, "grave minor seventh"),
+    // (29, 16, "minor seventh"), // "twenty-ninth harmonic"
+    (31, 16, "augmented seventh"),
+    (45, 32, "augmented fourth"),
+    (64, 45, "diminished fifth"),
+];
+
 /// Harmonic musical interval (of frequencies), represented by a rational number.
 #[derive(Debug, Copy, Clone)]
 pub struct Interval {
@@ -39,6 +92,77 @@ impl Interval {
         Ok(interval)
     }
 
+    /// Approximates the interval of the given [cents](https://en.wikipedia.org/wiki/Cent_(music))
+    /// value with the simplest rational number, which is within `INTERVAL_CENTS_TOLERANCE`. It is
+    /// the best approximation (see `get_rational_approximation`) with the smallest sufficient
+    /// denominator limit. E.g. 702 cents gives the 3:2 perfect fifth.
+    pub fn from_cents(cents: SampleCalc) -> SoundResult<Interval> {
+        let octave: SampleCalc = 2.0;
+        let ratio = octave.powf(cents / 1200.0);
+        if !ratio.is_finite() {
+            return Err(Error::Overflow);
+        }
+        // Checks the best approximation with a denominator not exceeding the given limit.
+        let is_close = |max_denominator| match get_rational_approximation(ratio, max_denominator) {
+            Ok((numerator, denominator)) => {
+                let approximation = numerator as SampleCalc / denominator as SampleCalc;
+                (1200.0 * (approximation / ratio).log2()).abs() <= INTERVAL_CENTS_TOLERANCE
+            }
+            Err(_) => false,
+        };
+        if !is_close(u16::MAX) {
+            return Err(Error::Overflow);
+        }
+        // The error of the best approximation does not grow with its denominator limit, so the
+        // smallest sufficient limit is found by bisection.
+        let (mut low, mut high) = (1, u16::MAX);
+        while low < high {
+            let middle = low + (high - low) / 2;
+            if is_close(middle) {
+                high = middle;
+            } else {
+                low = middle + 1;
+            }
+        }
+        let (numerator, denominator) = get_rational_approximation(ratio, low)?;
+        Interval::new(numerator, denominator)
+    }
+
+    /// Approximates a frequency ratio with the simplest interval, which has a denominator not
+    /// exceeding `max_denominator`. E.g. 1.5 gives 3:2.
+    pub fn from_ratio(ratio: SampleCalc, max_denominator: u16) -> SoundResult<Interval> {
+        let (numerator, denominator) = get_rational_approximation(ratio, max_denominator)?;
+        Interval::new(numerator, denominator)
+    }
+
+    /// Gives the interval of a [MIDI](https://en.wikipedia.org/wiki/MIDI_tuning_standard) note
+    /// relative to the reference pitch (`MIDI_NOTE_REFERENCE`, A4). Without a `tuning` it is the
+    /// 12 tone equal temperament, approximated by `from_cents`. The `tuning` is a scale in the
+    /// format of `scale_from_scala`: the degrees above the reference note (without the 1:1
+    /// unison), the last one is the period (usually the 2:1 octave) which repeats the scale.
+    pub fn from_midi_note(note: u8, tuning: Option<&[Interval]>) -> SoundResult<Interval> {
+        let equal_temperament: Vec<Interval>;
+        let scale = match tuning {
+            Some(scale) => scale,
+            None => {
+                equal_temperament = (1..=12)
+                    .map(|step| Interval::from_cents(100.0 * step as SampleCalc))
+                    .collect::<SoundResult<Vec<Interval>>>()?;
+                &equal_temperament
+            }
+        };
+        let period = scale.last().ok_or(Error::SequenceEmpty)?;
+        let steps = note as i32 - MIDI_NOTE_REFERENCE as i32;
+        let degree = steps.rem_euclid(scale.len() as i32) as usize;
+        let interval = period.pow(steps.div_euclid(scale.len() as i32))?;
+        if degree == 0 {
+            return Ok(interval);
+        }
+        interval
+            .checked_mul(&scale[degree - 1])
+            .ok_or(Error::Overflow)
+    }
+
     /// Reduces to lowest terms with dividing by the greatest common divisor.
     fn reduce(&mut self) {
         let d = self.numerator.gcd(&self.denominator);
@@ -62,16 +186,70 @@ impl Interval {
         Ok(())
     }
 
+    /// Returns the numerator of the interval (in lowest terms).
+    pub fn get_numerator(&self) -> u16 {
+        self.numerator
+    }
+
+    /// Returns the denominator of the interval (in lowest terms).
+    pub fn get_denominator(&self) -> u16 {
+        self.denominator
+    }
+
     /// Returns the ratio of the frequency interval.
     pub fn get_ratio(&self) -> SampleCalc {
         self.ratio
     }
 
+    /// Returns the size of the interval in [cents](https://en.wikipedia.org/wiki/Cent_(music)).
+    /// E.g. the 5:4 major third is ~386.3 cents, while it is 400 cents in equal temperament.
+    pub fn to_cents(&self) -> SampleCalc {
+        1200.0 * self.ratio.log2()
+    }
+
     /// Returns the reciprocal of the frequency interval.
     pub fn get_recip(&self) -> SampleCalc {
         self.reciprocal
     }
 
+    /// Returns the [inversion](https://en.wikipedia.org/wiki/Inversion_(music)#Intervals) of the
+    /// interval (it's octave complement), e.g. the 3:2 fifth inverts to the 4:3 fourth.
+    /// The interval must be in the [1:1, 2:1] range.
+    pub fn invert(&self) -> SoundResult<Interval> {
+        if self.numerator < self.denominator || self.numerator as u32 > 2 * self.denominator as u32
+        {
+            return Err(Error::IntervalInvalid);
+        }
+        if self.denominator > u16::MAX / 2 {
+            return Err(Error::Overflow);
+        }
+        Ok(INTERVAL_OCTAVE / *self)
+    }
+
+    /// Stacks the interval `n` times, e.g. 3:2 to the power of 2 is 9:4. Negative `n` stacks the
+    /// reciprocal interval.
+    pub fn pow(&self, n: i32) -> SoundResult<Interval> {
+        if self.is_unison() {
+            return Ok(INTERVAL_UNISON);
+        }
+        let (base_numerator, base_denominator) = if n < 0 {
+            (self.denominator, self.numerator)
+        } else {
+            (self.numerator, self.denominator)
+        };
+        let mut numerator: u16 = 1;
+        let mut denominator: u16 = 1;
+        for _ in 0..n.unsigned_abs() {
+            numerator = numerator
+                .checked_mul(base_numerator)
+                .ok_or(Error::Overflow)?;
+            denominator = denominator
+                .checked_mul(base_denominator)
+                .ok_or(Error::Overflow)?;
+        }
+        Interval::new(numerator, denominator)
+    }
+
     /// True, if the interval is `1:1`, aka. unison.
     pub fn is_unison(&self) -> bool {
         self.numerator == self.denominator
@@ -84,44 +262,23 @@ impl Interval {
         } else {
             (self.denominator, self.numerator)
         };
-        // https://en.wikipedia.org/wiki/List_of_pitch_intervals
-        // https://gist.github.com/endolith/3098720
-        match ratio {
-            (1, 1) => "unison",
-            (2, 1) => "octave",
-            (3, 2) => "perfect fifth",
-            (4, 3) => "perfect fourth",
-            (5, 4) => "major third",
-            (5, 3) => "major sixth",
-            (6, 5) => "minor third",
-            (7, 6) => "septimal minor third",
-            (7, 5) => "lesser septimal tritone",
-            (7, 4) => "augmented sixth", // "harmonic seventh", "septimal minor seventh" too
-            (8, 7) => "septimal major second",
-            (8, 5) => "minor sixth",
-            (9, 8) => "major second", // "major tone" too
-            (9, 7) => "septimal major third",
-            (9, 5) => "minor seventh",
-
-            (10, 9) => "minor tone",
-            // (10, 8) => "",
-            (10, 7) => "greater septimal tritone",
-            // (11, 6) => "major seventh",
-            (11, 8) => "lesser undecimal tritone",
-            // (12, 11) => "minor second",
-            (13, 8) => "acute minor sixth",
-            (15, 8) => "major seventh",
-            (16, 15) => "semitone", // "minor second" too
-            (16, 9) => "grave minor seventh",
-            // (29, 16) => "minor seventh", // "twenty-ninth harmonic"
-            (31, 16) => "augmented seventh",
-            (45, 32) => "augmented fourth",
-            (64, 45) => "diminished fifth",
-            _ => "",
-        }
+        INTERVAL_NAMES
+            .iter()
+            .find(|(numerator, denominator, _)| (*numerator, *denominator) == ratio)
+            .map_or("", |(_, _, name)| name)
     }
 
-    /// Change a frequency according to the interval.
+    /// Gives the (ascending) interval of the given common name, e.g. "perfect fifth" is 3:2.
+    pub fn from_name(name: &str) -> SoundResult<Interval> {
+        let (numerator, denominator, _) = INTERVAL_NAMES
+            .iter()
+            .find(|(_, _, interval_name)| *interval_name == name)
+            .ok_or(Error::ItemInvalid)?;
+        Interval::new(*numerator, *denominator)
+    }
+
+    /// Change a frequency according to the interval. A result out of the hearing range is an
+    /// error, see also `change_frequency_clamped()`.
     pub fn change_frequency(&self, frequency: SampleCalc) -> SoundResult<SampleCalc> {
         let new_frequency = frequency * self.ratio;
         if new_frequency < TONE_FREQUENCY_MIN {
@@ -133,6 +290,12 @@ impl Interval {
         Ok(new_frequency)
     }
 
+    /// Change a frequency according to the interval. A result out of the hearing range is
+    /// clamped to [`TONE_FREQUENCY_MIN`, `TONE_FREQUENCY_MAX`] instead of being an error.
+    pub fn change_frequency_clamped(&self, frequency: SampleCalc) -> SampleCalc {
+        (frequency * self.ratio).clamp(TONE_FREQUENCY_MIN, TONE_FREQUENCY_MAX)
+    }
+
     /// Change a frequency according to the interval's reciprocal.
     pub fn reverse_frequency(&self, frequency: SampleCalc) -> SoundResult<SampleCalc> {
         let new_frequency = frequency * self.reciprocal;
@@ -145,7 +308,9 @@ impl Interval {
         Ok(new_frequency)
     }
 
-    /// Change a frequency according to the interval.
+    /// Change a frequency according to the interval. If any of the results is out of the
+    /// hearing range, the whole buffer is rejected with an error, see also
+    /// `transpose_clamped()`.
     pub fn transpose(
         &self,
         base_frequency: &[SampleCalc],
@@ -165,6 +330,24 @@ impl Interval {
         }
         Ok(())
     }
+
+    /// Change a frequency according to the interval. The results out of the hearing range are
+    /// clamped to [`TONE_FREQUENCY_MIN`, `TONE_FREQUENCY_MAX`], so e.g. a glissando sweeping
+    /// past the range continues at the limit, instead of aborting the buffer.
+    pub fn transpose_clamped(
+        &self,
+        base_frequency: &[SampleCalc],
+        result: &mut [SampleCalc],
+    ) -> SoundResult<()> {
+        if base_frequency.len() != result.len() {
+            return Err(Error::BufferSize);
+        }
+        for (new_frequency, frequency) in result.iter_mut().zip(base_frequency) {
+            *new_frequency =
+                (*frequency * self.ratio).clamp(TONE_FREQUENCY_MIN, TONE_FREQUENCY_MAX);
+        }
+        Ok(())
+    }
 }
 
 impl Mul for Interval {
@@ -183,6 +366,15 @@ impl Mul for Interval {
     }
 }
 
+impl CheckedMul for Interval {
+    fn checked_mul(&self, v: &Self) -> Option<Self> {
+        let numerator = self.numerator as u32 * v.numerator as u32;
+        let denominator = self.denominator as u32 * v.denominator as u32;
+        let d = numerator.gcd(&denominator);
+        Interval::new((numerator / d).to_u16()?, (denominator / d).to_u16()?).ok()
+    }
+}
+
 impl Div for Interval {
     type Output = Interval;
 
@@ -199,14 +391,122 @@ impl Div for Interval {
     }
 }
 
+impl PartialEq for Interval {
+    fn eq(&self, other: &Interval) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Interval {}
+
+impl PartialOrd for Interval {
+    fn partial_cmp(&self, other: &Interval) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Intervals are ordered by their ratio (compared exactly, as rational numbers).
+impl Ord for Interval {
+    fn cmp(&self, other: &Interval) -> Ordering {
+        (self.numerator as u32 * other.denominator as u32)
+            .cmp(&(other.numerator as u32 * self.denominator as u32))
+            .then(self.numerator.cmp(&other.numerator))
+            .then(self.denominator.cmp(&other.denominator))
+    }
+}
+
 impl From<Interval> for SampleCalc {
     fn from(interval: Interval) -> Self {
         interval.ratio
     }
 }
 
+/// The alternate format (`{:#}`) shows the size in cents too, e.g. `5:4 (386.3¢)`.
 impl fmt::Display for Interval {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}:{}", self.numerator, self.denominator)
+        if f.alternate() {
+            write!(
+                f,
+                "{}:{} ({:.1}¢)",
+                self.numerator,
+                self.denominator,
+                self.to_cents()
+            )
+        } else {
+            write!(f, "{}:{}", self.numerator, self.denominator)
+        }
+    }
+}
+
+/// Parses the `numerator:denominator` format, e.g. `"3:2"`.
+impl FromStr for Interval {
+    type Err = Error;
+
+    fn from_str(text: &str) -> SoundResult<Interval> {
+        let mut parts = text.trim().splitn(2, ':');
+        let mut parse_next = || -> SoundResult<u16> {
+            parts
+                .next()
+                .and_then(|part| part.trim().parse().ok())
+                .ok_or(Error::ParseInvalid)
+        };
+        let numerator = parse_next()?;
+        let denominator = parse_next()?;
+        Interval::new(numerator, denominator)
+    }
+}
+
+/// Serialized in the `numerator:denominator` format, e.g. `"3:2"`.
+#[cfg(feature = "serde")]
+impl serde::Serialize for Interval {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// Deserialized from the `numerator:denominator` format, it is validated by `Interval::new()`.
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Interval {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        text.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cents_round_trip() {
+        for cents in [
+            0.0, 100.0, 386.3137, 701.955, 1200.0, -702.0, -1200.12, 2400.0, 3.5,
+        ]
+        .iter()
+        {
+            let interval = Interval::from_cents(*cents).unwrap();
+            let difference = interval.to_cents() - *cents;
+            assert!(
+                difference.abs() <= INTERVAL_CENTS_TOLERANCE,
+                "{} cents: {}",
+                cents,
+                interval
+            );
+        }
+        let fifth = Interval::from_cents(702.0).unwrap();
+        assert_eq!((fifth.get_numerator(), fifth.get_denominator()), (3, 2));
+        let third = Interval::from_cents(386.3137).unwrap();
+        assert_eq!((third.get_numerator(), third.get_denominator()), (5, 4));
+    }
+
+    #[test]
+    fn from_ratio_finds_simple_intervals() {
+        let fifth = Interval::from_ratio(1.5, 64).unwrap();
+        assert_eq!((fifth.get_numerator(), fifth.get_denominator()), (3, 2));
+        let third = Interval::from_ratio(1.25, 64).unwrap();
+        assert_eq!((third.get_numerator(), third.get_denominator()), (5, 4));
+        let bounded = Interval::from_ratio(1.333, 2).unwrap();
+        assert_eq!((bounded.get_numerator(), bounded.get_denominator()), (3, 2));
+        assert!(Interval::from_ratio(-1.5, 64).is_err());
     }
 }
@@ -0,0 +1,114 @@
+use crate::sound::*;
+#[cfg(not(feature = "precision-f64"))]
+use std::f32::consts::{FRAC_PI_2, LOG2_E, PI};
+#[cfg(feature = "precision-f64")]
+use std::f64::consts::{FRAC_PI_2, LOG2_E, PI};
+
+/// Fast approximations of transcendental functions, trading accuracy for speed.
+pub trait MathFast<T> {
+    /// Approximation of the sine function, the absolute error is below 0.0011.
+    fn sin_fast(self) -> T;
+    /// Approximation of the cosine function, the absolute error is below 0.0011.
+    fn cos_fast(self) -> T;
+    /// Approximation of the sine and cosine of the same angle, with a single range reduction.
+    /// Returns `(sine, cosine)`.
+    fn sincos_fast(self) -> (T, T);
+    /// Approximation of `2^x`, assembled from the bits of the floating point number. The
+    /// relative error is below 0.0002. Results below the normal range are flushed to zero.
+    fn pow2_fast(self) -> T;
+    /// Approximation of `e^x`, see `pow2_fast()`.
+    fn exp_fast(self) -> T;
+}
+
+impl MathFast<SampleCalc> for SampleCalc {
+    fn sin_fast(self) -> SampleCalc {
+        sin_reduced(reduce_angle(self))
+    }
+
+    fn cos_fast(self) -> SampleCalc {
+        sin_reduced(reduce_angle(self + FRAC_PI_2))
+    }
+
+    fn sincos_fast(self) -> (SampleCalc, SampleCalc) {
+        let angle = reduce_angle(self);
+        // cos(x) = sin(x + π/2), a single wrap is enough to keep it in the [-π, π) range.
+        let mut quadrature = angle + FRAC_PI_2;
+        if quadrature >= PI {
+            quadrature -= PI2;
+        }
+        (sin_reduced(angle), sin_reduced(quadrature))
+    }
+
+    fn pow2_fast(self) -> SampleCalc {
+        if self < -126.0 {
+            return 0.0;
+        }
+        if self >= 128.0 {
+            return SampleCalc::INFINITY;
+        }
+        let integer = self.floor();
+        let fraction = self - integer;
+        // The exponent bits give 2^integer, a cubic polynomial approximates 2^fraction.
+        #[cfg(not(feature = "precision-f64"))]
+        let exponent = SampleCalc::from_bits(((integer as i32 + 127) as u32) << 23);
+        #[cfg(feature = "precision-f64")]
+        let exponent = SampleCalc::from_bits(((integer as i64 + 1023) as u64) << 52);
+        exponent
+            * (1.0 + fraction * (0.695_976_1 + fraction * (0.224_974_5 + fraction * 0.079_049_4)))
+    }
+
+    fn exp_fast(self) -> SampleCalc {
+        (self * LOG2_E).pow2_fast()
+    }
+}
+
+/// Maps the angle into the [-π, π) range.
+#[inline]
+fn reduce_angle(angle: SampleCalc) -> SampleCalc {
+    angle - PI2 * (angle / PI2 + 0.5).floor()
+}
+
+/// Parabolic sine approximation with an extra precision step, valid in the [-π, π] range.
+/// See: [fast and accurate sine/cosine](https://web.archive.org/web/20171228230531/http://forum.devmaster.net/t/fast-and-accurate-sine-cosine/9648)
+#[inline]
+fn sin_reduced(angle: SampleCalc) -> SampleCalc {
+    const B: SampleCalc = 4.0 / PI;
+    const C: SampleCalc = -4.0 / (PI * PI);
+    const P: SampleCalc = 0.225;
+    let parabola = B * angle + C * angle * angle.abs();
+    P * (parabola * parabola.abs() - parabola) + parabola
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sin_cos_fast_accuracy() {
+        let step_count = 10000;
+        for step in 0..=step_count {
+            let angle = -PI + PI2 * step as SampleCalc / step_count as SampleCalc;
+            let (sine, cosine) = angle.sincos_fast();
+            assert!((angle.sin_fast() - angle.sin()).abs() < 0.0011, "{}", angle);
+            assert!((angle.cos_fast() - angle.cos()).abs() < 0.0011, "{}", angle);
+            assert!((sine - angle.sin()).abs() < 0.0011, "{}", angle);
+            assert!((cosine - angle.cos()).abs() < 0.0011, "{}", angle);
+        }
+    }
+
+    #[test]
+    fn pow2_exp_fast_error_bound() {
+        let step_count = 10000;
+        for step in 0..=step_count {
+            let x = -20.0 + 40.0 * step as SampleCalc / step_count as SampleCalc;
+            let error_pow2 = (x.pow2_fast() - x.exp2()) / x.exp2();
+            assert!(error_pow2.abs() < 0.0002, "{}: {}", x, error_pow2);
+            let error_exp = (x.exp_fast() - x.exp()) / x.exp();
+            assert!(error_exp.abs() < 0.0002, "{}: {}", x, error_exp);
+        }
+        let tiny: SampleCalc = -130.0;
+        assert_eq!(tiny.pow2_fast(), 0.0);
+        let huge: SampleCalc = 130.0;
+        assert_eq!(huge.pow2_fast(), SampleCalc::INFINITY);
+    }
+}
@@ -2,23 +2,53 @@
 pub mod amplitude;
 /// Fuctions which provide amplitude changes for overtones also.
 pub mod amplitude_overtones;
+/// Analysis of sound samples.
+pub mod analysis;
+/// Audio callback shared by the sound output backends.
+pub mod backend;
+/// Sound output backend without a device, for testing.
+pub mod backend_null;
 /// Error messages.
 pub mod errors;
+/// Sound effects and filters.
+pub mod filter;
 /// Fuctions which provide frequency changes.
 pub mod frequency;
+/// Instruments and voice management.
+pub mod instrument;
 /// Frequency interval.
 pub mod interval;
+/// Fast approximations of mathematical functions.
+pub mod math;
+/// Modulation sources.
+pub mod modulation;
+/// Noise generators.
+pub mod noise;
 /// Musical note structures.
 pub mod note;
 /// Time and tempo based progress measurement.
 pub mod progress;
+/// Offline rendering of sound generators into files.
+pub mod render;
 /// Rhythm section.
 pub mod rhythm;
+/// Playback of recorded samples.
+pub mod sample;
+/// Import and export of the [Scala](http://www.huygens-fokker.org/scala/scl_format.html)
+/// scale file format.
+pub mod scala;
+/// Shared fixtures of the unit tests.
+#[cfg(test)]
+mod test_utils;
 /// Timing for the duration of sound components.
 pub mod timing;
 /// Fuctions which provide complete waveforms.
 pub mod wave;
 
+/// [`cpal`](https://github.com/RustAudio/cpal) backend for sound playback.
+#[cfg(feature = "be-cpal")]
+pub mod backend_cpal;
+
 /// [`PortAudio`](https://github.com/RustAudio/rust-portaudio) backend for sound playback.
 #[cfg(feature = "be-portaudio")]
 pub mod backend_portaudio;
@@ -33,6 +63,11 @@ pub mod backend_sdl2;
 
 pub use self::amplitude::*;
 pub use self::amplitude_overtones::*;
+pub use self::analysis::*;
+pub use self::backend::*;
+#[cfg(feature = "be-cpal")]
+pub use self::backend_cpal::*;
+pub use self::backend_null::*;
 #[cfg(feature = "be-portaudio")]
 pub use self::backend_portaudio::*;
 #[cfg(feature = "be-rsoundio")]
@@ -40,23 +75,43 @@ pub use self::backend_rsoundio::*;
 #[cfg(feature = "be-sdl2")]
 pub use self::backend_sdl2::*;
 pub use self::errors::*;
+pub use self::filter::*;
 pub use self::frequency::*;
+pub use self::instrument::*;
 pub use self::interval::*;
+pub use self::math::*;
+pub use self::modulation::*;
+pub use self::noise::*;
 pub use self::note::*;
 pub use self::progress::*;
+pub use self::render::*;
 pub use self::rhythm::*;
+pub use self::sample::*;
+pub use self::scala::*;
 pub use self::timing::*;
 pub use self::wave::*;
 
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender};
+
 /// Precision of the finally produced samples.
 pub type SampleOutput = f32;
-/// Precision of calculations. Changing it to `f64` can slow down some calculations 4 times.
+/// Precision of calculations. The `precision-f64` feature changes it to `f64`, which can slow
+/// down some calculations 4 times.
+#[cfg(not(feature = "precision-f64"))]
 pub type SampleCalc = f32;
+/// Precision of calculations. The `precision-f64` feature changes it to `f64`, which can slow
+/// down some calculations 4 times.
+#[cfg(feature = "precision-f64")]
+pub type SampleCalc = f64;
 
 /// Sample count for calculations. It affects both latency and computation performance.
 /// Latency perception for musical instruments: over ~12ms is already disturbing for some players.
 pub const BUFFER_SIZE_DEFAULT: usize = 512;
 
+/// = 5 ms, the default duration of the fade-in applied by the backends when playback starts.
+/// It prevents clicks when the first generated sample is not zero.
+pub const FADE_IN_DURATION_DEFAULT: SampleCalc = 0.005;
+
 /// = 3 Hz, the lowest feelable frequency. Tones below it will not be calculated. The hearable
 /// lowest is 12 Hz.
 /// See: [hearing range](https://en.wikipedia.org/wiki/Hearing_range#Humans)
@@ -75,26 +130,220 @@ pub const TONE_FREQUENCY_MAX: SampleCalc = 28000.0;
 pub const SAMPLE_RATE_DEFAULT: u32 = 192_000;
 
 /// = π x 2
-// pub const PI2: SampleCalc = ::std::f64::consts::PI * 2.0;
+#[cfg(feature = "precision-f64")]
+pub const PI2: SampleCalc = ::std::f64::consts::PI * 2.0;
+/// = π x 2
+#[cfg(not(feature = "precision-f64"))]
 pub const PI2: SampleCalc = ::std::f32::consts::PI * 2.0;
 
+/// = -60 dB, the lowest amplitude used by exponential fades. A geometric interpolation can not
+/// start from, or reach zero.
+pub const FADE_EXPONENTIAL_AMPLITUDE_MIN: SampleCalc = 0.001;
+
+/// The level above which the limiter of the backends starts compressing the samples.
+pub const LIMITER_THRESHOLD: SampleCalc = 0.8;
+
+/// The default pole of the DC blocker filter. The closer to 1.0, the lower its cutoff frequency.
+pub const DC_BLOCKER_POLE_DEFAULT: SampleCalc = 0.995;
+
+/// Capacity of the telemetry queue of the backends. Events are dropped when it is full.
+pub const TELEMETRY_QUEUE_SIZE: usize = 64;
+
+/// Lightweight events sent from the audio callback of the backends.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum TelemetryEvent {
+    /// The output buffer ran out of samples.
+    Underrun,
+    /// The given number of samples were out of the [-1.0, 1.0] range in a buffer.
+    Clip(usize),
+    /// A command was applied by the sound generator.
+    CommandApplied,
+    /// The sound generator returned an error, the output is silenced.
+    GeneratorFailed,
+    /// The output stream of the backend reported an error (e.g. the device was disconnected).
+    StreamError,
+}
+
+/// Audio callback side of the telemetry queue of the backends.
+#[derive(Debug, Clone)]
+pub struct TelemetrySender {
+    sender: SyncSender<TelemetryEvent>,
+}
+
+impl TelemetrySender {
+    /// Queues the event without blocking. The event is dropped when the queue is full.
+    pub fn send(&self, event: TelemetryEvent) {
+        let _ = self.sender.try_send(event);
+    }
+
+    /// Queues a `Clip` event, if any of the samples are out of the [-1.0, 1.0] range.
+    pub fn send_clip_count(&self, samples: &[SampleCalc]) {
+        let clip_count = get_clip_count(samples);
+        if clip_count > 0 {
+            self.send(TelemetryEvent::Clip(clip_count));
+        }
+    }
+}
+
+/// Host thread side of the telemetry queue of the backends. It delivers the events to the
+/// registered callback function.
+#[derive(Debug)]
+pub struct TelemetryQueue {
+    receiver: Receiver<TelemetryEvent>,
+    callback: Option<fn(TelemetryEvent)>,
+}
+
+impl TelemetryQueue {
+    /// Creates the queue of `TELEMETRY_QUEUE_SIZE` capacity. The bounded queue is preallocated,
+    /// sending does not allocate in the audio callback.
+    pub fn new() -> (TelemetrySender, TelemetryQueue) {
+        let (sender, receiver) = sync_channel(TELEMETRY_QUEUE_SIZE);
+        (
+            TelemetrySender { sender },
+            TelemetryQueue {
+                receiver,
+                callback: None,
+            },
+        )
+    }
+
+    /// Sets the function receiving the telemetry events.
+    pub fn set_callback(&mut self, callback: fn(TelemetryEvent)) {
+        self.callback = Some(callback);
+    }
+
+    /// Delivers the queued events to the registered callback. Without a callback the events are
+    /// dropped.
+    pub fn process(&self) {
+        while let Ok(event) = self.receiver.try_recv() {
+            if let Some(callback) = self.callback {
+                callback(event);
+            }
+        }
+    }
+}
+
+/// Converts a calculated sample to the precision of the output.
+#[inline]
+#[allow(trivial_numeric_casts)]
+pub fn get_output_sample(sample: SampleCalc) -> SampleOutput {
+    sample as SampleOutput
+}
+
+/// Counts the samples which are out of the [-1.0, 1.0] range.
+pub fn get_clip_count(samples: &[SampleCalc]) -> usize {
+    samples.iter().filter(|item| item.abs() > 1.0).count()
+}
+
+/// Returns the largest absolute value of the samples (0.0 for an empty buffer).
+pub fn peak(samples: &[SampleCalc]) -> SampleCalc {
+    samples.iter().fold(0.0, |max, item| max.max(item.abs()))
+}
+
+/// Returns the root mean square of the samples (0.0 for an empty buffer).
+pub fn rms(samples: &[SampleCalc]) -> SampleCalc {
+    if samples.is_empty() {
+        return 0.0;
+    }
+    let sum: SampleCalc = samples.iter().map(|item| item * item).sum();
+    (sum / samples.len() as SampleCalc).sqrt()
+}
+
+/// Converts an amplitude to decibels relative to full scale (1.0 is 0 dBFS). Silence gives
+/// negative infinity.
+pub fn to_db(amplitude: SampleCalc) -> SampleCalc {
+    20.0 * amplitude.abs().log10()
+}
+
+/// Soft limiter: samples below `LIMITER_THRESHOLD` are left unchanged, the ones above are
+/// smoothly compressed (with `tanh`) into the range [-1.0, 1.0].
+pub fn soft_limit(samples: &mut [SampleCalc]) {
+    let knee = 1.0 - LIMITER_THRESHOLD;
+    for item in samples.iter_mut() {
+        let level = item.abs();
+        if level > LIMITER_THRESHOLD {
+            let limited = LIMITER_THRESHOLD + knee * ((level - LIMITER_THRESHOLD) / knee).tanh();
+            *item = limited.copysign(*item);
+        }
+    }
+}
+
+/// Creates the amplitude function of the fade-in applied by the backends when playback starts,
+/// or `None` if it is turned off (the `duration` is 0.0).
+pub fn get_fade_in(sample_rate: u32, duration: SampleCalc) -> SoundResult<Option<FadeLinear>> {
+    if duration == 0.0 {
+        return Ok(None);
+    }
+    FadeLinear::new_with_time(sample_rate as SampleCalc, duration, 1.0).map(Some)
+}
+
+/// Applies the fade-in on the output samples. It is turned off (set to `None`) when finished.
+pub fn apply_fade_in(fade_in: &mut Option<FadeLinear>, samples: &mut [SampleCalc]) {
+    if let Some(ref fade) = *fade_in {
+        // the output buffer can be longer than the buffer of the amplitude function
+        for chunk in samples.chunks_mut(BUFFER_SIZE_DEFAULT) {
+            if fade.apply(chunk).is_err() {
+                *fade_in = None;
+                return;
+            }
+        }
+    }
+}
+
 /// Sound sample generator for output (playback). It can also take real-time input (commands),
 /// thus musical instruments can be realized with it.
 pub trait SoundGenerator: Send {
     /// Message type.
     type Command;
     /// Get the next `sample_count` amount of samples, put them in `result`
-    fn get_samples(&mut self, sample_count: usize, result: &mut Vec<SampleCalc>);
+    fn get_samples(&mut self, sample_count: usize, result: &mut Vec<SampleCalc>)
+        -> SoundResult<()>;
     /// Send a message to the `SoundGenerator`.
     fn process_command(&mut self, command: Self::Command);
+    /// Provides the time elapsed since the start of the generation (in seconds).
+    fn get_time(&self) -> SampleCalc;
+    /// Restarts the generator to a clean state (time, phases, envelopes), without
+    /// reconstructing it. The default implementation does nothing.
+    fn reset(&mut self) {}
+    /// Moves forward `frames` samples in time. The default implementation generates the samples
+    /// in chunks of `BUFFER_SIZE_DEFAULT` size, and drops them.
+    fn advance(&mut self, frames: usize) -> SoundResult<()> {
+        let mut buffer = vec![0.0; frames.min(BUFFER_SIZE_DEFAULT)];
+        let mut remaining = frames;
+        while remaining > 0 {
+            let sample_count = remaining.min(BUFFER_SIZE_DEFAULT);
+            self.get_samples(sample_count, &mut buffer)?;
+            remaining -= sample_count;
+        }
+        Ok(())
+    }
+    /// Provides the actual position both in seconds and in beats of the given tempo.
+    fn current_position(&self, tempo: &Tempo) -> (SampleCalc, SampleCalc) {
+        let seconds = self.get_time();
+        (seconds, seconds / tempo.get_duration())
+    }
 }
 
 /// A sound component. Can be a simple wave or a complex structure of waves.
 pub trait SoundStructure: HasTimer {
-    /// Returns the calculated samples in the `result` buffer.
+    /// Returns the calculated samples in the `result` buffer. The buffers can be shorter than
+    /// the reserved buffer size (e.g. when a sequence item ends inside a buffer).
     fn get(&self, base_frequency: &[SampleCalc], result: &mut [SampleCalc]) -> SoundResult<()>;
 }
 
+/// A sound component with stereo output. A mono `SoundStructure` can be turned into a stereo
+/// one with `MonoToStereo`.
+pub trait StereoStructure: HasTimer {
+    /// Returns the calculated samples in separate `left` and `right` buffers. All the buffers
+    /// must have the same length.
+    fn get_stereo(
+        &self,
+        base_frequency: &[SampleCalc],
+        left: &mut [SampleCalc],
+        right: &mut [SampleCalc],
+    ) -> SoundResult<()>;
+}
+
 /// A structure of music.
 pub trait MusicStructure {
     /// Returns the calculated samples in the `result` buffer.
@@ -106,6 +355,25 @@ pub trait MusicStructure {
     ) -> SoundResult<()>;
 }
 
+/// Captures `total_samples` samples of the generator's output, calling `get_samples` in chunks
+/// of `buffer_size` (the last chunk can be shorter). The result is mono: duplicating the samples
+/// for the output channels is the task of the backends.
+pub fn render_to_vec<T>(
+    generator: &mut dyn SoundGenerator<Command = T>,
+    buffer_size: usize,
+    total_samples: usize,
+) -> SoundResult<Vec<SampleCalc>> {
+    let buffer_size = buffer_size.max(1);
+    let mut buffer = vec![0.0; buffer_size];
+    let mut result = Vec::with_capacity(total_samples);
+    while result.len() < total_samples {
+        let sample_count = (total_samples - result.len()).min(buffer_size);
+        generator.get_samples(sample_count, &mut buffer)?;
+        result.extend_from_slice(&buffer[..sample_count]);
+    }
+    Ok(result)
+}
+
 /// Calculates the period of one sample for the given sample rate.
 /// Sample rates below 1.0 are considered to be invalid.
 pub fn get_sample_time(sample_rate: SampleCalc) -> SoundResult<SampleCalc> {
@@ -115,3 +383,230 @@ pub fn get_sample_time(sample_rate: SampleCalc) -> SoundResult<SampleCalc> {
         Ok(1.0 / sample_rate)
     }
 }
+
+/// Finds the best rational approximation `(numerator, denominator)` of a positive value, with
+/// the denominator not exceeding `max_denominator`. The simplest fraction is chosen, which is
+/// equal to the value within the precision of `SampleCalc`. It uses
+/// [continued fractions](https://en.wikipedia.org/wiki/Continued_fraction#Best_rational_approximations)
+/// (including semiconvergents).
+pub fn get_rational_approximation(
+    value: SampleCalc,
+    max_denominator: u16,
+) -> SoundResult<(u16, u16)> {
+    if max_denominator == 0 {
+        return Err(Error::DenominatorInvalid);
+    }
+    if !value.is_finite() || value <= 0.0 {
+        return Err(Error::NumeratorInvalid);
+    }
+    // `SampleCalc` is `f64` with the precision-f64 feature
+    #[allow(trivial_numeric_casts, clippy::unnecessary_cast)]
+    let (target, epsilon) = (value as f64, SampleCalc::EPSILON as f64);
+    let max_denominator = u64::from(max_denominator);
+    let max_numerator = u64::from(u16::MAX);
+    let tolerance = target * epsilon;
+    // the previous and the actual convergent
+    let (mut p0, mut q0, mut p1, mut q1) = (0_u64, 1_u64, 1_u64, 0_u64);
+    let mut remainder = target;
+    loop {
+        let term = remainder.floor();
+        if q1 == 0 && term > f64::from(u16::MAX) {
+            return Err(Error::Overflow);
+        }
+        // a later huge term exceeds the limits anyway, the semiconvergent is chosen then
+        let term = term.min(f64::from(u16::MAX) + 1.0) as u64;
+        let q2 = term * q1 + q0;
+        let p2 = term * p1 + p0;
+        if q2 > max_denominator || p2 > max_numerator {
+            // the best semiconvergent within the limits competes with the last convergent
+            let k = ((max_denominator - q0) / q1)
+                .min((max_numerator - p0).checked_div(p1).unwrap_or(u64::MAX));
+            let (ps, qs) = (k * p1 + p0, k * q1 + q0);
+            let error_semi = (ps as f64 / qs as f64 - target).abs();
+            let error_last = (p1 as f64 / q1 as f64 - target).abs();
+            if error_semi < error_last {
+                p1 = ps;
+                q1 = qs;
+            }
+            break;
+        }
+        p0 = p1;
+        q0 = q1;
+        p1 = p2;
+        q1 = q2;
+        let fraction = remainder - term as f64;
+        if fraction < 1e-9 || (p1 as f64 / q1 as f64 - target).abs() <= tolerance {
+            break;
+        }
+        remainder = 1.0 / fraction;
+    }
+    if p1 == 0 {
+        return Err(Error::NumeratorInvalid);
+    }
+    Ok((p1 as u16, q1 as u16))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn fade_in_ramps_up_the_first_buffer() {
+        let mut fade_in = get_fade_in(48000, 0.005).unwrap();
+        let mut samples = vec![1.0; BUFFER_SIZE_DEFAULT];
+        apply_fade_in(&mut fade_in, &mut samples);
+        assert!(samples[0] < 0.01, "{}", samples[0]);
+        assert!(samples.windows(2).all(|pair| pair[0] <= pair[1]));
+        assert_eq!(samples[BUFFER_SIZE_DEFAULT - 1], 1.0);
+        assert!(fade_in.is_none());
+
+        assert!(get_fade_in(48000, 0.0).unwrap().is_none());
+        assert!(get_fade_in(48000, -0.005).is_err());
+    }
+
+    #[test]
+    fn fade_in_of_a_long_buffer() {
+        let mut fade_in = get_fade_in(48000, 0.05).unwrap();
+        let mut samples = vec![1.0; 4 * BUFFER_SIZE_DEFAULT];
+        apply_fade_in(&mut fade_in, &mut samples);
+        assert!(samples.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!(fade_in.is_some());
+    }
+
+    #[test]
+    fn rational_approximation_with_a_large_term() {
+        // the second continued fraction term is about 10^6
+        assert_eq!(get_rational_approximation(1.000001, 64).unwrap(), (1, 1));
+        assert_eq!(get_rational_approximation(1.5, 64).unwrap(), (3, 2));
+        assert!(matches!(
+            get_rational_approximation(100000.0, 64),
+            Err(Error::Overflow)
+        ));
+    }
+
+    /// Generator of a sine wave, which counts the generated samples.
+    struct SineGenerator {
+        sample_rate: SampleCalc,
+        frequency_buffer: Vec<SampleCalc>,
+        wave: Wave,
+        frame_count: usize,
+    }
+
+    impl SineGenerator {
+        fn new(sample_rate: SampleCalc) -> SineGenerator {
+            SineGenerator {
+                sample_rate,
+                frequency_buffer: vec![440.0; BUFFER_SIZE_DEFAULT],
+                wave: Wave::new(sample_rate, 0).unwrap(),
+                frame_count: 0,
+            }
+        }
+    }
+
+    impl SoundGenerator for SineGenerator {
+        type Command = ();
+
+        fn get_samples(
+            &mut self,
+            sample_count: usize,
+            result: &mut Vec<SampleCalc>,
+        ) -> SoundResult<()> {
+            self.wave.get(
+                &self.frequency_buffer[..sample_count],
+                &mut result[..sample_count],
+            )?;
+            self.frame_count += sample_count;
+            Ok(())
+        }
+
+        fn process_command(&mut self, _command: ()) {}
+
+        fn get_time(&self) -> SampleCalc {
+            self.frame_count as SampleCalc / self.sample_rate
+        }
+    }
+
+    #[test]
+    fn advance_is_frame_exact() {
+        let mut skipped = SineGenerator::new(48000.0);
+        let mut played = SineGenerator::new(48000.0);
+        skipped.advance(50 * BUFFER_SIZE_DEFAULT + 100).unwrap();
+        let mut buffer = vec![0.0; BUFFER_SIZE_DEFAULT];
+        played.get_samples(100, &mut buffer).unwrap();
+        for _ in 0..50 {
+            played
+                .get_samples(BUFFER_SIZE_DEFAULT, &mut buffer)
+                .unwrap();
+        }
+        assert_eq!(skipped.get_time(), played.get_time());
+        let tempo = Tempo::new(120.0).unwrap();
+        let (seconds, beats) = skipped.current_position(&tempo);
+        let frames = (50 * BUFFER_SIZE_DEFAULT + 100) as SampleCalc;
+        assert!((seconds - frames / 48000.0).abs() < 1e-6, "{}", seconds);
+        assert!((beats - 2.0 * seconds).abs() < 1e-6, "{}", beats);
+        // the wave continues from the same phase
+        let mut expected = vec![0.0; BUFFER_SIZE_DEFAULT];
+        played
+            .get_samples(BUFFER_SIZE_DEFAULT, &mut expected)
+            .unwrap();
+        skipped
+            .get_samples(BUFFER_SIZE_DEFAULT, &mut buffer)
+            .unwrap();
+        for (item, expected) in buffer.iter().zip(&expected) {
+            assert!((item - expected).abs() < 1e-4, "{} {}", item, expected);
+        }
+    }
+
+    /// Generator which skips a part of its inner generator during each buffer.
+    struct NestedGenerator {
+        inner: SineGenerator,
+    }
+
+    impl SoundGenerator for NestedGenerator {
+        type Command = ();
+
+        fn get_samples(
+            &mut self,
+            sample_count: usize,
+            result: &mut Vec<SampleCalc>,
+        ) -> SoundResult<()> {
+            self.inner.advance(sample_count)?;
+            self.inner.get_samples(sample_count, result)
+        }
+
+        fn process_command(&mut self, _command: ()) {}
+
+        fn get_time(&self) -> SampleCalc {
+            self.inner.get_time() / 2.0
+        }
+    }
+
+    #[test]
+    fn advance_of_nested_generators() {
+        let mut generator = NestedGenerator {
+            inner: SineGenerator::new(48000.0),
+        };
+        generator.advance(1000).unwrap();
+        assert_eq!(generator.inner.frame_count, 2000);
+    }
+
+    static CLIP_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+    fn count_clips(event: TelemetryEvent) {
+        if let TelemetryEvent::Clip(clip_count) = event {
+            let _ = CLIP_COUNT.fetch_add(clip_count, Ordering::Relaxed);
+        }
+    }
+
+    #[test]
+    fn telemetry_delivers_clip_event() {
+        let (sender, mut queue) = TelemetryQueue::new();
+        queue.set_callback(count_clips);
+        sender.send_clip_count(&[0.5, 1.5, -2.0, 1.0]);
+        sender.send_clip_count(&[0.5, -0.5]);
+        assert_eq!(CLIP_COUNT.load(Ordering::Relaxed), 0);
+        queue.process();
+        assert_eq!(CLIP_COUNT.load(Ordering::Relaxed), 2);
+    }
+}
@@ -0,0 +1,115 @@
+use crate::sound::*;
+use std::cell::Cell;
+
+/// Follows the amplitude envelope of a signal: it is the rectified input, smoothed with separate
+/// attack (rising) and release (falling) time constants. The output can drive amplitudes,
+/// filter cutoffs, or gain reduction (e.g. auto-wah and compressor effects).
+#[derive(Debug, Clone)]
+pub struct EnvelopeFollower {
+    sample_time: SampleCalc,
+    /// Smoothing coefficient of the rising envelope.
+    attack_coefficient: Cell<SampleCalc>,
+    /// Smoothing coefficient of the falling envelope.
+    release_coefficient: Cell<SampleCalc>,
+    /// The actual level of the envelope.
+    envelope: Cell<SampleCalc>,
+}
+
+impl EnvelopeFollower {
+    /// custom constructor
+    /// The `attack` and `release` are time constants (in seconds): the time needed to cover
+    /// ~63% (1 - 1/e) of a level change. 0.0 means immediate change.
+    pub fn new(
+        sample_rate: SampleCalc,
+        attack: SampleCalc,
+        release: SampleCalc,
+    ) -> SoundResult<EnvelopeFollower> {
+        let follower = EnvelopeFollower {
+            sample_time: get_sample_time(sample_rate)?,
+            attack_coefficient: Cell::new(0.0),
+            release_coefficient: Cell::new(0.0),
+            envelope: Cell::new(0.0),
+        };
+        follower.set_attack(attack)?;
+        follower.set_release(release)?;
+        Ok(follower)
+    }
+
+    /// Sets the attack time constant (in seconds).
+    pub fn set_attack(&self, attack: SampleCalc) -> SoundResult<()> {
+        self.attack_coefficient.set(self.get_coefficient(attack)?);
+        Ok(())
+    }
+
+    /// Sets the release time constant (in seconds).
+    pub fn set_release(&self, release: SampleCalc) -> SoundResult<()> {
+        self.release_coefficient.set(self.get_coefficient(release)?);
+        Ok(())
+    }
+
+    /// Returns the actual level of the envelope.
+    pub fn get_envelope(&self) -> SampleCalc {
+        self.envelope.get()
+    }
+
+    /// Resets the envelope to silence.
+    pub fn reset(&self) {
+        self.envelope.set(0.0);
+    }
+
+    /// Calculates the envelope of the `input` samples into the `output` buffer.
+    pub fn process(&self, input: &[SampleCalc], output: &mut [SampleCalc]) -> SoundResult<()> {
+        if input.len() != output.len() {
+            return Err(Error::BufferSize);
+        }
+        let attack_coefficient = self.attack_coefficient.get();
+        let release_coefficient = self.release_coefficient.get();
+        let mut envelope = self.envelope.get();
+        for (item, sample) in output.iter_mut().zip(input) {
+            let level = sample.abs();
+            let coefficient = if level > envelope {
+                attack_coefficient
+            } else {
+                release_coefficient
+            };
+            envelope = level + coefficient * (envelope - level);
+            *item = envelope;
+        }
+        self.envelope.set(envelope);
+        Ok(())
+    }
+
+    /// Converts a time constant to a one-pole smoothing coefficient.
+    fn get_coefficient(&self, time: SampleCalc) -> SoundResult<SampleCalc> {
+        if time < 0.0 || !time.is_finite() {
+            return Err(Error::AmplitudeTimeInvalid);
+        }
+        if time == 0.0 {
+            return Ok(0.0);
+        }
+        Ok((-self.sample_time / time).exp())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn envelope_follower_tracks_decaying_sine() {
+        let sample_rate: SampleCalc = 48000.0;
+        let decay_time: SampleCalc = 0.2;
+        let envelope = |index: usize| (-(index as SampleCalc) / sample_rate / decay_time).exp();
+        let input: Vec<SampleCalc> = (0..24000)
+            .map(|index| envelope(index) * (PI2 * 1000.0 * index as SampleCalc / sample_rate).sin())
+            .collect();
+        let follower = EnvelopeFollower::new(sample_rate, 0.001, 0.01).unwrap();
+        let mut output = vec![0.0; input.len()];
+        follower.process(&input, &mut output).unwrap();
+        // after the attack, it stays below the peak envelope, and the ripple is small
+        for (index, level) in output.iter().enumerate().skip(2400) {
+            assert!(*level <= envelope(index) * 1.001);
+            assert!(*level >= envelope(index) * 0.8);
+        }
+    }
+}
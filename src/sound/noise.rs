@@ -0,0 +1,76 @@
+use crate::sound::*;
+use rand::rngs::SmallRng;
+use rand::{Rng, SeedableRng};
+use std::cell::RefCell;
+
+/// [White noise](https://en.wikipedia.org/wiki/White_noise) generator, with uniformly
+/// distributed samples in the [-1.0, 1.0] range. The base frequency is ignored.
+#[derive(Clone)]
+pub struct WhiteNoise {
+    timer: Timer,
+    rng: RefCell<SmallRng>,
+}
+
+impl WhiteNoise {
+    /// Custom constructor. The same `seed` always produces the same sequence of samples.
+    pub fn new(sample_rate: SampleCalc, seed: u64) -> SoundResult<WhiteNoise> {
+        Ok(WhiteNoise {
+            timer: Timer::new(sample_rate)?,
+            rng: RefCell::new(SmallRng::seed_from_u64(seed)),
+        })
+    }
+}
+
+impl HasTimer for WhiteNoise {
+    fn set_timing(&self, timing: TimingOption) -> SoundResult<()> {
+        self.timer.set_timing(timing)?;
+        self.restart();
+        Ok(())
+    }
+
+    fn get_timing(&self) -> TimingOption {
+        self.timer.get_timing()
+    }
+
+    fn restart(&self) {
+        self.timer.restart();
+    }
+
+    fn apply_parent_timing(&self, parent_timing: TimingOption) -> SoundResult<()> {
+        self.timer.apply_parent_timing(parent_timing)
+    }
+}
+
+impl SoundStructure for WhiteNoise {
+    fn get(&self, base_frequency: &[SampleCalc], result: &mut [SampleCalc]) -> SoundResult<()> {
+        if base_frequency.len() != result.len() {
+            return Err(Error::BufferSize);
+        }
+        let mut rng = self.rng.borrow_mut();
+        for item in result.iter_mut() {
+            *item = rng.gen_range(-1.0..=1.0);
+        }
+        self.timer.jump_by_time(result.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn white_noise_ends_with_its_timing() {
+        let noise = WhiteNoise::new(48000.0, 1).unwrap();
+        noise.set_timing(TimingOption::Samples(10)).unwrap();
+        let frequency = [440.0; 8];
+        let mut result = [0.0; 8];
+        noise.get(&frequency, &mut result).unwrap();
+        assert!(matches!(
+            noise.get(&frequency, &mut result),
+            Err(Error::ItemsCompleted(2))
+        ));
+        for item in result.iter() {
+            assert!((-1.0..=1.0).contains(item));
+        }
+    }
+}
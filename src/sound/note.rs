@@ -1,10 +1,16 @@
 use crate::sound::*;
-use std::cell::RefCell;
+#[cfg(feature = "midi")]
+use num::{Integer, ToPrimitive};
+use std::cell::{Cell, RefCell};
 use std::rc::Rc;
 
 /// Musical note.
 #[derive(Clone)]
 pub struct Note {
+    /// Measures the duration of the note.
+    timer: Timer,
+    /// Measures the sustain of the note, the sound is silenced after it.
+    sustain_timer: Timer,
     /// The interval of the note's frequency relative to the input frequency buffer.
     interval: Interval,
     /// Time for rhythm syncronization. It usually represents the time needed (from start) for
@@ -24,66 +30,735 @@ pub struct Note {
     wave_buffer: RefCell<Vec<SampleCalc>>,
 }
 
-/// Sequence of musical notes.
-#[doc(hidden)]
+impl Note {
+    /// custom constructor
+    pub fn new(
+        sample_rate: SampleCalc,
+        interval: Interval,
+        sound: Rc<dyn SoundStructure>,
+        tempo: Tempo,
+        sustain: NoteValue,
+        duration: NoteValue,
+        volume: SampleCalc,
+    ) -> SoundResult<Note> {
+        if volume < 0.0 {
+            return Err(Error::AmplitudeInvalid);
+        }
+        let timer = Timer::new(sample_rate)?;
+        timer.set_timing(TimingOption::TimeConst(duration.to_seconds(&tempo)))?;
+        let sustain_timer = Timer::new(sample_rate)?;
+        sustain_timer.set_timing(TimingOption::TimeConst(sustain.to_seconds(&tempo)))?;
+        sound.restart();
+        Ok(Note {
+            timer,
+            sustain_timer,
+            interval,
+            onset_time: 0.0,
+            sustain,
+            duration,
+            tempo,
+            sound,
+            volume_relative: volume,
+            volume_normalized: volume,
+            frequency_buffer: RefCell::new(vec![1.0; BUFFER_SIZE_DEFAULT]),
+            wave_buffer: RefCell::new(vec![0.0; BUFFER_SIZE_DEFAULT]),
+        })
+    }
+
+    /// Provides the interval relative to the input frequency.
+    pub fn get_interval(&self) -> Interval {
+        self.interval
+    }
+
+    /// Provides the time between the onset and the release.
+    pub fn get_sustain(&self) -> NoteValue {
+        self.sustain
+    }
+
+    /// Provides the time between the onset and the next note's onset.
+    pub fn get_duration(&self) -> NoteValue {
+        self.duration
+    }
+
+    /// Provides the tempo of the note.
+    pub fn get_tempo(&self) -> Tempo {
+        self.tempo
+    }
+}
+
+impl HasTimer for Note {
+    fn set_timing(&self, timing: TimingOption) -> SoundResult<()> {
+        self.timer.set_timing(timing)?;
+        self.restart();
+        Ok(())
+    }
+
+    fn get_timing(&self) -> TimingOption {
+        self.timer.get_timing()
+    }
+
+    fn restart(&self) {
+        self.timer.restart();
+        self.sustain_timer.restart();
+        self.sound.restart();
+    }
+
+    fn apply_parent_timing(&self, parent_timing: TimingOption) -> SoundResult<()> {
+        self.timer.apply_parent_timing(parent_timing)
+    }
+}
+
+impl SoundStructure for Note {
+    fn get(&self, base_frequency: &[SampleCalc], result: &mut [SampleCalc]) -> SoundResult<()> {
+        if base_frequency.len() != result.len() {
+            return Err(Error::BufferSize);
+        }
+        let mut frequency_buffer = self.frequency_buffer.borrow_mut();
+        let mut wave_buffer = self.wave_buffer.borrow_mut();
+        frequency_buffer.resize(base_frequency.len(), 1.0);
+        wave_buffer.resize(base_frequency.len(), 0.0);
+        self.interval
+            .transpose(base_frequency, &mut frequency_buffer)?;
+        // the note lasts for its own duration, even if its sound is completed earlier
+        let sound_completed = match self.sound.get(&frequency_buffer, &mut wave_buffer) {
+            Ok(()) => result.len(),
+            Err(Error::ItemsCompleted(completed)) => completed,
+            Err(e) => return Err(e),
+        };
+        let sustained = match self.sustain_timer.jump_by_time(result.len()) {
+            Ok(()) => result.len(),
+            Err(Error::ItemsCompleted(completed)) => completed,
+            Err(e) => return Err(e),
+        };
+        let sounding = sound_completed.min(sustained);
+        for (index, (item, wave)) in result.iter_mut().zip(wave_buffer.iter()).enumerate() {
+            *item = if index < sounding {
+                *wave * self.volume_normalized
+            } else {
+                0.0
+            };
+        }
+        self.timer.jump_by_time(result.len())
+    }
+}
+
+/// Sequence of musical notes. Each note is played for its duration, then the next one follows.
 #[derive(Clone)]
 pub struct NoteSequence {
-    buffer_size: usize,
+    timer: Timer,
+    sample_rate: SampleCalc,
+    tempo: Tempo,
     notes: RefCell<Vec<Note>>,
+    note_index: Cell<usize>,
 }
 
 impl NoteSequence {
     /// custom constructor
-    pub fn new(buffer_size: usize) -> SoundResult<NoteSequence> {
+    pub fn new(sample_rate: SampleCalc, tempo: Tempo) -> SoundResult<NoteSequence> {
         Ok(NoteSequence {
-            buffer_size,
+            timer: Timer::new(sample_rate)?,
+            sample_rate,
+            tempo,
             notes: RefCell::new(Vec::new()),
+            note_index: Cell::new(0),
         })
     }
 
-    /// Add a new note to the sequence.
+    /// Add a new note to the sequence. The note is sustained for its whole duration.
     pub fn add(
         &self,
-        // interval: Interval,
-        // sound: Rc<SoundStructure>,
-        duration: SampleCalc,
+        interval: Interval,
+        sound: Rc<dyn SoundStructure>,
+        duration: NoteValue,
         volume: SampleCalc,
     ) -> SoundResult<&NoteSequence> {
-        if duration <= 0.0 {
-            return Err(Error::PeriodInvalid);
-        }
-        if volume < 0.0 {
-            return Err(Error::AmplitudeInvalid);
-        }
-        // let note = Note {
-        // interval: interval,
-        // onset_time: 0.0,
-        // duration: duration,
-        // sound: sound,
-        // volume_relative: volume,
-        // volume_normalized: 0.0,
-        // frequency_buffer: RefCell::new(vec![1.0; self.buffer_size]),
-        // wave_buffer: RefCell::new(vec![0.0; self.buffer_size]),
-        // };
-        // self.notes.borrow_mut().push(note);
+        self.add_with_sustain(interval, sound, duration, duration, volume)
+    }
+
+    /// Add a new note to the sequence. The note is silenced after `sustain`, until the end of
+    /// its duration.
+    pub fn add_with_sustain(
+        &self,
+        interval: Interval,
+        sound: Rc<dyn SoundStructure>,
+        sustain: NoteValue,
+        duration: NoteValue,
+        volume: SampleCalc,
+    ) -> SoundResult<&NoteSequence> {
+        let note = Note::new(
+            self.sample_rate,
+            interval,
+            sound,
+            self.tempo,
+            sustain,
+            duration,
+            volume,
+        )?;
+        self.notes.borrow_mut().push(note);
         self.normalize();
         Ok(self)
     }
 
-    /// Generates the normalized volumes for the channels. Only normalizes if the sum of volumes
-    /// is greater than 1.0
+    /// Creates a sequence from the notes of a [Standard MIDI
+    /// File](https://www.midi.org/specifications/file-format-specifications/standard-midi-files).
+    /// The note numbers are converted by `Interval::from_midi_note`, so the base frequency of the
+    /// sequence is the pitch of the A4 reference note. The note durations are calculated with
+    /// the given `tempo`, the tempo changes of the file are ignored. The volumes are given by
+    /// the velocities.
+    ///
+    /// The notes of all tracks are merged. As only one note sounds at a time, simultaneous notes
+    /// can not be represented: a note is cut off by the next one, and from the notes starting
+    /// at the same time only the last one is kept.
+    #[cfg(feature = "midi")]
+    pub fn from_midi(
+        sample_rate: SampleCalc,
+        data: &[u8],
+        sound: Rc<dyn SoundStructure>,
+        tempo: Tempo,
+        tuning: Option<&[Interval]>,
+    ) -> SoundResult<NoteSequence> {
+        use midly::{MidiMessage, Smf, Timing, TrackEventKind};
+
+        let smf = Smf::parse(data).map_err(|e| Error::Midi(e.to_string()))?;
+        let ticks_per_beat = match smf.header.timing {
+            Timing::Metrical(ticks_per_beat) if ticks_per_beat.as_int() > 0 => {
+                ticks_per_beat.as_int()
+            }
+            _ => return Err(Error::Midi("only metrical timing is supported".to_string())),
+        };
+        // (tick, note, velocity), a velocity of 0 means note-off
+        let mut events: Vec<(u64, u8, u8)> = Vec::new();
+        for track in &smf.tracks {
+            let mut tick: u64 = 0;
+            for event in track {
+                tick += event.delta.as_int() as u64;
+                if let TrackEventKind::Midi { message, .. } = event.kind {
+                    match message {
+                        MidiMessage::NoteOn { key, vel } => {
+                            events.push((tick, key.as_int(), vel.as_int()))
+                        }
+                        MidiMessage::NoteOff { key, .. } => events.push((tick, key.as_int(), 0)),
+                        _ => {}
+                    }
+                }
+            }
+        }
+        // the note-offs precede the note-ons of the same tick
+        events.sort_by_key(|&(tick, _, velocity)| (tick, velocity > 0));
+        let onsets: Vec<(usize, u64)> = events
+            .iter()
+            .enumerate()
+            .filter(|(_, event)| event.2 > 0)
+            .map(|(index, event)| (index, event.0))
+            .collect();
+
+        let sequence = NoteSequence::new(sample_rate, tempo)?;
+        if let Some(&(_, first_onset)) = onsets.first() {
+            if first_onset > 0 {
+                let rest = get_note_value_of_ticks(first_onset, ticks_per_beat)?;
+                let _ = sequence.add(INTERVAL_UNISON, sound.clone(), rest, 0.0)?;
+            }
+        }
+        for (position, &(index, onset)) in onsets.iter().enumerate() {
+            let (_, note, velocity) = events[index];
+            let release = events[index + 1..]
+                .iter()
+                .find(|&&(_, off_note, off_velocity)| off_note == note && off_velocity == 0)
+                .map(|&(tick, _, _)| tick);
+            let end = match onsets.get(position + 1) {
+                Some(&(_, next_onset)) => next_onset,
+                None => release.unwrap_or(onset),
+            };
+            if end == onset {
+                continue;
+            }
+            let sustain = release.unwrap_or(end).min(end).max(onset + 1) - onset;
+            let _ = sequence.add_with_sustain(
+                Interval::from_midi_note(note, tuning)?,
+                sound.clone(),
+                get_note_value_of_ticks(sustain, ticks_per_beat)?,
+                get_note_value_of_ticks(end - onset, ticks_per_beat)?,
+                velocity as SampleCalc / 127.0,
+            )?;
+        }
+        Ok(sequence)
+    }
+
+    /// Generates the normalized volumes for the notes. As only one note sounds at a time, it
+    /// only normalizes if the greatest volume is greater than 1.0
     fn normalize(&self) {
-        let mut volume_sum: SampleCalc = 0.0;
+        let mut volume_max: SampleCalc = 0.0;
         for note in self.notes.borrow().iter() {
-            volume_sum += note.volume_relative;
+            volume_max = volume_max.max(note.volume_relative);
         }
-        let volume_multiplier = if volume_sum < 1.0 {
+        let volume_multiplier = if volume_max < 1.0 {
             1.0
         } else {
-            1.0 / volume_sum
+            1.0 / volume_max
         };
         for note in self.notes.borrow_mut().iter_mut() {
             note.volume_normalized = note.volume_relative * volume_multiplier;
         }
     }
 }
+
+impl HasTimer for NoteSequence {
+    fn set_timing(&self, timing: TimingOption) -> SoundResult<()> {
+        self.timer.set_timing(timing)?;
+        self.restart();
+        Ok(())
+    }
+
+    fn get_timing(&self) -> TimingOption {
+        self.timer.get_timing()
+    }
+
+    fn restart(&self) {
+        self.timer.restart();
+        self.note_index.set(0);
+        if let Some(note) = self.notes.borrow().first() {
+            note.restart();
+        }
+    }
+
+    fn apply_parent_timing(&self, parent_timing: TimingOption) -> SoundResult<()> {
+        self.timer.apply_parent_timing(parent_timing)?;
+        self.restart();
+        Ok(())
+    }
+}
+
+impl SoundStructure for NoteSequence {
+    fn get(&self, base_frequency: &[SampleCalc], result: &mut [SampleCalc]) -> SoundResult<()> {
+        if base_frequency.len() != result.len() {
+            return Err(Error::BufferSize);
+        }
+        let notes = self.notes.borrow();
+        if notes.is_empty() {
+            return Err(Error::SequenceEmpty);
+        }
+        let timer_result = self.timer.jump_by_time(result.len());
+        let buffer_size = match timer_result {
+            Ok(()) => result.len(),
+            Err(Error::ItemsCompleted(completed)) => completed,
+            Err(_) => return timer_result,
+        };
+        for item in result.iter_mut().skip(buffer_size) {
+            *item = 0.0;
+        }
+        let mut index_from: usize = 0;
+        loop {
+            let note = notes.get(self.note_index.get()).ok_or(Error::ItemInvalid)?;
+            let note_result = note.get(
+                &base_frequency[index_from..buffer_size],
+                &mut result[index_from..buffer_size],
+            );
+            match note_result {
+                Ok(()) => return timer_result,
+                Err(Error::ItemsCompleted(completed)) => {
+                    index_from += completed;
+                    let note_index = self.note_index.get() + 1;
+                    if note_index >= notes.len() {
+                        for item in result.iter_mut().skip(index_from) {
+                            *item = 0.0;
+                        }
+                        return Err(Error::ItemsCompleted(index_from));
+                    }
+                    self.note_index.set(note_index);
+                    notes[note_index].restart();
+                }
+                Err(_) => return note_result,
+            }
+        }
+    }
+}
+
+/// [Chord](https://en.wikipedia.org/wiki/Chord_(music)): notes sounding together, given by their
+/// intervals relative to the base frequency. The constructors of the common chords use just
+/// intonation ratios (e.g. 4:5:6 for the major triad).
+#[derive(Debug, Clone)]
+pub struct Chord {
+    frequency: SampleCalc,
+    intervals: Vec<Interval>,
+}
+
+impl Chord {
+    /// custom constructor
+    pub fn new(frequency: SampleCalc, intervals: Vec<Interval>) -> SoundResult<Chord> {
+        if frequency <= 0.0 || !frequency.is_finite() {
+            return Err(Error::FrequencyInvalid);
+        }
+        if intervals.is_empty() {
+            return Err(Error::SequenceEmpty);
+        }
+        Ok(Chord {
+            frequency,
+            intervals,
+        })
+    }
+
+    /// Creates a chord from the frequency ratios of its notes (e.g. `&[4, 5, 6]`), the first
+    /// one is played at the base frequency.
+    pub fn from_ratios(frequency: SampleCalc, ratios: &[u16]) -> SoundResult<Chord> {
+        let root = *ratios.first().ok_or(Error::SequenceEmpty)?;
+        let intervals = ratios
+            .iter()
+            .map(|ratio| Interval::new(*ratio, root))
+            .collect::<SoundResult<Vec<Interval>>>()?;
+        Chord::new(frequency, intervals)
+    }
+
+    /// Major triad (4:5:6).
+    pub fn major_triad(frequency: SampleCalc) -> SoundResult<Chord> {
+        Chord::from_ratios(frequency, &[4, 5, 6])
+    }
+
+    /// Minor triad (10:12:15).
+    pub fn minor_triad(frequency: SampleCalc) -> SoundResult<Chord> {
+        Chord::from_ratios(frequency, &[10, 12, 15])
+    }
+
+    /// Diminished triad (25:30:36).
+    pub fn diminished_triad(frequency: SampleCalc) -> SoundResult<Chord> {
+        Chord::from_ratios(frequency, &[25, 30, 36])
+    }
+
+    /// Augmented triad (16:20:25).
+    pub fn augmented_triad(frequency: SampleCalc) -> SoundResult<Chord> {
+        Chord::from_ratios(frequency, &[16, 20, 25])
+    }
+
+    /// Suspended second chord (8:9:12).
+    pub fn suspended_second(frequency: SampleCalc) -> SoundResult<Chord> {
+        Chord::from_ratios(frequency, &[8, 9, 12])
+    }
+
+    /// Suspended fourth chord (6:8:9).
+    pub fn suspended_fourth(frequency: SampleCalc) -> SoundResult<Chord> {
+        Chord::from_ratios(frequency, &[6, 8, 9])
+    }
+
+    /// Dominant seventh chord, with harmonic seventh (4:5:6:7).
+    pub fn dominant_seventh(frequency: SampleCalc) -> SoundResult<Chord> {
+        Chord::from_ratios(frequency, &[4, 5, 6, 7])
+    }
+
+    /// Major seventh chord (8:10:12:15).
+    pub fn major_seventh(frequency: SampleCalc) -> SoundResult<Chord> {
+        Chord::from_ratios(frequency, &[8, 10, 12, 15])
+    }
+
+    /// Minor seventh chord (10:12:15:18).
+    pub fn minor_seventh(frequency: SampleCalc) -> SoundResult<Chord> {
+        Chord::from_ratios(frequency, &[10, 12, 15, 18])
+    }
+
+    /// Returns the base frequency.
+    pub fn get_frequency(&self) -> SampleCalc {
+        self.frequency
+    }
+
+    /// Changes the base frequency.
+    pub fn set_frequency(&mut self, frequency: SampleCalc) -> SoundResult<()> {
+        if frequency <= 0.0 || !frequency.is_finite() {
+            return Err(Error::FrequencyInvalid);
+        }
+        self.frequency = frequency;
+        Ok(())
+    }
+
+    /// Returns the intervals of the notes, relative to the base frequency.
+    pub fn get_intervals(&self) -> &[Interval] {
+        &self.intervals
+    }
+
+    /// Calculates the frequencies of the notes.
+    pub fn get_frequencies(&self) -> SoundResult<Vec<SampleCalc>> {
+        self.intervals
+            .iter()
+            .map(|interval| interval.change_frequency(self.frequency))
+            .collect()
+    }
+
+    /// Provides the next [inversion](https://en.wikipedia.org/wiki/Inversion_(music)): the
+    /// lowest note is moved up by an octave. The base frequency is unchanged.
+    pub fn invert(&self) -> SoundResult<Chord> {
+        let mut intervals = self.intervals.clone();
+        let lowest = intervals.remove(0);
+        intervals.push(lowest * INTERVAL_OCTAVE);
+        Chord::new(self.frequency, intervals)
+    }
+
+    /// Builds a mixer with a channel for each note of the chord, transposed by its interval.
+    /// The `sound_factory` is called once per note, because a sound structure has its own state
+    /// (e.g. the phase of its oscillators), so it cannot be shared between the channels.
+    /// The base frequency of the chord is not used, it is the base frequency of the mixer.
+    pub fn build_mixer<F>(
+        &self,
+        sample_rate: SampleCalc,
+        buffer_size: usize,
+        mut sound_factory: F,
+    ) -> SoundResult<Mixer>
+    where
+        F: FnMut() -> SoundResult<Rc<dyn SoundStructure>>,
+    {
+        let mixer = Mixer::new(sample_rate, buffer_size)?;
+        for interval in &self.intervals {
+            let _ = mixer.add(*interval, sound_factory()?, 1.0)?;
+        }
+        Ok(mixer)
+    }
+
+    /// Renders the chord for `seconds` long, at its base frequency. See `build_mixer()`.
+    pub fn render<F>(
+        &self,
+        sample_rate: SampleCalc,
+        seconds: SampleCalc,
+        sound_factory: F,
+    ) -> SoundResult<Vec<SampleCalc>>
+    where
+        F: FnMut() -> SoundResult<Rc<dyn SoundStructure>>,
+    {
+        self.build_mixer(sample_rate, BUFFER_SIZE_DEFAULT, sound_factory)?
+            .render(self.frequency, sample_rate, seconds)
+    }
+}
+
+/// Converts a duration measured in MIDI ticks to a note value.
+#[cfg(feature = "midi")]
+fn get_note_value_of_ticks(ticks: u64, ticks_per_beat: u16) -> SoundResult<NoteValue> {
+    let divisor = ticks.gcd(&(ticks_per_beat as u64));
+    let numerator = (ticks / divisor).to_u16().ok_or(Error::Overflow)?;
+    NoteValue::new(numerator, (ticks_per_beat as u64 / divisor) as u16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sound::test_utils::*;
+
+    /// The sample time is exact in binary, so are the durations.
+    const SAMPLE_RATE: SampleCalc = 1024.0;
+
+    /// At 60 BPM the duration of a beat is 1 second.
+    fn tempo() -> Tempo {
+        Tempo::new(60.0).unwrap()
+    }
+
+    #[test]
+    fn note_lasts_for_its_duration_after_its_sound_completes() {
+        let sound = TestSignal::new(SAMPLE_RATE, 1.0);
+        sound.set_timing(TimingOption::Samples(64)).unwrap();
+        let quarter_beat = NoteValue::new(1, 4).unwrap();
+        let note = Note::new(
+            SAMPLE_RATE,
+            INTERVAL_UNISON,
+            sound,
+            tempo(),
+            quarter_beat,
+            quarter_beat,
+            0.5,
+        )
+        .unwrap();
+        let frequency = [440.0; 100];
+        let mut result = [0.0; 100];
+        note.get(&frequency, &mut result).unwrap();
+        assert!(result[..64].iter().all(|item| *item == 0.5));
+        assert!(result[64..].iter().all(|item| *item == 0.0));
+        note.get(&frequency, &mut result).unwrap();
+        assert!(result.iter().all(|item| *item == 0.0));
+        // 256 samples long
+        assert!(matches!(
+            note.get(&frequency, &mut result),
+            Err(Error::ItemsCompleted(56))
+        ));
+
+        note.restart();
+        note.get(&frequency, &mut result).unwrap();
+        assert_eq!(result[0], 0.5);
+    }
+
+    #[test]
+    fn note_is_silenced_after_its_sustain() {
+        let note = Note::new(
+            SAMPLE_RATE,
+            INTERVAL_UNISON,
+            TestSignal::new(SAMPLE_RATE, 1.0),
+            tempo(),
+            NoteValue::new(1, 8).unwrap(),
+            NoteValue::new(1, 4).unwrap(),
+            1.0,
+        )
+        .unwrap();
+        let frequency = [440.0; 100];
+        let mut result = [0.0; 100];
+        note.get(&frequency, &mut result).unwrap();
+        assert!(result.iter().all(|item| *item == 1.0));
+        note.get(&frequency, &mut result).unwrap();
+        // 128 samples long sustain
+        assert!(result[..28].iter().all(|item| *item == 1.0));
+        assert!(result[28..].iter().all(|item| *item == 0.0));
+        assert!(matches!(
+            note.get(&frequency, &mut result),
+            Err(Error::ItemsCompleted(56))
+        ));
+        assert!(result.iter().all(|item| *item == 0.0));
+    }
+
+    #[test]
+    fn note_is_transposed_by_its_interval() {
+        let note = Note::new(
+            48000.0,
+            Interval::new(3, 2).unwrap(),
+            sine(48000.0),
+            tempo(),
+            NoteValue::new(1, 1).unwrap(),
+            NoteValue::new(1, 1).unwrap(),
+            1.0,
+        )
+        .unwrap();
+        let frequency = [400.0; 480];
+        let mut result = [0.0; 4800];
+        for chunk in result.chunks_mut(480) {
+            note.get(&frequency, chunk).unwrap();
+        }
+        let measured = dominant_frequency(&result, 48000.0);
+        assert!((measured - 600.0).abs() < 1.0, "{}", measured);
+    }
+
+    #[test]
+    fn note_sequence_switches_notes_inside_a_buffer() {
+        let eighth_beat = NoteValue::new(1, 8).unwrap();
+        let sequence = NoteSequence::new(SAMPLE_RATE, tempo()).unwrap();
+        let _ = sequence
+            .add(
+                INTERVAL_UNISON,
+                TestSignal::new(SAMPLE_RATE, 1.0),
+                eighth_beat,
+                1.0,
+            )
+            .unwrap()
+            .add(
+                INTERVAL_UNISON,
+                TestSignal::new(SAMPLE_RATE, 0.5),
+                eighth_beat,
+                1.0,
+            )
+            .unwrap();
+        let frequency = [440.0; 100];
+        let mut result = [0.0; 100];
+        sequence.get(&frequency, &mut result).unwrap();
+        assert!(result.iter().all(|item| *item == 1.0));
+        // the notes are 128 samples long
+        sequence.get(&frequency, &mut result).unwrap();
+        assert!(result[..28].iter().all(|item| *item == 1.0));
+        assert!(result[28..].iter().all(|item| *item == 0.5));
+        assert!(matches!(
+            sequence.get(&frequency, &mut result),
+            Err(Error::ItemsCompleted(56))
+        ));
+        assert!(result[..56].iter().all(|item| *item == 0.5));
+        assert!(result[56..].iter().all(|item| *item == 0.0));
+    }
+
+    #[test]
+    fn note_sequence_add_does_not_restart_the_playback() {
+        let eighth_beat = NoteValue::new(1, 8).unwrap();
+        let sequence = NoteSequence::new(SAMPLE_RATE, tempo()).unwrap();
+        let _ = sequence
+            .add(
+                INTERVAL_UNISON,
+                TestSignal::new(SAMPLE_RATE, 1.0),
+                eighth_beat,
+                1.0,
+            )
+            .unwrap();
+        let frequency = [440.0; 100];
+        let mut result = [0.0; 100];
+        sequence.get(&frequency, &mut result).unwrap();
+        let _ = sequence
+            .add(
+                INTERVAL_UNISON,
+                TestSignal::new(SAMPLE_RATE, 0.5),
+                eighth_beat,
+                1.0,
+            )
+            .unwrap();
+        sequence.get(&frequency, &mut result).unwrap();
+        assert!(result[..28].iter().all(|item| *item == 1.0));
+        assert!(result[28..].iter().all(|item| *item == 0.5));
+
+        sequence.restart();
+        sequence.get(&frequency, &mut result).unwrap();
+        assert!(result.iter().all(|item| *item == 1.0));
+    }
+
+    #[cfg(feature = "midi")]
+    #[test]
+    fn note_sequence_from_midi() {
+        #[rustfmt::skip]
+        let smf: [u8; 50] = [
+            b'M', b'T', b'h', b'd', 0, 0, 0, 6,
+            0, 0, // format 0
+            0, 1, // one track
+            0, 4, // 4 ticks per beat
+            b'M', b'T', b'r', b'k', 0, 0, 0, 28,
+            4, 0x90, 69, 127, // tick 4: A4 on, after a rest
+            4, 0x80, 69, 64, // tick 8: A4 off
+            0, 0x90, 72, 127, // tick 8: C5 on
+            2, 0x90, 76, 64, // tick 10: E5 on, while C5 is held
+            2, 0x90, 72, 0, // tick 12: C5 off (as a note-on with zero velocity)
+            2, 0x80, 76, 64, // tick 14: E5 off
+            0, 0xFF, 0x2F, 0, // end of track
+        ];
+        let tuning: Vec<Interval> = [
+            (16, 15),
+            (9, 8),
+            (6, 5),
+            (5, 4),
+            (4, 3),
+            (45, 32),
+            (3, 2),
+            (8, 5),
+            (5, 3),
+            (9, 5),
+            (15, 8),
+            (2, 1),
+        ]
+        .iter()
+        .map(|&(numerator, denominator)| Interval::new(numerator, denominator).unwrap())
+        .collect();
+        let sequence = NoteSequence::from_midi(
+            SAMPLE_RATE,
+            &smf,
+            TestSignal::new(SAMPLE_RATE, 1.0),
+            tempo(),
+            Some(&tuning),
+        )
+        .unwrap();
+        let notes = sequence.notes.borrow();
+        let summary: Vec<((u16, u16), NoteValue, NoteValue, SampleCalc)> = notes
+            .iter()
+            .map(|note| {
+                let interval = note.get_interval();
+                (
+                    (interval.get_numerator(), interval.get_denominator()),
+                    note.get_sustain(),
+                    note.get_duration(),
+                    note.volume_relative,
+                )
+            })
+            .collect();
+        let beats = |numerator, denominator| NoteValue::new(numerator, denominator).unwrap();
+        assert_eq!(
+            summary,
+            vec![
+                ((1, 1), beats(1, 1), beats(1, 1), 0.0),
+                ((1, 1), beats(1, 1), beats(1, 1), 1.0),
+                // cut off by the next note
+                ((6, 5), beats(1, 2), beats(1, 2), 1.0),
+                ((3, 2), beats(1, 1), beats(1, 1), 64.0 / 127.0),
+            ]
+        );
+    }
+}
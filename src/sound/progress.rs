@@ -10,6 +10,15 @@ pub trait Progress: HasTimer {
     /// Sets a new initial phase value, and restarts the progress.
     fn set_phase_init(&self, phase: SampleCalc);
 
+    /// Turns the ping-pong mode on or off, and restarts the progress. In ping-pong mode the
+    /// phase bounces between the initial phase and the initial phase + period unit, instead of
+    /// only increasing: a full up and down cycle lasts two periods.
+    fn set_ping_pong(&self, ping_pong: bool);
+
+    /// Sets the easing curve of the phase within each period (the default is
+    /// `Easing::Linear`), and restarts the progress.
+    fn set_easing(&self, easing: Easing);
+
     /// Simplifies the phase to achieve higher accuracy. It is only used for periodic functions.
     fn simplify(&self);
 
@@ -21,14 +30,102 @@ pub trait Progress: HasTimer {
     /// Tempo value is given in beats per second.
     fn next_by_tempo(&self, tempo: SampleCalc) -> SoundResult<SampleCalc>;
 
-    // Returns the final phase value. This phase value will be the last one when the progress
-    // reaches it's duration.
-    // fn get_phase_final(&self) -> SoundResult<SampleCalc>;
+    /// Returns the final phase value. This phase value will be the last one when the progress
+    /// reaches it's duration. Returns `Error::DurationInvalid` if the timing is turned off.
+    fn get_phase_final(&self) -> SoundResult<SampleCalc>;
 
     /// Returns the actual phase value.
     fn get_phase(&self) -> SampleCalc;
 }
 
+/// Reduces the phase by whole periods. In ping-pong mode a whole cycle is two periods, so the
+/// reflected phase stays the same.
+fn simplify_phase(phase: SampleCalc, period_unit: SampleCalc, ping_pong: bool) -> SampleCalc {
+    if ping_pong {
+        phase % (2.0 * period_unit)
+    } else {
+        phase % period_unit
+    }
+}
+
+/// Easing curves, they reshape the phase change within each period. See:
+/// [easing functions](https://easings.net/)
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum Easing {
+    /// Constant speed (no easing).
+    #[default]
+    Linear,
+    /// Quadratic, accelerating from zero speed.
+    QuadraticIn,
+    /// Quadratic, decelerating to zero speed.
+    QuadraticOut,
+    /// Quadratic, accelerating until halfway, then decelerating.
+    QuadraticInOut,
+    /// Cubic, accelerating from zero speed.
+    CubicIn,
+    /// Cubic, decelerating to zero speed.
+    CubicOut,
+    /// Cubic, accelerating until halfway, then decelerating (S-shaped).
+    CubicInOut,
+}
+
+impl Easing {
+    /// Maps the fraction of a period (in the [0.0, 1.0] range) to the eased fraction. The
+    /// bounds are kept: 0.0 gives 0.0 and 1.0 gives 1.0.
+    pub fn apply(self, fraction: SampleCalc) -> SampleCalc {
+        let x = fraction;
+        match self {
+            Easing::Linear => x,
+            Easing::QuadraticIn => x * x,
+            Easing::QuadraticOut => x * (2.0 - x),
+            Easing::QuadraticInOut => {
+                if x < 0.5 {
+                    2.0 * x * x
+                } else {
+                    1.0 - 2.0 * (1.0 - x) * (1.0 - x)
+                }
+            }
+            Easing::CubicIn => x * x * x,
+            Easing::CubicOut => 1.0 - (1.0 - x) * (1.0 - x) * (1.0 - x),
+            Easing::CubicInOut => {
+                if x < 0.5 {
+                    4.0 * x * x * x
+                } else {
+                    1.0 - 4.0 * (1.0 - x) * (1.0 - x) * (1.0 - x)
+                }
+            }
+        }
+    }
+}
+
+/// Returns the phase to be used: it is reflected at the bounds in ping-pong mode, and it is
+/// reshaped within the period by the easing curve.
+fn get_phase_shaped(
+    phase: SampleCalc,
+    phase_init: SampleCalc,
+    period_unit: SampleCalc,
+    ping_pong: bool,
+    easing: Easing,
+) -> SampleCalc {
+    if (!ping_pong && easing == Easing::Linear) || period_unit == 0.0 {
+        return phase;
+    }
+    let position = phase - phase_init;
+    if ping_pong {
+        // the period unit is negative for decreasing phases (e.g. fade-outs)
+        let unit = period_unit.abs();
+        let cycle = 2.0 * unit;
+        let mut reflected = position.abs() % cycle;
+        if reflected > unit {
+            reflected = cycle - reflected;
+        }
+        return phase_init + easing.apply(reflected / unit) * period_unit;
+    }
+    let periods = position / period_unit;
+    let whole = periods.floor();
+    phase_init + (whole + easing.apply(periods - whole)) * period_unit
+}
+
 /// Time based progress measurement. It provides the sequence of phases (for sound functions) by
 /// calling `next_phase()`. The whole duration can be divided to periods, for periodic functions.
 #[derive(Debug, Clone)]
@@ -41,8 +138,10 @@ pub struct ProgressTime {
     /// Initial value of `phase`.
     phase_init: Cell<SampleCalc>,
     phase_change: Cell<SampleCalc>,
-    /// The phase of the progress.
+    /// The phase of the progress (unreflected in ping-pong mode).
     phase: Cell<SampleCalc>,
+    ping_pong: Cell<bool>,
+    easing: Cell<Easing>,
 }
 
 impl ProgressTime {
@@ -63,6 +162,8 @@ impl ProgressTime {
             phase_init: Cell::new(0.0),
             phase_change: Cell::new(phase_change),
             phase: Cell::new(0.0),
+            ping_pong: Cell::new(false),
+            easing: Cell::new(Easing::Linear),
         })
     }
 
@@ -121,34 +222,69 @@ impl Progress for ProgressTime {
         self.restart();
     }
 
+    fn set_ping_pong(&self, ping_pong: bool) {
+        self.ping_pong.set(ping_pong);
+        self.restart();
+    }
+
+    fn set_easing(&self, easing: Easing) {
+        self.easing.set(easing);
+        self.restart();
+    }
+
     fn simplify(&self) {
-        self.phase.set(self.phase.get() % self.period_unit.get());
+        self.phase.set(simplify_phase(
+            self.phase.get(),
+            self.period_unit.get(),
+            self.ping_pong.get(),
+        ));
     }
 
     fn next_by_time(&self) -> SoundResult<SampleCalc> {
         self.timer.next_by_time()?;
         self.phase.set(self.phase.get() + self.phase_change.get());
-        Ok(self.phase.get())
+        Ok(self.get_phase())
     }
 
     /// Note: it means: the duration is tempo dependent, but the phase change is time dependent.
     fn next_by_tempo(&self, tempo: SampleCalc) -> SoundResult<SampleCalc> {
         self.timer.next_by_tempo(tempo)?;
         self.phase.set(self.phase.get() + self.phase_change.get());
-        Ok(self.phase.get())
+        Ok(self.get_phase())
+    }
+
+    /// Note: a tempo based duration results in `Error::TimingInvalid`, as the final phase
+    /// depends on the tempo.
+    fn get_phase_final(&self) -> SoundResult<SampleCalc> {
+        let duration = match self.timer.get_timing() {
+            TimingOption::None => return Err(Error::DurationInvalid),
+            TimingOption::TimeConst(duration) | TimingOption::TimeRatio { duration, .. } => {
+                duration
+            }
+            TimingOption::Samples(sample_count) => {
+                sample_count as SampleCalc * self.timer.get_sample_time()
+            }
+            TimingOption::TempoConst(_) | TimingOption::TempoRatio { .. } => {
+                return Err(Error::TimingInvalid)
+            }
+        };
+        Ok(get_phase_shaped(
+            self.phase_init.get() + (duration / self.period.get()) * self.period_unit.get(),
+            self.phase_init.get(),
+            self.period_unit.get(),
+            self.ping_pong.get(),
+            self.easing.get(),
+        ))
     }
 
-    // fn get_phase_final(&self) -> SoundResult<SampleCalc> {
-    // match self.duration.get() {
-    // Some(d) => {
-    // Ok(self.phase_init.get() + ((d / self.period.get()) * self.period_unit.get()))
-    // }
-    // None => Err(Error::DurationInvalid),
-    // }
-    // }
-
     fn get_phase(&self) -> SampleCalc {
-        self.phase.get()
+        get_phase_shaped(
+            self.phase.get(),
+            self.phase_init.get(),
+            self.period_unit.get(),
+            self.ping_pong.get(),
+            self.easing.get(),
+        )
     }
 }
 
@@ -164,8 +300,10 @@ pub struct ProgressTempo {
     /// Initial value of `phase`.
     phase_init: Cell<SampleCalc>,
     phase_change: Cell<SampleCalc>,
-    /// The phase of the progress.
+    /// The phase of the progress (unreflected in ping-pong mode).
     phase: Cell<SampleCalc>,
+    ping_pong: Cell<bool>,
+    easing: Cell<Easing>,
 }
 
 impl ProgressTempo {
@@ -183,6 +321,8 @@ impl ProgressTempo {
             phase_init: Cell::new(0.0),
             phase_change: Cell::new(phase_change),
             phase: Cell::new(0.0),
+            ping_pong: Cell::new(false),
+            easing: Cell::new(Easing::Linear),
         })
     }
 
@@ -232,8 +372,22 @@ impl Progress for ProgressTempo {
         self.restart();
     }
 
+    fn set_ping_pong(&self, ping_pong: bool) {
+        self.ping_pong.set(ping_pong);
+        self.restart();
+    }
+
+    fn set_easing(&self, easing: Easing) {
+        self.easing.set(easing);
+        self.restart();
+    }
+
     fn simplify(&self) {
-        self.phase.set(self.phase.get() % self.period_unit.get());
+        self.phase.set(simplify_phase(
+            self.phase.get(),
+            self.period_unit.get(),
+            self.ping_pong.get(),
+        ));
     }
 
     fn next_by_time(&self) -> SoundResult<SampleCalc> {
@@ -248,17 +402,39 @@ impl Progress for ProgressTempo {
         self.timer.next_by_tempo(tempo)?;
         self.phase
             .set(self.phase.get() + self.phase_change.get() * tempo);
-        Ok(self.phase.get())
+        Ok(self.get_phase())
+    }
+
+    fn get_phase_final(&self) -> SoundResult<SampleCalc> {
+        let duration = match self.timer.get_timing() {
+            TimingOption::None => return Err(Error::DurationInvalid),
+            TimingOption::TempoConst(duration) | TimingOption::TempoRatio { duration, .. } => {
+                duration
+            }
+            TimingOption::TimeConst(_)
+            | TimingOption::TimeRatio { .. }
+            | TimingOption::Samples(_) => return Err(Error::TimingInvalid),
+        };
+        Ok(get_phase_shaped(
+            self.phase_init.get()
+                + duration.get_duration_in_beats()
+                    * self.period.get().get_notes_per_beat()
+                    * self.period_unit.get(),
+            self.phase_init.get(),
+            self.period_unit.get(),
+            self.ping_pong.get(),
+            self.easing.get(),
+        ))
     }
 
-    // fn get_phase_final(&self) -> SoundResult<SampleCalc> {
-    // Ok(self.phase_init.get() +
-    // (self.duration.get().get_duration_in_beats() * self.period.get().get_notes_per_beat() *
-    // self.period_unit.get()))
-    // }
-
     fn get_phase(&self) -> SampleCalc {
-        self.phase.get()
+        get_phase_shaped(
+            self.phase.get(),
+            self.phase_init.get(),
+            self.period_unit.get(),
+            self.ping_pong.get(),
+            self.easing.get(),
+        )
     }
 }
 
@@ -316,6 +492,20 @@ impl Progress for ProgressOption {
         }
     }
 
+    fn set_ping_pong(&self, ping_pong: bool) {
+        match *self {
+            ProgressOption::Time(ref p) => p.set_ping_pong(ping_pong),
+            ProgressOption::Tempo(ref p) => p.set_ping_pong(ping_pong),
+        }
+    }
+
+    fn set_easing(&self, easing: Easing) {
+        match *self {
+            ProgressOption::Time(ref p) => p.set_easing(easing),
+            ProgressOption::Tempo(ref p) => p.set_easing(easing),
+        }
+    }
+
     fn simplify(&self) {
         match *self {
             ProgressOption::Time(ref p) => p.simplify(),
@@ -337,12 +527,12 @@ impl Progress for ProgressOption {
         }
     }
 
-    // fn get_phase_final(&self) -> SoundResult<SampleCalc> {
-    // match *self {
-    // ProgressOption::Time(ref p) => p.get_phase_final(),
-    // ProgressOption::Tempo(ref p) => p.get_phase_final(),
-    // }
-    // }
+    fn get_phase_final(&self) -> SoundResult<SampleCalc> {
+        match *self {
+            ProgressOption::Time(ref p) => p.get_phase_final(),
+            ProgressOption::Tempo(ref p) => p.get_phase_final(),
+        }
+    }
 
     fn get_phase(&self) -> SampleCalc {
         match *self {
@@ -363,3 +553,49 @@ impl From<ProgressTempo> for ProgressOption {
         ProgressOption::Tempo(progress)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn easing_curves_at_key_points() {
+        let curves = [
+            (Easing::Linear, 0.5),
+            (Easing::QuadraticIn, 0.25),
+            (Easing::QuadraticOut, 0.75),
+            (Easing::QuadraticInOut, 0.5),
+            (Easing::CubicIn, 0.125),
+            (Easing::CubicOut, 0.875),
+            (Easing::CubicInOut, 0.5),
+        ];
+        for (easing, middle) in curves.iter() {
+            assert_eq!(easing.apply(0.0), 0.0, "{:?}", easing);
+            assert_eq!(easing.apply(0.5), *middle, "{:?}", easing);
+            assert_eq!(easing.apply(1.0), 1.0, "{:?}", easing);
+        }
+    }
+
+    #[test]
+    fn ping_pong_stays_between_the_bounds() {
+        for period_unit in [1.0, -1.0] {
+            let progress = ProgressTime::new(100.0, 0.1).unwrap();
+            progress.set_timing(TimingOption::TimeConst(10.0)).unwrap();
+            progress.set_period_unit(period_unit);
+            let phase_init: SampleCalc = 1.0;
+            progress.set_phase_init(phase_init);
+            progress.set_ping_pong(true);
+            let bound_low = phase_init.min(phase_init + period_unit);
+            let bound_high = phase_init.max(phase_init + period_unit);
+            let phases: Vec<SampleCalc> =
+                (0..50).map(|_| progress.next_by_time().unwrap()).collect();
+            for phase in phases.iter() {
+                assert!(*phase >= bound_low - 1e-4 && *phase <= bound_high + 1e-4);
+            }
+            assert!(phases
+                .iter()
+                .any(|phase| (*phase - (1.0 + period_unit)).abs() < 1e-3));
+            assert!(phases[20..].iter().any(|phase| (*phase - 1.0).abs() < 1e-3));
+        }
+    }
+}
@@ -0,0 +1,70 @@
+use crate::sound::*;
+use std::path::Path;
+
+/// Renders `duration` seconds of the generator's output into a 32-bit float WAV file, without
+/// using any sound output device. All channels get the same samples, like with the backends.
+pub fn render_to_wav<T, P: AsRef<Path>>(
+    mut generator: Box<dyn SoundGenerator<Command = T>>,
+    sample_rate: u32,
+    channel_count: u16,
+    duration: SampleCalc,
+    path: P,
+) -> SoundResult<()> {
+    if sample_rate == 0 {
+        return Err(Error::SampleRateInvalid);
+    }
+    if channel_count == 0 {
+        return Err(Error::ChannelInvalid);
+    }
+    if duration <= 0.0 {
+        return Err(Error::DurationInvalid);
+    }
+    let spec = hound::WavSpec {
+        channels: channel_count,
+        sample_rate,
+        bits_per_sample: 32,
+        sample_format: hound::SampleFormat::Float,
+    };
+    let mut writer =
+        hound::WavWriter::create(path, spec).map_err(|e| Error::File(e.to_string()))?;
+    let mut buffer = vec![0.0; BUFFER_SIZE_DEFAULT];
+    let mut remaining = (duration * sample_rate as SampleCalc).round() as usize;
+    while remaining > 0 {
+        let sample_count = remaining.min(BUFFER_SIZE_DEFAULT);
+        generator.get_samples(sample_count, &mut buffer)?;
+        for sample in buffer.iter().take(sample_count) {
+            for _ in 0..channel_count {
+                writer
+                    .write_sample(get_output_sample(*sample))
+                    .map_err(|e| Error::File(e.to_string()))?;
+            }
+        }
+        remaining -= sample_count;
+    }
+    writer.finalize().map_err(|e| Error::File(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sound::test_utils::*;
+
+    #[test]
+    fn render_to_wav_writes_all_the_frames() {
+        let path =
+            std::env::temp_dir().join(format!("scaleless_music_render_{}.wav", std::process::id()));
+        // 2000 frames, the last buffer is not full
+        render_to_wav(ConstantGenerator::new(8000.0, 0.5), 8000, 2, 0.25, &path).unwrap();
+        let mut reader = hound::WavReader::open(&path).unwrap();
+        let spec = reader.spec();
+        assert_eq!(spec.channels, 2);
+        assert_eq!(spec.sample_rate, 8000);
+        assert_eq!(spec.bits_per_sample, 32);
+        assert_eq!(spec.sample_format, hound::SampleFormat::Float);
+        assert_eq!(reader.len(), 2000 * 2);
+        let samples: Vec<f32> = reader.samples::<f32>().map(|item| item.unwrap()).collect();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(samples.len(), 2000 * 2);
+        assert!(samples.iter().all(|item| *item == 0.5));
+    }
+}
@@ -1,7 +1,9 @@
 use crate::sound::*;
 use num::*;
+use std::cmp::Ordering;
 use std::fmt;
-use std::ops::{Add, Mul};
+use std::ops::{Add, Mul, Sub};
+use std::str::FromStr;
 
 /// See: [RFC #1394](https://github.com/rust-lang/rfcs/pull/1394),
 /// [RFC #1465](https://github.com/rust-lang/rfcs/pull/1465)
@@ -85,6 +87,23 @@ impl Tempo {
     }
 }
 
+/// Serialized as the number of beats per minute.
+#[cfg(feature = "serde")]
+impl serde::Serialize for Tempo {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.get_bpm().serialize(serializer)
+    }
+}
+
+/// Deserialized from the number of beats per minute, it is validated by `Tempo::new()`.
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Tempo {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let beats_per_minute = SampleCalc::deserialize(deserializer)?;
+        Tempo::new(beats_per_minute).map_err(serde::de::Error::custom)
+    }
+}
+
 impl TempoProvider for Tempo {
     fn get_beat_duration(&self, _time_start: SampleCalc, result: &mut [SampleCalc]) {
         for item in result {
@@ -171,6 +190,138 @@ impl TempoProvider for TempoChangeLinear {
     }
 }
 
+/// Exponentially changing speed of the music. The beat duration changes geometrically, which
+/// sounds more natural for accelerando and ritardando than a linear change.
+#[derive(Debug, Copy, Clone)]
+pub struct TempoChangeExponential {
+    sample_time: SampleCalc,
+    tempo_start: Tempo,
+    tempo_end: Tempo,
+    duration: SampleCalc,
+    /// the ratio of the ending and starting beat durations
+    beat_duration_ratio: SampleCalc,
+}
+
+impl TempoChangeExponential {
+    /// custom constructor
+    pub fn new(
+        sample_rate: SampleCalc,
+        tempo_start: Tempo,
+        tempo_end: Tempo,
+        duration: SampleCalc,
+    ) -> SoundResult<TempoChangeExponential> {
+        let sample_time = get_sample_time(sample_rate)?;
+        if duration <= 0.0 {
+            return Err(Error::DurationInvalid);
+        }
+        Ok(TempoChangeExponential {
+            sample_time,
+            tempo_start,
+            tempo_end,
+            duration,
+            beat_duration_ratio: tempo_end.beat_duration / tempo_start.beat_duration,
+        })
+    }
+}
+
+impl TempoProvider for TempoChangeExponential {
+    fn get_beat_duration(&self, time_start: SampleCalc, result: &mut [SampleCalc]) {
+        for (index, item) in result.iter_mut().enumerate() {
+            let time = (index as SampleCalc * self.sample_time) + time_start;
+            *item = if time < 0.0 {
+                self.tempo_start.beat_duration
+            } else if time < self.duration {
+                self.tempo_start.beat_duration * self.beat_duration_ratio.powf(time / self.duration)
+            } else {
+                self.tempo_end.beat_duration
+            }
+        }
+    }
+
+    fn get_beats_per_second(&self, time_start: SampleCalc, result: &mut [SampleCalc]) {
+        for (index, item) in result.iter_mut().enumerate() {
+            let time = (index as SampleCalc * self.sample_time) + time_start;
+            *item = if time < 0.0 {
+                self.tempo_start.beats_per_second
+            } else if time < self.duration {
+                self.tempo_start.beats_per_second
+                    / self.beat_duration_ratio.powf(time / self.duration)
+            } else {
+                self.tempo_end.beats_per_second
+            }
+        }
+    }
+}
+
+/// Swing (shuffle) timing: successive note values of the given subdivision are alternately
+/// lengthened and shortened, while the pairs keep the base tempo.
+#[derive(Debug, Copy, Clone)]
+pub struct TempoSwing {
+    sample_time: SampleCalc,
+    tempo: Tempo,
+    /// the duration of a long-short pair in seconds
+    pair_duration: SampleCalc,
+    /// the duration of the long note in seconds
+    long_duration: SampleCalc,
+    /// beat duration multiplier for the long note
+    long_multiplier: SampleCalc,
+    /// beat duration multiplier for the short note
+    short_multiplier: SampleCalc,
+}
+
+impl TempoSwing {
+    /// custom constructor
+    ///
+    /// `swing_ratio` is the duration ratio of the long and short notes, e.g. 2.0 for triplet
+    /// swing, 1.0 for straight timing.
+    pub fn new(
+        sample_rate: SampleCalc,
+        tempo: Tempo,
+        subdivision: NoteValue,
+        swing_ratio: SampleCalc,
+    ) -> SoundResult<TempoSwing> {
+        let sample_time = get_sample_time(sample_rate)?;
+        if swing_ratio <= 0.0 || !swing_ratio.is_finite() {
+            return Err(Error::RateInvalid);
+        }
+        let pair_duration = 2.0 * subdivision.to_seconds(&tempo);
+        let long_multiplier = 2.0 * swing_ratio / (swing_ratio + 1.0);
+        Ok(TempoSwing {
+            sample_time,
+            tempo,
+            pair_duration,
+            long_duration: pair_duration * swing_ratio / (swing_ratio + 1.0),
+            long_multiplier,
+            short_multiplier: 2.0 - long_multiplier,
+        })
+    }
+
+    /// Returns the beat duration multiplier at the given time.
+    fn get_multiplier(&self, time: SampleCalc) -> SampleCalc {
+        if time.rem_euclid(self.pair_duration) < self.long_duration {
+            self.long_multiplier
+        } else {
+            self.short_multiplier
+        }
+    }
+}
+
+impl TempoProvider for TempoSwing {
+    fn get_beat_duration(&self, time_start: SampleCalc, result: &mut [SampleCalc]) {
+        for (index, item) in result.iter_mut().enumerate() {
+            let time = (index as SampleCalc * self.sample_time) + time_start;
+            *item = self.tempo.beat_duration * self.get_multiplier(time);
+        }
+    }
+
+    fn get_beats_per_second(&self, time_start: SampleCalc, result: &mut [SampleCalc]) {
+        for (index, item) in result.iter_mut().enumerate() {
+            let time = (index as SampleCalc * self.sample_time) + time_start;
+            *item = self.tempo.beats_per_second / self.get_multiplier(time);
+        }
+    }
+}
+
 /// `NoteValue` with maximal duration. (For testing purposes.)
 pub const NOTEVALUE_MAX: NoteValue = NoteValue {
     numerator: ::std::u16::MAX,
@@ -240,6 +391,44 @@ impl NoteValue {
     pub fn get_duration_in_beats(&self) -> SampleCalc {
         self.duration_in_beats
     }
+
+    /// Provides the duration in seconds at the given tempo.
+    pub fn to_seconds(&self, tempo: &Tempo) -> SampleCalc {
+        self.duration_in_beats * tempo.get_duration()
+    }
+
+    /// Returns the dotted note value, which lasts 3/2 times longer.
+    pub fn dotted(&self) -> SoundResult<NoteValue> {
+        self.checked_mul(&NoteValue::new(3, 2)?)
+            .ok_or(Error::Overflow)
+    }
+
+    /// Returns the note value of a tuplet, where `n` notes are played in the time of `over`
+    /// notes, e.g. `tuplet(3, 2)` for triplets.
+    pub fn tuplet(&self, n: u16, over: u16) -> SoundResult<NoteValue> {
+        self.checked_mul(&NoteValue::new(over, n)?)
+            .ok_or(Error::Overflow)
+    }
+
+    /// Subtraction, which returns `None` on overflow, or if the result would not be positive.
+    pub fn checked_sub(&self, v: &NoteValue) -> Option<NoteValue> {
+        (*self - *v).ok()
+    }
+
+    /// Constructor from a duration in seconds at the given tempo. The nearest rational note
+    /// value is chosen, with a denominator not greater than `max_denominator`.
+    pub fn from_seconds(
+        seconds: SampleCalc,
+        tempo: &Tempo,
+        max_denominator: u16,
+    ) -> SoundResult<NoteValue> {
+        if seconds <= 0.0 {
+            return Err(Error::DurationInvalid);
+        }
+        let (numerator, denominator) =
+            get_rational_approximation(seconds / tempo.get_duration(), max_denominator)?;
+        NoteValue::new(numerator, denominator)
+    }
 }
 
 impl Add for NoteValue {
@@ -313,6 +502,53 @@ impl CheckedMul for NoteValue {
     }
 }
 
+/// The result is `Error::DurationInvalid` if it would not be positive, and `Error::Overflow` if
+/// the common denominator does not fit.
+impl Sub for NoteValue {
+    type Output = SoundResult<NoteValue>;
+
+    fn sub(self, rhs: NoteValue) -> SoundResult<NoteValue> {
+        let lowest_common_multiple = self
+            .denominator
+            .checked_mul(rhs.denominator / self.denominator.gcd(&rhs.denominator))
+            .ok_or(Error::Overflow)?;
+        let n1 = self
+            .numerator
+            .checked_mul(lowest_common_multiple / self.denominator)
+            .ok_or(Error::Overflow)?;
+        let n2 = rhs
+            .numerator
+            .checked_mul(lowest_common_multiple / rhs.denominator)
+            .ok_or(Error::Overflow)?;
+        if n1 <= n2 {
+            return Err(Error::DurationInvalid);
+        }
+        NoteValue::new(n1 - n2, lowest_common_multiple)
+    }
+}
+
+impl PartialEq for NoteValue {
+    fn eq(&self, other: &NoteValue) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for NoteValue {}
+
+impl PartialOrd for NoteValue {
+    fn partial_cmp(&self, other: &NoteValue) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Note values are ordered by their duration (compared exactly, as rational numbers).
+impl Ord for NoteValue {
+    fn cmp(&self, other: &NoteValue) -> Ordering {
+        (self.numerator as u32 * other.denominator as u32)
+            .cmp(&(other.numerator as u32 * self.denominator as u32))
+    }
+}
+
 impl From<NoteValue> for SampleCalc {
     fn from(note_value: NoteValue) -> Self {
         note_value.duration_in_beats
@@ -324,3 +560,195 @@ impl fmt::Display for NoteValue {
         write!(f, "{}/{}", self.numerator, self.denominator)
     }
 }
+
+/// Parses the `numerator/denominator` format, e.g. `"1/4"`.
+impl FromStr for NoteValue {
+    type Err = Error;
+
+    fn from_str(text: &str) -> SoundResult<NoteValue> {
+        let mut parts = text.trim().splitn(2, '/');
+        let mut parse_next = || -> SoundResult<u16> {
+            parts
+                .next()
+                .and_then(|part| part.trim().parse().ok())
+                .ok_or(Error::ParseInvalid)
+        };
+        let numerator = parse_next()?;
+        let denominator = parse_next()?;
+        NoteValue::new(numerator, denominator)
+    }
+}
+
+/// Serialized in the `numerator/denominator` format, e.g. `"1/4"`.
+#[cfg(feature = "serde")]
+impl serde::Serialize for NoteValue {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// Deserialized from the `numerator/denominator` format, it is validated by `NoteValue::new()`.
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for NoteValue {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        text.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// [Time signature](https://en.wikipedia.org/wiki/Time_signature): the number of beats in a bar,
+/// and the note value of one beat (e.g. 4 for quarter notes). The beat of the `Tempo` is the
+/// `beat_unit` note, so `NoteValue::new(1, 1)` lasts one beat of the time signature.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct TimeSignature {
+    beats_per_bar: u16,
+    beat_unit: u16,
+}
+
+impl Default for TimeSignature {
+    /// The default value is 4/4 (= common time)
+    fn default() -> TimeSignature {
+        TimeSignature {
+            beats_per_bar: 4,
+            beat_unit: 4,
+        }
+    }
+}
+
+impl TimeSignature {
+    /// custom constructor
+    pub fn new(beats_per_bar: u16, beat_unit: u16) -> SoundResult<TimeSignature> {
+        if beats_per_bar == 0 {
+            return Err(Error::NumeratorInvalid);
+        };
+        if beat_unit == 0 {
+            return Err(Error::DenominatorInvalid);
+        };
+        Ok(TimeSignature {
+            beats_per_bar,
+            beat_unit,
+        })
+    }
+
+    /// Returns the number of beats in a bar.
+    pub fn get_beats_per_bar(&self) -> u16 {
+        self.beats_per_bar
+    }
+
+    /// Returns the note value of one beat (e.g. 4 for quarter notes).
+    pub fn get_beat_unit(&self) -> u16 {
+        self.beat_unit
+    }
+
+    /// Provides the duration of one bar.
+    pub fn get_bar_duration(&self) -> SoundResult<NoteValue> {
+        NoteValue::new(self.beats_per_bar, 1)
+    }
+
+    /// Provides the duration of the given number of bars.
+    pub fn get_duration_of_bars(&self, bars: u16) -> SoundResult<NoteValue> {
+        let beats = bars
+            .checked_mul(self.beats_per_bar)
+            .ok_or(Error::Overflow)?;
+        NoteValue::new(beats, 1)
+    }
+
+    /// Converts a musical position to the number of beats elapsed since the beginning. Both
+    /// `bar` and `beat` are counted from 1, e.g. bar 3, beat 2 in 4/4 is 9 beats.
+    pub fn get_position_in_beats(&self, bar: u16, beat: u16) -> SoundResult<SampleCalc> {
+        if bar == 0 || beat == 0 || beat > self.beats_per_bar {
+            return Err(Error::PositionInvalid);
+        }
+        Ok((bar - 1) as SampleCalc * self.beats_per_bar as SampleCalc + (beat - 1) as SampleCalc)
+    }
+
+    /// Converts a musical position to the time elapsed since the beginning (in seconds), at the
+    /// given tempo. See `get_position_in_beats()`.
+    pub fn get_position_in_seconds(
+        &self,
+        bar: u16,
+        beat: u16,
+        tempo: &Tempo,
+    ) -> SoundResult<SampleCalc> {
+        Ok(self.get_position_in_beats(bar, beat)? * tempo.get_duration())
+    }
+}
+
+impl fmt::Display for TimeSignature {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}", self.beats_per_bar, self.beat_unit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn note_value_seconds_round_trip() {
+        let tempo = Tempo::new(120.0).unwrap();
+        // the beat is the quarter note
+        let quarter = NoteValue::new(1, 1).unwrap();
+        assert_eq!(quarter.to_seconds(&tempo), 0.5);
+        assert_eq!(NoteValue::from_seconds(0.5, &tempo, 16).unwrap(), quarter);
+        let dotted_eighth = NoteValue::new(3, 4).unwrap();
+        assert_eq!(dotted_eighth.to_seconds(&tempo), 0.375);
+        assert_eq!(
+            NoteValue::from_seconds(0.375, &tempo, 16).unwrap(),
+            dotted_eighth
+        );
+        assert!(NoteValue::from_seconds(0.0, &tempo, 16).is_err());
+    }
+
+    #[test]
+    fn note_value_subtraction() {
+        let quarter = NoteValue::new(1, 4).unwrap();
+        let half = NoteValue::new(1, 2).unwrap();
+        assert_eq!((half - quarter).unwrap(), quarter);
+        assert!(matches!(quarter - quarter, Err(Error::DurationInvalid)));
+        assert!(matches!(quarter - half, Err(Error::DurationInvalid)));
+        assert_eq!(quarter.checked_sub(&quarter), None);
+        assert_eq!(half.checked_sub(&quarter), Some(quarter));
+    }
+
+    #[test]
+    fn time_signature_bars_to_beats() {
+        let common_time = TimeSignature::new(4, 4).unwrap();
+        let three_bars = common_time.get_duration_of_bars(3).unwrap();
+        assert_eq!(three_bars, NoteValue::new(12, 1).unwrap());
+        assert_eq!(three_bars.get_duration_in_beats(), 12.0);
+        assert_eq!(
+            common_time.get_bar_duration().unwrap(),
+            NoteValue::new(4, 1).unwrap()
+        );
+        assert_eq!(common_time.get_position_in_beats(3, 2).unwrap(), 9.0);
+        assert!(matches!(
+            common_time.get_position_in_beats(1, 5),
+            Err(Error::PositionInvalid)
+        ));
+        assert!(matches!(
+            TimeSignature::new(0, 4),
+            Err(Error::NumeratorInvalid)
+        ));
+    }
+
+    #[test]
+    fn tempo_change_exponential_boundaries() {
+        let start = Tempo::new(60.0).unwrap();
+        let end = Tempo::new(120.0).unwrap();
+        let change = TempoChangeExponential::new(100.0, start, end, 2.0).unwrap();
+        let mut beat_duration = [0.0; 1];
+        let mut beats_per_second = [0.0; 1];
+        change.get_beat_duration(0.0, &mut beat_duration);
+        change.get_beats_per_second(0.0, &mut beats_per_second);
+        assert!((beat_duration[0] - 1.0).abs() < 1e-6);
+        assert!((beats_per_second[0] - 1.0).abs() < 1e-6);
+        change.get_beat_duration(2.0, &mut beat_duration);
+        change.get_beats_per_second(2.0, &mut beats_per_second);
+        assert!((beat_duration[0] - 0.5).abs() < 1e-6);
+        assert!((beats_per_second[0] - 2.0).abs() < 1e-6);
+        // halfway the beat duration is the geometric mean of the endpoints
+        change.get_beat_duration(1.0, &mut beat_duration);
+        assert!((beat_duration[0] - SampleCalc::sqrt(0.5)).abs() < 1e-6);
+    }
+}
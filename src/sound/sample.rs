@@ -0,0 +1,213 @@
+use crate::sound::*;
+use std::cell::Cell;
+
+/// Plays back a recorded sound (e.g. a one-shot), with optional looping. The pitch follows the
+/// base frequency: the playback rate is the ratio of the base frequency and the root frequency
+/// of the recording, so it is pitch-shifted by resampling (with linear interpolation).
+/// After the end of the recording (without looping) it is silent.
+#[derive(Debug, Clone)]
+pub struct SamplePlayer {
+    timer: Timer,
+    samples: Vec<SampleCalc>,
+    /// The frequency of the recorded sound, it is played back at this base frequency unchanged.
+    root_frequency: SampleCalc,
+    /// The loop start and end positions (in samples), if looping is turned on.
+    loop_range: Option<(usize, usize)>,
+    /// The read position (in samples).
+    position: Cell<SampleCalc>,
+}
+
+impl SamplePlayer {
+    /// custom constructor
+    /// The `sample_buffer` must have the same `sample_rate` as the output.
+    pub fn new(
+        sample_buffer: Vec<SampleCalc>,
+        root_frequency: SampleCalc,
+        sample_rate: SampleCalc,
+    ) -> SoundResult<SamplePlayer> {
+        if sample_buffer.is_empty() {
+            return Err(Error::SequenceEmpty);
+        }
+        if root_frequency <= 0.0 || !root_frequency.is_finite() {
+            return Err(Error::FrequencyInvalid);
+        }
+        Ok(SamplePlayer {
+            timer: Timer::new(sample_rate)?,
+            samples: sample_buffer,
+            root_frequency,
+            loop_range: None,
+            position: Cell::new(0.0),
+        })
+    }
+
+    /// Turns looping on. After reaching `loop_end`, the playback continues from `loop_start`
+    /// (both are sample positions, `loop_end` is exclusive).
+    pub fn set_loop(&mut self, loop_start: usize, loop_end: usize) -> SoundResult<()> {
+        if loop_start >= loop_end || loop_end > self.samples.len() {
+            return Err(Error::LoopInvalid);
+        }
+        self.loop_range = Some((loop_start, loop_end));
+        Ok(())
+    }
+
+    /// Turns looping off.
+    pub fn clear_loop(&mut self) {
+        self.loop_range = None;
+    }
+
+    /// Returns the length of the recording in samples.
+    pub fn get_sample_count(&self) -> usize {
+        self.samples.len()
+    }
+
+    /// Returns the sample at the (fractional) position, with linear interpolation.
+    fn get_interpolated(&self, position: SampleCalc) -> SampleCalc {
+        let index = position as usize;
+        let fraction = position - index as SampleCalc;
+        let next_index = match self.loop_range {
+            Some((loop_start, loop_end)) if index + 1 >= loop_end => loop_start,
+            _ => index + 1,
+        };
+        let current = self.samples[index];
+        let next = self.samples.get(next_index).copied().unwrap_or(0.0);
+        current + fraction * (next - current)
+    }
+}
+
+impl HasTimer for SamplePlayer {
+    fn set_timing(&self, timing: TimingOption) -> SoundResult<()> {
+        self.timer.set_timing(timing)?;
+        self.restart();
+        Ok(())
+    }
+
+    fn get_timing(&self) -> TimingOption {
+        self.timer.get_timing()
+    }
+
+    fn restart(&self) {
+        self.timer.restart();
+        self.position.set(0.0);
+    }
+
+    fn apply_parent_timing(&self, parent_timing: TimingOption) -> SoundResult<()> {
+        self.timer.apply_parent_timing(parent_timing)
+    }
+}
+
+impl SoundStructure for SamplePlayer {
+    /// It returns `ItemsCompleted` when the timer completes, or at the end of the recording
+    /// (without looping).
+    fn get(&self, base_frequency: &[SampleCalc], result: &mut [SampleCalc]) -> SoundResult<()> {
+        if base_frequency.len() != result.len() {
+            return Err(Error::BufferSize);
+        }
+        let timer_completed = match self.timer.jump_by_time(result.len()) {
+            Ok(()) => result.len(),
+            Err(Error::ItemsCompleted(completed)) => completed,
+            Err(e) => return Err(e),
+        };
+        let end = self.samples.len() as SampleCalc;
+        let mut position = self.position.get();
+        let mut completed = timer_completed;
+        for (index, (item, frequency)) in result.iter_mut().zip(base_frequency).enumerate() {
+            if let Some((loop_start, loop_end)) = self.loop_range {
+                let loop_length = (loop_end - loop_start) as SampleCalc;
+                while position >= loop_end as SampleCalc {
+                    position -= loop_length;
+                }
+            }
+            if position >= end {
+                completed = completed.min(index);
+            }
+            if index >= completed {
+                *item = 0.0;
+                continue;
+            }
+            *item = self.get_interpolated(position);
+            position += frequency / self.root_frequency;
+        }
+        self.position.set(position.min(end));
+        if completed < result.len() {
+            return Err(Error::ItemsCompleted(completed));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: SampleCalc = 48000.0;
+
+    fn ramp(sample_count: usize) -> Vec<SampleCalc> {
+        (0..sample_count).map(|index| index as SampleCalc).collect()
+    }
+
+    #[test]
+    fn sample_player_is_pitch_shifted_by_the_base_frequency() {
+        // 0.1 s of 100 Hz
+        let recording: Vec<SampleCalc> = (0..4800)
+            .map(|index| (PI2 * 100.0 * index as SampleCalc / SAMPLE_RATE).sin())
+            .collect();
+        let player = SamplePlayer::new(recording, 100.0, SAMPLE_RATE).unwrap();
+        let frequency = [200.0; 2400];
+        let mut samples = [0.0; 2400];
+        player.get(&frequency, &mut samples).unwrap();
+        let measured = dominant_frequency(&samples, SAMPLE_RATE);
+        assert!((measured - 200.0).abs() < 1.0, "{}", measured);
+        // the recording is played in half of its duration
+        assert!(matches!(
+            player.get(&frequency, &mut samples),
+            Err(Error::ItemsCompleted(0))
+        ));
+    }
+
+    #[test]
+    fn sample_player_completes_at_the_end_of_the_recording() {
+        let player = SamplePlayer::new(ramp(8), 100.0, SAMPLE_RATE).unwrap();
+        let frequency = [100.0; 5];
+        let mut samples = [1.0; 5];
+        player.get(&frequency, &mut samples).unwrap();
+        assert_eq!(samples, [0.0, 1.0, 2.0, 3.0, 4.0]);
+        assert!(matches!(
+            player.get(&frequency, &mut samples),
+            Err(Error::ItemsCompleted(3))
+        ));
+        assert_eq!(samples, [5.0, 6.0, 7.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn sample_player_loop_wraps_around() {
+        let mut player = SamplePlayer::new(ramp(8), 100.0, SAMPLE_RATE).unwrap();
+        player.set_loop(2, 6).unwrap();
+        let frequency = [100.0; 12];
+        let mut samples = [0.0; 12];
+        player.get(&frequency, &mut samples).unwrap();
+        assert_eq!(
+            samples,
+            [0.0, 1.0, 2.0, 3.0, 4.0, 5.0, 2.0, 3.0, 4.0, 5.0, 2.0, 3.0]
+        );
+        // between the loop end and the loop start it is interpolated
+        let frequency = [50.0; 4];
+        let mut samples = [0.0; 4];
+        player.get(&frequency, &mut samples).unwrap();
+        assert_eq!(samples, [4.0, 4.5, 5.0, 3.5]);
+    }
+
+    #[test]
+    fn sample_player_timer_completes_the_playback() {
+        let mut player = SamplePlayer::new(ramp(8), 100.0, SAMPLE_RATE).unwrap();
+        player.set_loop(0, 8).unwrap();
+        player.set_timing(TimingOption::Samples(10)).unwrap();
+        let frequency = [100.0; 6];
+        let mut samples = [0.0; 6];
+        player.get(&frequency, &mut samples).unwrap();
+        assert!(matches!(
+            player.get(&frequency, &mut samples),
+            Err(Error::ItemsCompleted(4))
+        ));
+        assert_eq!(samples, [6.0, 7.0, 0.0, 1.0, 0.0, 0.0]);
+    }
+}
@@ -0,0 +1,125 @@
+use crate::sound::*;
+use std::fmt::Write;
+use std::path::Path;
+
+/// Exports a set of intervals in the Scala `.scl` format. Each interval becomes a ratio line.
+/// Note: the `1/1` unison is implicit in the format, it is not expected in `intervals`.
+pub fn scale_to_scala(intervals: &[Interval], name: &str) -> String {
+    let mut scl = String::new();
+    // writing into a `String` can not fail
+    let _ = writeln!(scl, "! {}.scl", name);
+    let _ = writeln!(scl, "!");
+    let _ = writeln!(scl, "{}", name);
+    let _ = writeln!(scl, " {}", intervals.len());
+    let _ = writeln!(scl, "!");
+    for interval in intervals {
+        let _ = writeln!(
+            scl,
+            " {}/{}",
+            interval.get_numerator(),
+            interval.get_denominator()
+        );
+    }
+    scl
+}
+
+/// Parses the contents of a Scala `.scl` file into intervals. Comment lines (starting with `!`)
+/// are skipped. Ratio lines (e.g. `3/2` or `2`) are converted exactly, cents lines (containing
+/// a period, e.g. `701.955`) are approximated by `Interval::from_cents`.
+/// Malformed lines are reported by `Error::ScalaLineInvalid` with the line number.
+pub fn scale_from_scala(contents: &str) -> SoundResult<Vec<Interval>> {
+    let mut lines = contents
+        .lines()
+        .enumerate()
+        .map(|(index, line)| (index + 1, line.trim()))
+        .filter(|&(_, line)| !line.starts_with('!'));
+    // the first line is the description, it can be empty
+    let _description = lines.next().ok_or(Error::ScalaLineInvalid(0))?;
+    let (count_line, count_text) = lines.next().ok_or(Error::ScalaLineInvalid(0))?;
+    let count: usize = count_text
+        .split_whitespace()
+        .next()
+        .and_then(|text| text.parse().ok())
+        .ok_or(Error::ScalaLineInvalid(count_line))?;
+    let mut intervals = Vec::with_capacity(count);
+    for (line_number, line) in lines.filter(|&(_, line)| !line.is_empty()) {
+        let pitch = line
+            .split_whitespace()
+            .next()
+            .ok_or(Error::ScalaLineInvalid(line_number))?;
+        let interval = parse_scala_pitch(pitch).ok_or(Error::ScalaLineInvalid(line_number))?;
+        intervals.push(interval);
+    }
+    if intervals.len() != count {
+        return Err(Error::ScalaLineInvalid(count_line));
+    }
+    Ok(intervals)
+}
+
+impl Interval {
+    /// Reads the intervals of a Scala `.scl` file, see `scale_from_scala()`. Many of the files
+    /// are not UTF-8 encoded (the descriptions can contain Latin-1 characters), so invalid
+    /// characters are replaced.
+    pub fn from_scala<P: AsRef<Path>>(path: P) -> SoundResult<Vec<Interval>> {
+        let contents = std::fs::read(path).map_err(|e| Error::File(e.to_string()))?;
+        scale_from_scala(&String::from_utf8_lossy(&contents))
+    }
+}
+
+/// Parses one pitch value of a Scala file (cents or ratio).
+fn parse_scala_pitch(pitch: &str) -> Option<Interval> {
+    if pitch.contains('.') {
+        let cents: SampleCalc = pitch.parse().ok()?;
+        return Interval::from_cents(cents).ok();
+    }
+    let mut parts = pitch.splitn(2, '/');
+    let numerator: u16 = parts.next()?.parse().ok()?;
+    let denominator: u16 = match parts.next() {
+        Some(text) => text.parse().ok()?,
+        None => 1,
+    };
+    Interval::new(numerator, denominator).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn major_triad_to_scala() {
+        let triad = [
+            Interval::new(5, 4).unwrap(),
+            Interval::new(3, 2).unwrap(),
+            Interval::new(2, 1).unwrap(),
+        ];
+        let scl = scale_to_scala(&triad, "major_triad");
+        let lines: Vec<&str> = scl.lines().collect();
+        assert_eq!(lines.len(), 8);
+        assert_eq!(lines[2], "major_triad");
+        assert_eq!(lines[3], " 3");
+        assert_eq!(&lines[5..], &[" 5/4", " 3/2", " 2/1"]);
+    }
+
+    #[test]
+    fn scala_with_ratios_and_cents() {
+        let scl = "! mixed.scl\n\
+                   !\n\
+                   Mixed ratio and cents pitches\n\
+                   4\n\
+                   !\n\
+                   9/8\n\
+                   386.3137 ! the 5/4 major third\n\
+                   701.955\n\
+                   2\n";
+        let intervals = scale_from_scala(scl).unwrap();
+        let ratios: Vec<(u16, u16)> = intervals
+            .iter()
+            .map(|interval| (interval.get_numerator(), interval.get_denominator()))
+            .collect();
+        assert_eq!(ratios, vec![(9, 8), (5, 4), (3, 2), (2, 1)]);
+        assert!(matches!(
+            scale_from_scala("name\n 2\n 3/2\n x\n"),
+            Err(Error::ScalaLineInvalid(4))
+        ));
+    }
+}
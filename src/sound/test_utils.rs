@@ -0,0 +1,91 @@
+use crate::sound::*;
+use std::rc::Rc;
+
+/// Sine wave (a timbre without overtones).
+pub fn sine(sample_rate: SampleCalc) -> Rc<dyn SoundStructure> {
+    let amplitude = AmplitudeConstOvertones::new(sample_rate, 0, &[1.0]).unwrap();
+    Rc::new(Timbre::new(sample_rate, BUFFER_SIZE_DEFAULT, Rc::new(amplitude), 1).unwrap())
+}
+
+/// Constant signal, with optional timing.
+pub struct TestSignal {
+    timer: Timer,
+    value: SampleCalc,
+}
+
+impl TestSignal {
+    pub fn new(sample_rate: SampleCalc, value: SampleCalc) -> Rc<TestSignal> {
+        Rc::new(TestSignal {
+            timer: Timer::new(sample_rate).unwrap(),
+            value,
+        })
+    }
+}
+
+impl HasTimer for TestSignal {
+    fn set_timing(&self, timing: TimingOption) -> SoundResult<()> {
+        self.timer.set_timing(timing)
+    }
+
+    fn get_timing(&self) -> TimingOption {
+        self.timer.get_timing()
+    }
+
+    fn restart(&self) {
+        self.timer.restart();
+    }
+
+    fn apply_parent_timing(&self, parent_timing: TimingOption) -> SoundResult<()> {
+        self.timer.apply_parent_timing(parent_timing)
+    }
+}
+
+impl SoundStructure for TestSignal {
+    fn get(&self, _base_frequency: &[SampleCalc], result: &mut [SampleCalc]) -> SoundResult<()> {
+        for item in result.iter_mut() {
+            *item = self.value;
+        }
+        self.timer.jump_by_time(result.len())
+    }
+}
+
+/// Generator of a constant level, the command sets the level.
+pub struct ConstantGenerator {
+    sample_rate: SampleCalc,
+    level: SampleCalc,
+    frame_count: usize,
+}
+
+impl ConstantGenerator {
+    pub fn new(sample_rate: SampleCalc, level: SampleCalc) -> Box<ConstantGenerator> {
+        Box::new(ConstantGenerator {
+            sample_rate,
+            level,
+            frame_count: 0,
+        })
+    }
+}
+
+impl SoundGenerator for ConstantGenerator {
+    type Command = SampleCalc;
+
+    fn get_samples(
+        &mut self,
+        sample_count: usize,
+        result: &mut Vec<SampleCalc>,
+    ) -> SoundResult<()> {
+        for item in result.iter_mut().take(sample_count) {
+            *item = self.level;
+        }
+        self.frame_count += sample_count;
+        Ok(())
+    }
+
+    fn process_command(&mut self, level: SampleCalc) {
+        self.level = level;
+    }
+
+    fn get_time(&self) -> SampleCalc {
+        self.frame_count as SampleCalc / self.sample_rate
+    }
+}
@@ -49,6 +49,10 @@ pub enum TimingOption {
         /// The calculated duration in beats.
         duration: NoteValue,
     },
+
+    /// Exact count of samples, independent of rhythm. Unlike `TimeConst` it is free of
+    /// rounding errors (e.g. for sample-accurate loop boundaries).
+    Samples(usize),
 }
 
 /// Timer for sequence items, based on optional duration unit types.
@@ -57,6 +61,8 @@ pub struct Timer {
     sample_time: SampleCalc,
     timing: Cell<TimingOption>,
     remaining: Cell<SampleCalc>,
+    /// The remaining count of samples, for `TimingOption::Samples`.
+    remaining_count: Cell<usize>,
 }
 
 impl Timer {
@@ -67,6 +73,7 @@ impl Timer {
             sample_time,
             timing: Cell::new(TimingOption::None),
             remaining: Cell::new(0.0),
+            remaining_count: Cell::new(0),
         })
     }
 
@@ -75,6 +82,33 @@ impl Timer {
         self.sample_time
     }
 
+    /// Provides the time left until the end of the timing duration (in seconds). Returns `None`
+    /// if the timing is turned off, or if it is tempo based (then it depends on the tempo).
+    pub fn remaining_seconds(&self) -> Option<SampleCalc> {
+        match self.timing.get() {
+            TimingOption::None | TimingOption::TempoConst(_) | TimingOption::TempoRatio { .. } => {
+                None
+            }
+            TimingOption::TimeConst(_) | TimingOption::TimeRatio { .. } => {
+                Some(self.remaining.get())
+            }
+            TimingOption::Samples(_) => {
+                Some(self.remaining_count.get() as SampleCalc * self.sample_time)
+            }
+        }
+    }
+
+    /// Provides the count of samples left until the end of the timing duration. Returns `None`
+    /// if the timing is turned off, or if it is tempo based (then it depends on the tempo).
+    pub fn remaining_samples(&self) -> Option<usize> {
+        match self.timing.get() {
+            TimingOption::Samples(_) => Some(self.remaining_count.get()),
+            _ => self
+                .remaining_seconds()
+                .map(|remaining| (remaining / self.sample_time) as usize),
+        }
+    }
+
     /// Moves forward `sample_count` steps in time. If the elapsed time reaches the timing
     /// duration, it returns the count of samples wrapped in `Error::ItemsCompleted()`.
     pub fn jump_by_time(&self, sample_count: usize) -> SoundResult<()> {
@@ -90,6 +124,15 @@ impl Timer {
                 self.remaining.set(0.0);
                 Err(Error::ItemsCompleted(samples_left))
             }
+            TimingOption::Samples(_) => {
+                let samples_left = self.remaining_count.get();
+                if samples_left >= sample_count {
+                    self.remaining_count.set(samples_left - sample_count);
+                    return Ok(());
+                }
+                self.remaining_count.set(0);
+                Err(Error::ItemsCompleted(samples_left))
+            }
             TimingOption::TempoConst(_) | TimingOption::TempoRatio { .. } => {
                 Err(Error::TimingInvalid)
             }
@@ -102,9 +145,9 @@ impl Timer {
     pub fn jump_by_tempo(&self, tempo: &[SampleCalc]) -> SoundResult<()> {
         match self.timing.get() {
             TimingOption::None => Ok(()),
-            TimingOption::TimeConst(_) | TimingOption::TimeRatio { .. } => {
-                Err(Error::TimingInvalid)
-            }
+            TimingOption::TimeConst(_)
+            | TimingOption::TimeRatio { .. }
+            | TimingOption::Samples(_) => Err(Error::TimingInvalid),
             TimingOption::TempoConst(_) | TimingOption::TempoRatio { .. } => {
                 for (index, beats_per_second) in tempo.iter().enumerate() {
                     self.remaining
@@ -132,6 +175,13 @@ impl Timer {
                 self.remaining.set(0.0);
                 Err(Error::ProgressCompleted)
             }
+            TimingOption::Samples(_) => {
+                if self.remaining_count.get() >= 1 {
+                    self.remaining_count.set(self.remaining_count.get() - 1);
+                    return Ok(());
+                }
+                Err(Error::ProgressCompleted)
+            }
             TimingOption::TempoConst(_) | TimingOption::TempoRatio { .. } => {
                 Err(Error::TimingInvalid)
             }
@@ -144,9 +194,9 @@ impl Timer {
     pub fn next_by_tempo(&self, tempo: SampleCalc) -> SoundResult<()> {
         match self.timing.get() {
             TimingOption::None => Ok(()),
-            TimingOption::TimeConst(_) | TimingOption::TimeRatio { .. } => {
-                Err(Error::TimingInvalid)
-            }
+            TimingOption::TimeConst(_)
+            | TimingOption::TimeRatio { .. }
+            | TimingOption::Samples(_) => Err(Error::TimingInvalid),
             TimingOption::TempoConst(_) | TimingOption::TempoRatio { .. } => {
                 self.remaining
                     .set(self.remaining.get() - (tempo * self.sample_time));
@@ -178,6 +228,12 @@ impl HasTimer for Timer {
             TimingOption::TempoRatio { duration, .. } => {
                 self.remaining.set(duration.get_duration_in_beats());
             }
+            TimingOption::Samples(sample_count) => {
+                if sample_count == 0 {
+                    return Err(Error::DurationInvalid);
+                }
+                self.remaining_count.set(sample_count);
+            }
         }
         self.timing.set(timing);
         Ok(())
@@ -199,12 +255,18 @@ impl HasTimer for Timer {
             TimingOption::TempoRatio { duration, .. } => {
                 self.remaining.set(duration.get_duration_in_beats());
             }
+            TimingOption::Samples(sample_count) => {
+                self.remaining_count.set(sample_count);
+            }
         }
     }
 
     fn apply_parent_timing(&self, parent_timing: TimingOption) -> SoundResult<()> {
         match self.timing.get() {
-            TimingOption::None | TimingOption::TimeConst(..) | TimingOption::TempoConst(..) => {}
+            TimingOption::None
+            | TimingOption::TimeConst(..)
+            | TimingOption::TempoConst(..)
+            | TimingOption::Samples(..) => {}
             TimingOption::TimeRatio { ratio, duration } => {
                 let parent_duration = match parent_timing {
                     TimingOption::None
@@ -212,6 +274,9 @@ impl HasTimer for Timer {
                     | TimingOption::TempoRatio { .. } => return Err(Error::TimingInvalid),
                     TimingOption::TimeConst(duration)
                     | TimingOption::TimeRatio { duration, .. } => duration,
+                    TimingOption::Samples(sample_count) => {
+                        sample_count as SampleCalc * self.sample_time
+                    }
                 };
                 self.timing.set(TimingOption::TimeRatio {
                     ratio,
@@ -222,7 +287,8 @@ impl HasTimer for Timer {
                 let parent_duration = match parent_timing {
                     TimingOption::None
                     | TimingOption::TimeConst(_)
-                    | TimingOption::TimeRatio { .. } => return Err(Error::TimingInvalid),
+                    | TimingOption::TimeRatio { .. }
+                    | TimingOption::Samples(_) => return Err(Error::TimingInvalid),
                     TimingOption::TempoConst(duration)
                     | TimingOption::TempoRatio { duration, .. } => duration,
                 };
@@ -1,32 +1,111 @@
 use crate::sound::*;
 use std::cell::RefCell;
 use std::rc::Rc;
+use std::sync::{Arc, OnceLock};
 // use rayon::prelude::*;
 
-/// A sinusoidal wave generator, with variable frequency.
+/// Common interface of the periodic wave generators, with variable frequency.
+pub trait Oscillator {
+    /// Gets the next samples of the wave.
+    fn get(&mut self, base_frequency: &[SampleCalc], result: &mut [SampleCalc]) -> SoundResult<()>;
+
+    /// Sets a new frequency interval.
+    fn set_interval(&mut self, interval: Interval);
+
+    /// Sets a new phase value.
+    fn set_phase(&mut self, phase: SampleCalc);
+}
+
+/// The common phase handling of the oscillators. The phase advances proportionally to the
+/// frequency of the overtone, which is transposed by the interval.
 #[derive(Debug, Copy, Clone)]
-pub struct Wave {
+struct Phasor {
     sample_time: SampleCalc,
     /// The interval is used for transposition of the input frequencies
     interval: Interval,
     overtone: SampleCalc,
+    /// Frequency ratio of the stretched (inharmonic) overtone, relative to the harmonic one.
+    stretch: SampleCalc,
+    /// The phase change of a whole period (e.g. π x 2 for radians).
+    period: SampleCalc,
+    /// Phase change per frequency unit, during one sample.
     frequency_multiplier: SampleCalc,
     /// The phase value is always kept close to zero for maximizing the floating point precision.
     phase: SampleCalc,
 }
 
+impl Phasor {
+    /// custom constructor
+    fn new(sample_rate: SampleCalc, overtone: usize, period: SampleCalc) -> SoundResult<Phasor> {
+        let mut phasor = Phasor {
+            sample_time: get_sample_time(sample_rate)?,
+            interval: INTERVAL_UNISON,
+            overtone: overtone as SampleCalc,
+            stretch: 1.0,
+            period,
+            frequency_multiplier: 0.0,
+            phase: 0.0,
+        };
+        phasor.set_interval(INTERVAL_UNISON);
+        Ok(phasor)
+    }
+
+    /// Sets a new frequency interval.
+    fn set_interval(&mut self, interval: Interval) {
+        self.interval = interval;
+        self.frequency_multiplier = (self.overtone + 1.0)
+            * self.stretch
+            * self.period
+            * self.sample_time
+            * interval.get_ratio();
+    }
+
+    /// Sets the frequency ratio of the overtone, relative to the harmonic one.
+    fn set_stretch(&mut self, stretch: SampleCalc) {
+        self.stretch = stretch;
+        self.set_interval(self.interval);
+    }
+
+    /// Sets a new phase value (in radians).
+    fn set_phase(&mut self, phase: SampleCalc) {
+        self.phase = (phase.rem_euclid(PI2) / PI2 * self.period) % self.period;
+    }
+
+    /// Advances the phase by one sample, and returns the change of the position (see
+    /// `get_position()`).
+    #[inline]
+    fn advance(&mut self, frequency: SampleCalc) -> SampleCalc {
+        let phase_change = frequency * self.frequency_multiplier;
+        self.phase += phase_change;
+        phase_change / self.period
+    }
+
+    /// Returns the position within the period, in the [0.0, 1.0) range.
+    #[inline]
+    fn get_position(&self) -> SampleCalc {
+        (self.phase % self.period) / self.period
+    }
+
+    /// Keeps the phase within one period.
+    #[inline]
+    fn wrap(&mut self) {
+        self.phase %= self.period;
+    }
+}
+
+/// A sinusoidal wave generator, with variable frequency.
+#[derive(Debug, Copy, Clone)]
+pub struct Wave {
+    phasor: Phasor,
+}
+
 // TODO: speed optimization
 // e.g. https://pizer.wordpress.com/2010/02/08/fast-digital-sine-oscillator/
 impl Wave {
     /// custom constructor
     pub fn new(sample_rate: SampleCalc, overtone: usize) -> SoundResult<Wave> {
-        let sample_time = get_sample_time(sample_rate)?;
         Ok(Wave {
-            sample_time,
-            interval: INTERVAL_UNISON,
-            overtone: overtone as SampleCalc,
-            frequency_multiplier: (overtone as SampleCalc + 1.0) * PI2 * sample_time,
-            phase: 0.0,
+            phasor: Phasor::new(sample_rate, overtone, PI2)?,
         })
     }
 
@@ -37,37 +116,421 @@ impl Wave {
         result: &mut [SampleCalc],
     ) -> SoundResult<()> {
         for (item, frequency) in result.iter_mut().zip(base_frequency) {
-            self.phase += frequency * self.frequency_multiplier;
-            *item = (self.phase).sin();
+            let _ = self.phasor.advance(*frequency);
+            *item = self.phasor.phase.sin();
+        }
+        self.phasor.wrap();
+        Ok(())
+    }
+
+    /// Adds the next samples of the wave to the result. Useful for additive synthesis, without
+    /// an intermediate buffer.
+    pub fn get_add(
+        &mut self,
+        base_frequency: &[SampleCalc],
+        result: &mut [SampleCalc],
+    ) -> SoundResult<()> {
+        for (item, frequency) in result.iter_mut().zip(base_frequency) {
+            let _ = self.phasor.advance(*frequency);
+            *item += self.phasor.phase.sin();
         }
-        self.phase %= PI2;
+        self.phasor.wrap();
         Ok(())
     }
 
     /// Sets a new frequency interval.
     pub fn set_interval(&mut self, interval: Interval) {
-        self.interval = interval;
-        self.frequency_multiplier =
-            (self.overtone + 1.0) * PI2 * self.sample_time * interval.get_ratio();
+        self.phasor.set_interval(interval);
+    }
+
+    /// Sets the [inharmonicity](https://en.wikipedia.org/wiki/Inharmonicity) coefficient (B).
+    /// The frequency of overtone n will be f * (n + 1) * sqrt(1 + B * n^2). B = 0.0 means
+    /// harmonic overtones.
+    pub fn set_inharmonicity(&mut self, inharmonicity: SampleCalc) -> SoundResult<()> {
+        if inharmonicity < 0.0 || !inharmonicity.is_finite() {
+            return Err(Error::InharmonicityInvalid);
+        }
+        let overtone = self.phasor.overtone;
+        self.phasor
+            .set_stretch((1.0 + inharmonicity * overtone * overtone).sqrt());
+        Ok(())
     }
 
     /// Sets a new phase value.
     pub fn set_phase(&mut self, phase: SampleCalc) {
-        self.phase = phase % PI2;
+        self.phasor.set_phase(phase);
+    }
+}
+
+impl Oscillator for Wave {
+    fn get(&mut self, base_frequency: &[SampleCalc], result: &mut [SampleCalc]) -> SoundResult<()> {
+        Wave::get(self, base_frequency, result)
+    }
+
+    fn set_interval(&mut self, interval: Interval) {
+        Wave::set_interval(self, interval)
+    }
+
+    fn set_phase(&mut self, phase: SampleCalc) {
+        Wave::set_phase(self, phase)
+    }
+}
+
+/// = 4096, the default size of the sine table of `WaveTableSine`.
+pub const WAVE_TABLE_SIZE_DEFAULT: usize = 4096;
+
+/// The sine table of the default size, it is calculated only once.
+static SINE_TABLE_DEFAULT: OnceLock<Arc<[SampleCalc]>> = OnceLock::new();
+
+/// Calculates one period of the sine wave, with an extra guard item for the interpolation.
+fn get_sine_table(table_size: usize) -> Arc<[SampleCalc]> {
+    (0..=table_size)
+        .map(|index| (PI2 * index as SampleCalc / table_size as SampleCalc).sin())
+        .collect()
+}
+
+/// A sinusoidal wave generator, with variable frequency. It reads a precomputed sine table with
+/// linear interpolation, which is faster than `Wave`.
+///
+/// The error of the interpolation is about (π / table size)^2 / 2 relative to the amplitude,
+/// it always lowers the amplitude slightly and adds harmonic distortion at the same level:
+/// about -82 dB with 256 items, -106 dB with 1024 and -126 dB with 4096 (the default, limited
+/// by the precision of `f32`). Bigger tables are more accurate, but use more cache memory.
+#[derive(Debug, Clone)]
+pub struct WaveTableSine {
+    /// The phase is measured in table items, in the [0.0, table size) range.
+    phasor: Phasor,
+    table: Arc<[SampleCalc]>,
+}
+
+impl WaveTableSine {
+    /// custom constructor, with the default table size.
+    pub fn new(sample_rate: SampleCalc, overtone: usize) -> SoundResult<WaveTableSine> {
+        let table = SINE_TABLE_DEFAULT
+            .get_or_init(|| get_sine_table(WAVE_TABLE_SIZE_DEFAULT))
+            .clone();
+        Self::new_with_table(sample_rate, overtone, table)
+    }
+
+    /// custom constructor, with custom table size. The table is calculated for each instance.
+    pub fn new_with_table_size(
+        sample_rate: SampleCalc,
+        overtone: usize,
+        table_size: usize,
+    ) -> SoundResult<WaveTableSine> {
+        if table_size < 2 {
+            return Err(Error::BufferSize);
+        }
+        Self::new_with_table(sample_rate, overtone, get_sine_table(table_size))
+    }
+
+    fn new_with_table(
+        sample_rate: SampleCalc,
+        overtone: usize,
+        table: Arc<[SampleCalc]>,
+    ) -> SoundResult<WaveTableSine> {
+        let table_size = (table.len() - 1) as SampleCalc;
+        Ok(WaveTableSine {
+            phasor: Phasor::new(sample_rate, overtone, table_size)?,
+            table,
+        })
+    }
+
+    /// Advances the phase, and returns the next sample.
+    #[inline]
+    fn next_sample(&mut self, frequency: SampleCalc) -> SampleCalc {
+        let _ = self.phasor.advance(frequency);
+        self.phasor.wrap();
+        let phase = self.phasor.phase;
+        let index = phase as usize;
+        let fraction = phase - index as SampleCalc;
+        self.table[index] + fraction * (self.table[index + 1] - self.table[index])
+    }
+
+    /// Gets the next samples of the wave.
+    pub fn get(
+        &mut self,
+        base_frequency: &[SampleCalc],
+        result: &mut [SampleCalc],
+    ) -> SoundResult<()> {
+        for (item, frequency) in result.iter_mut().zip(base_frequency) {
+            *item = self.next_sample(*frequency);
+        }
+        Ok(())
+    }
+
+    /// Adds the next samples of the wave to the result. Useful for additive synthesis, without
+    /// an intermediate buffer.
+    pub fn get_add(
+        &mut self,
+        base_frequency: &[SampleCalc],
+        result: &mut [SampleCalc],
+    ) -> SoundResult<()> {
+        for (item, frequency) in result.iter_mut().zip(base_frequency) {
+            *item += self.next_sample(*frequency);
+        }
+        Ok(())
+    }
+
+    /// Sets a new frequency interval.
+    pub fn set_interval(&mut self, interval: Interval) {
+        self.phasor.set_interval(interval);
+    }
+
+    /// Sets a new phase value (in radians).
+    pub fn set_phase(&mut self, phase: SampleCalc) {
+        self.phasor.set_phase(phase);
+    }
+}
+
+impl Oscillator for WaveTableSine {
+    fn get(&mut self, base_frequency: &[SampleCalc], result: &mut [SampleCalc]) -> SoundResult<()> {
+        WaveTableSine::get(self, base_frequency, result)
+    }
+
+    fn set_interval(&mut self, interval: Interval) {
+        WaveTableSine::set_interval(self, interval)
+    }
+
+    fn set_phase(&mut self, phase: SampleCalc) {
+        WaveTableSine::set_phase(self, phase)
+    }
+}
+
+/// [PolyBLEP](http://www.martin-finke.de/blog/articles/audio-plugins-018-polyblep-oscillator/)
+/// residual for smoothing a unit step discontinuity. The `position` is the phase normalized to
+/// the [0.0, 1.0) range, the `position_change` is its change during one sample.
+fn poly_blep(position: SampleCalc, position_change: SampleCalc) -> SampleCalc {
+    if position < position_change {
+        let t = position / position_change;
+        t + t - t * t - 1.0
+    } else if position > 1.0 - position_change {
+        let t = (position - 1.0) / position_change;
+        t * t + t + t + 1.0
+    } else {
+        0.0
+    }
+}
+
+/// A [sawtooth wave](https://en.wikipedia.org/wiki/Sawtooth_wave) generator, with variable
+/// frequency. It ramps up from -1.0 to 1.0 in each period.
+#[derive(Debug, Copy, Clone)]
+pub struct Sawtooth {
+    phasor: Phasor,
+    /// Aliasing is reduced with PolyBLEP correction, if it is true.
+    band_limited: bool,
+}
+
+impl Sawtooth {
+    /// custom constructor
+    pub fn new(sample_rate: SampleCalc, overtone: usize) -> SoundResult<Sawtooth> {
+        Ok(Sawtooth {
+            phasor: Phasor::new(sample_rate, overtone, PI2)?,
+            band_limited: false,
+        })
+    }
+
+    /// Turns the band limiting (PolyBLEP correction) on or off. It reduces the aliasing of high
+    /// notes, at the cost of some extra calculation.
+    pub fn set_band_limited(&mut self, band_limited: bool) {
+        self.band_limited = band_limited;
+    }
+}
+
+impl Oscillator for Sawtooth {
+    fn get(&mut self, base_frequency: &[SampleCalc], result: &mut [SampleCalc]) -> SoundResult<()> {
+        for (item, frequency) in result.iter_mut().zip(base_frequency) {
+            let position_change = self.phasor.advance(*frequency);
+            let position = self.phasor.get_position();
+            *item = 2.0 * position - 1.0;
+            if self.band_limited {
+                *item -= poly_blep(position, position_change);
+            }
+        }
+        self.phasor.wrap();
+        Ok(())
+    }
+
+    fn set_interval(&mut self, interval: Interval) {
+        self.phasor.set_interval(interval);
+    }
+
+    fn set_phase(&mut self, phase: SampleCalc) {
+        self.phasor.set_phase(phase);
+    }
+}
+
+/// A [square wave](https://en.wikipedia.org/wiki/Square_wave) generator, with variable
+/// frequency and adjustable duty cycle
+/// ([pulse wave](https://en.wikipedia.org/wiki/Pulse_wave)).
+#[derive(Debug, Copy, Clone)]
+pub struct Square {
+    phasor: Phasor,
+    /// The ratio of the positive part of the period.
+    duty_cycle: SampleCalc,
+    /// Aliasing is reduced with PolyBLEP correction, if it is true.
+    band_limited: bool,
+}
+
+impl Square {
+    /// custom constructor. The default duty cycle is 0.5.
+    pub fn new(sample_rate: SampleCalc, overtone: usize) -> SoundResult<Square> {
+        Ok(Square {
+            phasor: Phasor::new(sample_rate, overtone, PI2)?,
+            duty_cycle: 0.5,
+            band_limited: false,
+        })
+    }
+
+    /// Turns the band limiting (PolyBLEP correction) on or off. It reduces the aliasing of high
+    /// notes, at the cost of some extra calculation.
+    pub fn set_band_limited(&mut self, band_limited: bool) {
+        self.band_limited = band_limited;
+    }
+
+    /// Sets the ratio of the positive part of the period. It must be in the (0.0, 1.0) range.
+    pub fn set_duty_cycle(&mut self, duty: SampleCalc) -> SoundResult<()> {
+        if duty <= 0.0 || duty >= 1.0 {
+            return Err(Error::DutyCycleInvalid);
+        }
+        self.duty_cycle = duty;
+        Ok(())
+    }
+}
+
+impl Oscillator for Square {
+    fn get(&mut self, base_frequency: &[SampleCalc], result: &mut [SampleCalc]) -> SoundResult<()> {
+        for (item, frequency) in result.iter_mut().zip(base_frequency) {
+            let position_change = self.phasor.advance(*frequency);
+            let position = self.phasor.get_position();
+            *item = if position < self.duty_cycle {
+                1.0
+            } else {
+                -1.0
+            };
+            if self.band_limited {
+                *item += poly_blep(position, position_change);
+                *item -= poly_blep((position + 1.0 - self.duty_cycle) % 1.0, position_change);
+            }
+        }
+        self.phasor.wrap();
+        Ok(())
+    }
+
+    fn set_interval(&mut self, interval: Interval) {
+        self.phasor.set_interval(interval);
+    }
+
+    fn set_phase(&mut self, phase: SampleCalc) {
+        self.phasor.set_phase(phase);
+    }
+}
+
+/// A [triangle wave](https://en.wikipedia.org/wiki/Triangle_wave) generator, with variable
+/// frequency. It rises from -1.0 to 1.0 in the first half of the period, and falls back in the
+/// second half.
+#[derive(Debug, Copy, Clone)]
+pub struct Triangle {
+    phasor: Phasor,
+}
+
+impl Triangle {
+    /// custom constructor
+    pub fn new(sample_rate: SampleCalc, overtone: usize) -> SoundResult<Triangle> {
+        Ok(Triangle {
+            phasor: Phasor::new(sample_rate, overtone, PI2)?,
+        })
+    }
+}
+
+impl Oscillator for Triangle {
+    fn get(&mut self, base_frequency: &[SampleCalc], result: &mut [SampleCalc]) -> SoundResult<()> {
+        for (item, frequency) in result.iter_mut().zip(base_frequency) {
+            let _ = self.phasor.advance(*frequency);
+            let position = self.phasor.get_position();
+            *item = if position < 0.5 {
+                4.0 * position - 1.0
+            } else {
+                3.0 - 4.0 * position
+            };
+        }
+        self.phasor.wrap();
+        Ok(())
+    }
+
+    fn set_interval(&mut self, interval: Interval) {
+        self.phasor.set_interval(interval);
+    }
+
+    fn set_phase(&mut self, phase: SampleCalc) {
+        self.phasor.set_phase(phase);
+    }
+}
+
+/// A [wavetable](https://en.wikipedia.org/wiki/Wavetable_synthesis) oscillator, with variable
+/// frequency. It plays back a single-cycle waveform, using linear interpolation.
+#[derive(Debug, Clone)]
+pub struct Wavetable {
+    phasor: Phasor,
+    /// The samples of one period.
+    table: Vec<SampleCalc>,
+}
+
+impl Wavetable {
+    /// custom constructor. The `table` contains the samples of one period of the waveform.
+    pub fn new(
+        sample_rate: SampleCalc,
+        overtone: usize,
+        table: Vec<SampleCalc>,
+    ) -> SoundResult<Wavetable> {
+        let phasor = Phasor::new(sample_rate, overtone, PI2)?;
+        if table.is_empty() {
+            return Err(Error::ItemInvalid);
+        }
+        Ok(Wavetable { phasor, table })
+    }
+}
+
+impl Oscillator for Wavetable {
+    fn get(&mut self, base_frequency: &[SampleCalc], result: &mut [SampleCalc]) -> SoundResult<()> {
+        let table_size = self.table.len();
+        for (item, frequency) in result.iter_mut().zip(base_frequency) {
+            let _ = self.phasor.advance(*frequency);
+            let position = self.phasor.get_position() * table_size as SampleCalc;
+            let index = (position as usize).min(table_size - 1);
+            let fraction = position - index as SampleCalc;
+            let next = self.table[(index + 1) % table_size];
+            *item = self.table[index] + (next - self.table[index]) * fraction;
+        }
+        self.phasor.wrap();
+        Ok(())
+    }
+
+    fn set_interval(&mut self, interval: Interval) {
+        self.phasor.set_interval(interval);
+    }
+
+    fn set_phase(&mut self, phase: SampleCalc) {
+        self.phasor.set_phase(phase);
     }
 }
 
 /// A tone with optional overtones and amplitude modulation.
 /// Some examples: <https://youtu.be/VRAXK4QKJ1Q?t=25s>
+/// The waves of the overtones are sinusoidal by default, but any `Oscillator` can be used.
 #[derive(Clone)]
-pub struct Timbre {
+pub struct Timbre<W: Oscillator = Wave> {
     // sample_time: SampleCalc,
     /// The interval is used for transposition of the input frequencies
     interval: Interval,
-    waves: RefCell<Vec<Wave>>,
+    waves: RefCell<Vec<W>>,
     amplitude_overtones: Rc<dyn AmplitudeOvertonesProvider>,
     wave_buffer: RefCell<Vec<SampleCalc>>,
     overtone_max: usize,
+    /// The overtones above this frequency are left out from the calculations.
+    frequency_limit: SampleCalc,
+    /// The inharmonicity coefficient of the overtones, see `set_inharmonicity()`.
+    inharmonicity: SampleCalc,
 }
 
 impl Timbre {
@@ -82,12 +545,44 @@ impl Timbre {
         for overtone in 0..overtone_max {
             wave_vec.push(Wave::new(sample_rate, overtone)?);
         }
+        Timbre::new_with_oscillators(sample_rate, buffer_size, amplitude_overtones, wave_vec)
+    }
+
+    /// Sets the [inharmonicity](https://en.wikipedia.org/wiki/Inharmonicity) coefficient (B) of
+    /// the overtones, for piano-like timbres. The frequency of overtone n will be
+    /// f * (n + 1) * sqrt(1 + B * n^2). The default is 0.0 (harmonic overtones).
+    pub fn set_inharmonicity(&mut self, inharmonicity: SampleCalc) -> SoundResult<()> {
+        for wave in self.waves.borrow_mut().iter_mut() {
+            wave.set_inharmonicity(inharmonicity)?;
+        }
+        self.inharmonicity = inharmonicity;
+        Ok(())
+    }
+}
+
+impl<W: Oscillator> Timbre<W> {
+    /// Constructor with custom oscillators. `waves[0]` is used for the fundamental tone, the
+    /// others for the overtones. The overtones above `TONE_FREQUENCY_MAX` or the Nyquist
+    /// frequency are left out from the calculations.
+    pub fn new_with_oscillators(
+        sample_rate: SampleCalc,
+        buffer_size: usize,
+        amplitude_overtones: Rc<dyn AmplitudeOvertonesProvider>,
+        waves: Vec<W>,
+    ) -> SoundResult<Timbre<W>> {
+        if sample_rate < 1.0 {
+            return Err(Error::SampleRateInvalid);
+        }
+        let overtone_max = waves.len();
         Ok(Timbre {
             interval: INTERVAL_UNISON,
-            waves: RefCell::new(wave_vec),
+            waves: RefCell::new(waves),
             amplitude_overtones,
             wave_buffer: RefCell::new(vec![0.0; buffer_size]),
             overtone_max,
+            // the overtones above the Nyquist frequency would alias
+            frequency_limit: TONE_FREQUENCY_MAX.min(sample_rate / 2.0),
+            inharmonicity: 0.0,
         })
     }
 
@@ -99,17 +594,29 @@ impl Timbre {
         }
     }
 
+    /// Sets the phases of the waves. `phases[0]` is for the fundamental tone, the others for the
+    /// overtones. Waves without a given phase are left unchanged.
+    pub fn set_overtone_phases(&mut self, phases: &[SampleCalc]) -> SoundResult<()> {
+        if phases.len() > self.overtone_max {
+            return Err(Error::OvertoneCountInvalid);
+        }
+        for (wave, phase) in self.waves.borrow_mut().iter_mut().zip(phases) {
+            wave.set_phase(*phase);
+        }
+        Ok(())
+    }
+
     /// Set a new amplitude function
     pub fn set_amplitude(
         &mut self,
         amplitude_overtones: Rc<dyn AmplitudeOvertonesProvider>,
-    ) -> &mut Timbre {
+    ) -> &mut Timbre<W> {
         self.amplitude_overtones = amplitude_overtones;
         self
     }
 }
 
-impl HasTimer for Timbre {
+impl<W: Oscillator> HasTimer for Timbre<W> {
     fn set_timing(&self, timing: TimingOption) -> SoundResult<()> {
         self.amplitude_overtones.set_timing(timing)?;
         self.restart();
@@ -129,23 +636,36 @@ impl HasTimer for Timbre {
     }
 }
 
-impl SoundStructure for Timbre {
-    // TODO: filtering out frequencies from the calculations which are out of range
+impl<W: Oscillator> SoundStructure for Timbre<W> {
+    /// The overtones which go above the frequency limit (`TONE_FREQUENCY_MAX`, or the Nyquist
+    /// frequency) anywhere in the buffer are left out, their oscillators are not calculated.
     fn get(&self, base_frequency: &[SampleCalc], result: &mut [SampleCalc]) -> SoundResult<()> {
-        let mut wave_buffer = self.wave_buffer.borrow_mut();
-        let buffer_size = wave_buffer.len();
-        if base_frequency.len() != buffer_size {
+        let mut wave_buffer_reserved = self.wave_buffer.borrow_mut();
+        if base_frequency.len() > wave_buffer_reserved.len() {
             return Err(Error::BufferSize);
         }
-        if result.len() != buffer_size {
+        if result.len() != base_frequency.len() {
             return Err(Error::BufferSize);
         }
+        let wave_buffer = &mut wave_buffer_reserved[..result.len()];
         for item in result.iter_mut() {
             *item = 0.0;
         }
+        let frequency_max =
+            base_frequency.iter().fold(0.0, |max, item| item.max(max)) * self.interval.get_ratio();
         for (overtone, wave) in self.waves.borrow_mut().iter_mut().enumerate() {
-            wave.get(base_frequency, &mut wave_buffer)?;
-            self.amplitude_overtones.apply(overtone, &mut wave_buffer)?;
+            let n = overtone as SampleCalc;
+            let stretch = (1.0 + self.inharmonicity * n * n).sqrt();
+            if frequency_max * (n + 1.0) * stretch > self.frequency_limit {
+                // the amplitude function is still applied, to keep its state going
+                for item in wave_buffer.iter_mut() {
+                    *item = 0.0;
+                }
+                self.amplitude_overtones.apply(overtone, wave_buffer)?;
+                continue;
+            }
+            wave.get(base_frequency, wave_buffer)?;
+            self.amplitude_overtones.apply(overtone, wave_buffer)?;
             for (item, wave) in result.iter_mut().zip(wave_buffer.iter()) {
                 *item += *wave;
             }
@@ -154,39 +674,159 @@ impl SoundStructure for Timbre {
     }
 }
 
-/// Channel structure used for mixing sound structures.
-#[derive(Clone)]
-struct MixerChannel {
-    /// The interval of the channel's frequency relative to the mixer's input frequency.
-    interval: Interval,
-    /// Sound structure.
-    sound: Rc<dyn SoundStructure>,
-    volume_relative: SampleCalc,
-    volume_normalized: SampleCalc,
-    frequency_buffer: Vec<SampleCalc>,
-    wave_buffer: Vec<SampleCalc>,
-}
-
-/// Mixes sound channels (structures).
+/// A tone with optional undertones (subharmonics: f/2, f/3, ...), the mirror of the overtones
+/// of `Timbre`. Undertones below `TONE_FREQUENCY_MIN` are left out from the calculations.
 #[derive(Clone)]
-pub struct Mixer {
+pub struct SubharmonicGenerator {
     timer: Timer,
-    buffer_size: usize,
-    channels: RefCell<Vec<MixerChannel>>,
+    /// index: 0 = fundamental tone, 1.. = undertones.
+    waves: RefCell<Vec<Wave>>,
+    /// The normalized amplitudes of the fundamental tone and the undertones.
+    amplitudes: Vec<SampleCalc>,
+    wave_buffer: RefCell<Vec<SampleCalc>>,
 }
 
-impl Mixer {
-    /// custom constructor
-    pub fn new(sample_rate: SampleCalc, buffer_size: usize) -> SoundResult<Mixer> {
-        Ok(Mixer {
+impl SubharmonicGenerator {
+    /// Custom constructor. `amplitudes[0]` is the amplitude of the fundamental tone,
+    /// `amplitudes[n]` is the amplitude of the undertone with frequency f/(n+1).
+    /// It normalizes the amplitudes, so the sum of them will be 1.0.
+    pub fn new(
+        sample_rate: SampleCalc,
+        buffer_size: usize,
+        amplitudes: &[SampleCalc],
+    ) -> SoundResult<SubharmonicGenerator> {
+        if amplitudes.len() > u16::MAX as usize {
+            return Err(Error::OvertoneCountInvalid);
+        }
+        let mut amplitude_sum: SampleCalc = 0.0;
+        for amplitude in amplitudes {
+            if *amplitude < 0.0 {
+                return Err(Error::AmplitudeInvalid);
+            }
+            amplitude_sum += *amplitude;
+        }
+        if amplitude_sum == 0.0 {
+            return Err(Error::AmplitudeInvalid);
+        }
+        let mut waves = Vec::with_capacity(amplitudes.len());
+        for undertone in 0..amplitudes.len() {
+            let mut wave = Wave::new(sample_rate, 0)?;
+            wave.set_interval(Interval::new(1, undertone as u16 + 1)?);
+            waves.push(wave);
+        }
+        Ok(SubharmonicGenerator {
             timer: Timer::new(sample_rate)?,
-            buffer_size,
-            channels: RefCell::new(Vec::new()),
+            waves: RefCell::new(waves),
+            amplitudes: amplitudes
+                .iter()
+                .map(|amplitude| amplitude / amplitude_sum)
+                .collect(),
+            wave_buffer: RefCell::new(vec![0.0; buffer_size]),
         })
     }
+}
 
-    /// Add a new channel to the mixer.
-    pub fn add(
+impl HasTimer for SubharmonicGenerator {
+    fn set_timing(&self, timing: TimingOption) -> SoundResult<()> {
+        self.timer.set_timing(timing)?;
+        self.restart();
+        Ok(())
+    }
+
+    fn get_timing(&self) -> TimingOption {
+        self.timer.get_timing()
+    }
+
+    fn restart(&self) {
+        self.timer.restart();
+    }
+
+    fn apply_parent_timing(&self, parent_timing: TimingOption) -> SoundResult<()> {
+        self.timer.apply_parent_timing(parent_timing)
+    }
+}
+
+impl SoundStructure for SubharmonicGenerator {
+    fn get(&self, base_frequency: &[SampleCalc], result: &mut [SampleCalc]) -> SoundResult<()> {
+        let mut wave_buffer_reserved = self.wave_buffer.borrow_mut();
+        if base_frequency.len() > wave_buffer_reserved.len() {
+            return Err(Error::BufferSize);
+        }
+        if result.len() != base_frequency.len() {
+            return Err(Error::BufferSize);
+        }
+        let wave_buffer = &mut wave_buffer_reserved[..result.len()];
+        for item in result.iter_mut() {
+            *item = 0.0;
+        }
+        let frequency_lowest = base_frequency
+            .iter()
+            .fold(TONE_FREQUENCY_MAX, |lowest, frequency| {
+                lowest.min(*frequency)
+            });
+        for ((undertone, wave), amplitude) in self
+            .waves
+            .borrow_mut()
+            .iter_mut()
+            .enumerate()
+            .zip(self.amplitudes.iter())
+        {
+            if frequency_lowest / (undertone as SampleCalc + 1.0) < TONE_FREQUENCY_MIN {
+                break;
+            }
+            wave.get(base_frequency, wave_buffer)?;
+            for (item, wave) in result.iter_mut().zip(wave_buffer.iter()) {
+                *item += *wave * *amplitude;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Channel structure used for mixing sound structures.
+#[derive(Clone)]
+struct MixerChannel {
+    /// The interval of the channel's frequency relative to the mixer's input frequency.
+    interval: Interval,
+    /// Sound structure.
+    sound: Rc<dyn SoundStructure>,
+    volume_relative: SampleCalc,
+    volume_normalized: SampleCalc,
+    muted: bool,
+    soloed: bool,
+    /// Stereo position in the [-1.0, 1.0] range (left to right).
+    pan: SampleCalc,
+    frequency_buffer: Vec<SampleCalc>,
+    wave_buffer: Vec<SampleCalc>,
+}
+
+impl MixerChannel {
+    /// A muted channel is never audible. If any channel is soloed, only the soloed ones are.
+    fn is_audible(&self, solo_active: bool) -> bool {
+        !self.muted && (self.soloed || !solo_active)
+    }
+}
+
+/// Mixes sound channels (structures).
+#[derive(Clone)]
+pub struct Mixer {
+    timer: Timer,
+    buffer_size: usize,
+    channels: RefCell<Vec<MixerChannel>>,
+}
+
+impl Mixer {
+    /// custom constructor
+    pub fn new(sample_rate: SampleCalc, buffer_size: usize) -> SoundResult<Mixer> {
+        Ok(Mixer {
+            timer: Timer::new(sample_rate)?,
+            buffer_size,
+            channels: RefCell::new(Vec::new()),
+        })
+    }
+
+    /// Add a new channel to the mixer.
+    pub fn add(
         &self,
         interval: Interval,
         sound: Rc<dyn SoundStructure>,
@@ -201,6 +841,9 @@ impl Mixer {
             sound,
             volume_relative: volume,
             volume_normalized: 0.0,
+            muted: false,
+            soloed: false,
+            pan: 0.0,
             frequency_buffer: vec![1.0; self.buffer_size],
             wave_buffer: vec![0.0; self.buffer_size],
         };
@@ -210,12 +853,15 @@ impl Mixer {
     }
 
     /// Generates the normalized volumes for the channels. Only normalizes if the sum of volumes
-    /// is greater than 1.0
+    /// of the audible channels is greater than 1.0
     fn normalize(&self) {
         let mut volume_sum: SampleCalc = 0.0;
         let mut channels = self.channels.borrow_mut();
+        let solo_active = channels.iter().any(|channel| channel.soloed);
         for channel in channels.iter() {
-            volume_sum += channel.volume_relative;
+            if channel.is_audible(solo_active) {
+                volume_sum += channel.volume_relative;
+            }
         }
         let volume_multiplier = if volume_sum < 1.0 {
             1.0
@@ -227,6 +873,19 @@ impl Mixer {
         }
     }
 
+    /// Removes the channel from the mixer. The following channels are shifted down by one.
+    pub fn remove(&self, channel: usize) -> SoundResult<()> {
+        {
+            let mut channels = self.channels.borrow_mut();
+            if channel >= channels.len() {
+                return Err(Error::ChannelInvalid);
+            }
+            let _ = channels.remove(channel);
+        }
+        self.normalize();
+        Ok(())
+    }
+
     /// Sets a new interval for the channel, relative to the base frequency of the mixer.
     pub fn set_interval(&self, channel: usize, interval: Interval) -> SoundResult<()> {
         if let Some(ch) = self.channels.borrow_mut().get_mut(channel) {
@@ -239,17 +898,82 @@ impl Mixer {
 
     /// Sets the relative volume of the channel.
     pub fn set_volume(&self, channel: usize, volume: SampleCalc) -> SoundResult<()> {
-        if let Some(ch) = self.channels.borrow_mut().get_mut(channel) {
-            if volume < 0.0 {
-                return Err(Error::AmplitudeInvalid);
+        if volume < 0.0 {
+            return Err(Error::AmplitudeInvalid);
+        }
+        self.channels
+            .borrow_mut()
+            .get_mut(channel)
+            .ok_or(Error::ChannelInvalid)?
+            .volume_relative = volume;
+        self.normalize();
+        Ok(())
+    }
+
+    /// Mutes or unmutes the channel. A muted channel is not rendered.
+    pub fn set_mute(&self, channel: usize, mute: bool) -> SoundResult<()> {
+        self.channels
+            .borrow_mut()
+            .get_mut(channel)
+            .ok_or(Error::ChannelInvalid)?
+            .muted = mute;
+        self.normalize();
+        Ok(())
+    }
+
+    /// Sets the stereo position of the channel, from -1.0 (left) to 1.0 (right).
+    pub fn set_pan(&self, channel: usize, pan: SampleCalc) -> SoundResult<()> {
+        if !(-1.0..=1.0).contains(&pan) {
+            return Err(Error::PanInvalid);
+        }
+        self.channels
+            .borrow_mut()
+            .get_mut(channel)
+            .ok_or(Error::ChannelInvalid)?
+            .pan = pan;
+        Ok(())
+    }
+
+    /// Renders each audible channel, and passes its samples to `mix`.
+    // TODO: render the channels in parallel (e.g. with rayon). It needs the sound structures to
+    // be `Send + Sync`, but they are shared with `Rc` and use `Cell`/`RefCell` for their state.
+    fn render_channels<F>(&self, base_frequency: &[SampleCalc], mut mix: F) -> SoundResult<()>
+    where
+        F: FnMut(&MixerChannel, &[SampleCalc]),
+    {
+        let buffer_size = base_frequency.len();
+        if buffer_size > self.buffer_size {
+            return Err(Error::BufferSize);
+        }
+        let mut channels = self.channels.borrow_mut();
+        let solo_active = channels.iter().any(|channel| channel.soloed);
+        for channel in channels.iter_mut() {
+            if !channel.is_audible(solo_active) {
+                continue;
             }
-            ch.volume_relative = volume;
-            self.normalize();
-        } else {
-            return Err(Error::ChannelInvalid);
+            channel
+                .interval
+                .transpose(base_frequency, &mut channel.frequency_buffer[..buffer_size])?;
+            channel.sound.get(
+                &channel.frequency_buffer[..buffer_size],
+                &mut channel.wave_buffer[..buffer_size],
+            )?;
+            mix(channel, &channel.wave_buffer[..buffer_size]);
         }
         Ok(())
     }
+
+    /// Turns solo on or off for the channel. If any channel is soloed, only the soloed (and not
+    /// muted) channels are rendered.
+    pub fn set_solo(&self, channel: usize, solo: bool) -> SoundResult<()> {
+        self.channels
+            .borrow_mut()
+            .get_mut(channel)
+            .ok_or(Error::ChannelInvalid)?
+            .soloed = solo;
+        self.normalize();
+        Ok(())
+    }
 }
 
 impl HasTimer for Mixer {
@@ -290,34 +1014,56 @@ impl SoundStructure for Mixer {
         for item in result.iter_mut() {
             *item = 0.0;
         }
-        for channel in self.channels.borrow_mut().iter_mut() {
-            channel
-                .interval
-                .transpose(base_frequency, &mut channel.frequency_buffer)?;
-            channel
-                .sound
-                .get(&channel.frequency_buffer, &mut channel.wave_buffer)?;
-            for (item, wave) in result.iter_mut().zip(channel.wave_buffer.iter()) {
+        self.render_channels(base_frequency, |channel, wave_buffer| {
+            for (item, wave) in result.iter_mut().zip(wave_buffer) {
                 *item += *wave * channel.volume_normalized;
             }
+        })
+    }
+}
+
+/// The channels are positioned using an equal-power pan law.
+impl StereoStructure for Mixer {
+    fn get_stereo(
+        &self,
+        base_frequency: &[SampleCalc],
+        left: &mut [SampleCalc],
+        right: &mut [SampleCalc],
+    ) -> SoundResult<()> {
+        if base_frequency.len() != left.len() || base_frequency.len() != right.len() {
+            return Err(Error::BufferSize);
         }
-        Ok(())
+        for (item_left, item_right) in left.iter_mut().zip(right.iter_mut()) {
+            *item_left = 0.0;
+            *item_right = 0.0;
+        }
+        self.render_channels(base_frequency, |channel, wave_buffer| {
+            let angle = (channel.pan + 1.0) * PI2 / 8.0;
+            let volume_left = channel.volume_normalized * angle.cos();
+            let volume_right = channel.volume_normalized * angle.sin();
+            for ((item_left, item_right), wave) in
+                left.iter_mut().zip(right.iter_mut()).zip(wave_buffer)
+            {
+                *item_left += *wave * volume_left;
+                *item_right += *wave * volume_right;
+            }
+        })
     }
 }
 
 // TODO: `FadeOutLinear` and `FadeInLinear` replaced by `FadeLinear`, ProgressOption shall be used
 // too here.
 // https://en.wikipedia.org/wiki/Fade_(audio_engineering)#Crossfading
-/// Mixes two sound structures. While one fades out, another fades in.
-#[doc(hidden)]
-#[allow(dead_code)]
+/// Mixes two sound structures. While one fades out, another fades in. After the crossfade,
+/// only the faded in sound can be heard.
 pub struct Crossfader {
+    timer: Timer,
     duration: SampleCalc,
     sound_fade_out: Rc<dyn SoundStructure>,
     sound_fade_in: Rc<dyn SoundStructure>,
     interval: Interval,
-    amplitude_fade_out: FadeLinear,
-    amplitude_fade_in: FadeLinear,
+    amplitude_fade_out: Rc<dyn AmplitudeJoinable>,
+    amplitude_fade_in: Rc<dyn AmplitudeJoinable>,
     frequency_buffer_in: RefCell<Vec<SampleCalc>>, // only used when interval is not unison
     wave_fade_out_buffer: RefCell<Vec<SampleCalc>>,
     wave_fade_in_buffer: RefCell<Vec<SampleCalc>>,
@@ -335,7 +1081,50 @@ impl Crossfader {
         let amplitude_fade_out = FadeLinear::new_with_time(sample_rate, duration, 0.0)?;
         amplitude_fade_out.set_amplitude_start(1.0)?;
         let amplitude_fade_in = FadeLinear::new_with_time(sample_rate, duration, 1.0)?;
+        Crossfader::new_with_fades(
+            sample_rate,
+            buffer_size,
+            duration,
+            sound_fade_out,
+            sound_fade_in,
+            Rc::new(amplitude_fade_out),
+            Rc::new(amplitude_fade_in),
+        )
+    }
+
+    /// Constructor with exponential fades, which sound smoother than the linear ones.
+    pub fn new_exponential(
+        sample_rate: SampleCalc,
+        buffer_size: usize,
+        duration: SampleCalc,
+        sound_fade_out: Rc<dyn SoundStructure>,
+        sound_fade_in: Rc<dyn SoundStructure>,
+    ) -> SoundResult<Crossfader> {
+        let amplitude_fade_out = FadeExponential::new_with_time(sample_rate, duration, 0.0)?;
+        amplitude_fade_out.set_amplitude_start(1.0)?;
+        let amplitude_fade_in = FadeExponential::new_with_time(sample_rate, duration, 1.0)?;
+        Crossfader::new_with_fades(
+            sample_rate,
+            buffer_size,
+            duration,
+            sound_fade_out,
+            sound_fade_in,
+            Rc::new(amplitude_fade_out),
+            Rc::new(amplitude_fade_in),
+        )
+    }
+
+    fn new_with_fades(
+        sample_rate: SampleCalc,
+        buffer_size: usize,
+        duration: SampleCalc,
+        sound_fade_out: Rc<dyn SoundStructure>,
+        sound_fade_in: Rc<dyn SoundStructure>,
+        amplitude_fade_out: Rc<dyn AmplitudeJoinable>,
+        amplitude_fade_in: Rc<dyn AmplitudeJoinable>,
+    ) -> SoundResult<Crossfader> {
         Ok(Crossfader {
+            timer: Timer::new(sample_rate)?,
             duration,
             interval: Interval::new(1, 1)?,
             sound_fade_out,
@@ -360,39 +1149,510 @@ impl Crossfader {
     }
 }
 
-// impl SoundStructure for Crossfader {
-// fn get(&self,
-// time_start: SampleCalc,
-// base_frequency: &[SampleCalc],
-// result: &mut [SampleCalc])
-// -> SoundResult<()> {
-// if base_frequency.len() != result.len() {
-// return Err(Error::BufferSize);
-// }
-// try!(self.sound_fade_out.get(time_start,
-// base_frequency,
-// &mut self.wave_fade_out_buffer.borrow_mut()));
-// if self.interval.is_unison() {
-// try!(self.sound_fade_in.get(time_start,
-// base_frequency,
-// &mut self.wave_fade_in_buffer.borrow_mut()));
-//
-// } else {
-// try!(self.interval
-// .transpose(base_frequency, &mut self.frequency_buffer_in.borrow_mut()));
-// try!(self.sound_fade_in.get(time_start,
-// &self.frequency_buffer_in.borrow(),
-// &mut self.wave_fade_in_buffer.borrow_mut()));
-// }
-// try!(self.amplitude_fade_out
-// .apply(time_start, &mut self.wave_fade_out_buffer.borrow_mut()));
-// try!(self.amplitude_fade_in
-// .apply(time_start, &mut self.wave_fade_in_buffer.borrow_mut()));
-// for ((item, sample_out), sample_in) in result.iter_mut()
-// .zip(self.wave_fade_out_buffer.borrow().iter())
-// .zip(self.wave_fade_in_buffer.borrow().iter()) {
-// item = *sample_out + *sample_in;
-// }
-// Ok(())
-// }
-// }
+impl HasTimer for Crossfader {
+    fn set_timing(&self, timing: TimingOption) -> SoundResult<()> {
+        self.timer.set_timing(timing)?;
+        self.restart();
+        Ok(())
+    }
+
+    fn get_timing(&self) -> TimingOption {
+        self.timer.get_timing()
+    }
+
+    fn restart(&self) {
+        self.timer.restart();
+        self.sound_fade_out.restart();
+        self.sound_fade_in.restart();
+        self.amplitude_fade_out.restart();
+        self.amplitude_fade_in.restart();
+    }
+
+    fn apply_parent_timing(&self, parent_timing: TimingOption) -> SoundResult<()> {
+        self.timer.apply_parent_timing(parent_timing)
+    }
+}
+
+impl SoundStructure for Crossfader {
+    fn get(&self, base_frequency: &[SampleCalc], result: &mut [SampleCalc]) -> SoundResult<()> {
+        if base_frequency.len() != result.len() {
+            return Err(Error::BufferSize);
+        }
+        let buffer_size = result.len();
+        let mut wave_fade_out_reserved = self.wave_fade_out_buffer.borrow_mut();
+        let mut wave_fade_in_reserved = self.wave_fade_in_buffer.borrow_mut();
+        if buffer_size > wave_fade_out_reserved.len() {
+            return Err(Error::BufferSize);
+        }
+        let wave_fade_out = &mut wave_fade_out_reserved[..buffer_size];
+        let wave_fade_in = &mut wave_fade_in_reserved[..buffer_size];
+        self.sound_fade_out.get(base_frequency, wave_fade_out)?;
+        if self.interval.is_unison() {
+            self.sound_fade_in.get(base_frequency, wave_fade_in)?;
+        } else {
+            let mut frequency_buffer_in = self.frequency_buffer_in.borrow_mut();
+            self.interval
+                .transpose(base_frequency, &mut frequency_buffer_in[..buffer_size])?;
+            self.sound_fade_in
+                .get(&frequency_buffer_in[..buffer_size], wave_fade_in)?;
+        }
+        // the faded out sound stays silent after the crossfade
+        match self.amplitude_fade_out.apply(wave_fade_out) {
+            Ok(()) => {}
+            Err(Error::ItemsCompleted(completed)) => {
+                for item in wave_fade_out.iter_mut().skip(completed) {
+                    *item = 0.0;
+                }
+            }
+            Err(e) => return Err(e),
+        }
+        // the faded in sound keeps its full amplitude after the crossfade
+        match self.amplitude_fade_in.apply(wave_fade_in) {
+            Ok(()) | Err(Error::ItemsCompleted(_)) => {}
+            Err(e) => return Err(e),
+        }
+        for ((item, sample_out), sample_in) in result
+            .iter_mut()
+            .zip(wave_fade_out.iter())
+            .zip(wave_fade_in.iter())
+        {
+            *item = *sample_out + *sample_in;
+        }
+        self.timer.jump_by_time(buffer_size)
+    }
+}
+
+/// [Ring modulation](https://en.wikipedia.org/wiki/Ring_modulation) of two sound structures:
+/// their outputs are multiplied. The result contains the sum and difference frequencies of the
+/// components, so it gives inharmonic, metallic tones even from harmonic timbres.
+pub struct RingModulator {
+    timer: Timer,
+    carrier: Rc<dyn SoundStructure>,
+    modulator: Rc<dyn SoundStructure>,
+    /// The interval of the modulator, relative to the base frequency.
+    interval: Interval,
+    frequency_buffer: RefCell<Vec<SampleCalc>>,
+    modulator_buffer: RefCell<Vec<SampleCalc>>,
+}
+
+impl RingModulator {
+    /// custom constructor
+    /// The carrier plays at the base frequency, the modulator is transposed by the `interval`.
+    pub fn new(
+        sample_rate: SampleCalc,
+        buffer_size: usize,
+        carrier: Rc<dyn SoundStructure>,
+        modulator: Rc<dyn SoundStructure>,
+        interval: Interval,
+    ) -> SoundResult<RingModulator> {
+        Ok(RingModulator {
+            timer: Timer::new(sample_rate)?,
+            carrier,
+            modulator,
+            interval,
+            frequency_buffer: RefCell::new(vec![0.0; buffer_size]),
+            modulator_buffer: RefCell::new(vec![0.0; buffer_size]),
+        })
+    }
+
+    /// Sets the interval of the modulator (relative to the base frequency).
+    pub fn set_interval(&mut self, interval: Interval) -> &mut RingModulator {
+        self.interval = interval;
+        self
+    }
+}
+
+impl HasTimer for RingModulator {
+    fn set_timing(&self, timing: TimingOption) -> SoundResult<()> {
+        self.timer.set_timing(timing)?;
+        self.restart();
+        Ok(())
+    }
+
+    fn get_timing(&self) -> TimingOption {
+        self.timer.get_timing()
+    }
+
+    fn restart(&self) {
+        self.timer.restart();
+        self.carrier.restart();
+        self.modulator.restart();
+    }
+
+    fn apply_parent_timing(&self, parent_timing: TimingOption) -> SoundResult<()> {
+        self.timer.apply_parent_timing(parent_timing)?;
+        self.carrier.apply_parent_timing(self.timer.get_timing())?;
+        self.modulator.apply_parent_timing(self.timer.get_timing())
+    }
+}
+
+impl SoundStructure for RingModulator {
+    fn get(&self, base_frequency: &[SampleCalc], result: &mut [SampleCalc]) -> SoundResult<()> {
+        if base_frequency.len() != result.len() {
+            return Err(Error::BufferSize);
+        }
+        let buffer_size = result.len();
+        let mut modulator_reserved = self.modulator_buffer.borrow_mut();
+        if buffer_size > modulator_reserved.len() {
+            return Err(Error::BufferSize);
+        }
+        let modulator_wave = &mut modulator_reserved[..buffer_size];
+        self.carrier.get(base_frequency, result)?;
+        if self.interval.is_unison() {
+            self.modulator.get(base_frequency, modulator_wave)?;
+        } else {
+            let mut frequency_buffer = self.frequency_buffer.borrow_mut();
+            self.interval
+                .transpose(base_frequency, &mut frequency_buffer[..buffer_size])?;
+            self.modulator
+                .get(&frequency_buffer[..buffer_size], modulator_wave)?;
+        }
+        for (item, modulator_sample) in result.iter_mut().zip(modulator_wave.iter()) {
+            *item *= *modulator_sample;
+        }
+        self.timer.jump_by_time(buffer_size)
+    }
+}
+
+/// Generator of [binaural beats](https://en.wikipedia.org/wiki/Beat_(acoustics)#Binaural_beats):
+/// the same sound is rendered for the left and the right ear with slightly different
+/// frequencies, the difference is perceived as a beat. The left ear gets the base frequency,
+/// the right one gets the base frequency + `beat_frequency`.
+pub struct Binaural {
+    timer: Timer,
+    /// The sound for the left ear.
+    inner_left: Rc<dyn SoundStructure>,
+    /// The same sound for the right ear (a separate instance, as it has its own state).
+    inner_right: Rc<dyn SoundStructure>,
+    beat_frequency: SampleCalc,
+    frequency_buffer: RefCell<Vec<SampleCalc>>,
+}
+
+impl Binaural {
+    /// custom constructor
+    /// `inner_left` and `inner_right` shall be two instances of the same sound.
+    pub fn new(
+        sample_rate: SampleCalc,
+        buffer_size: usize,
+        inner_left: Rc<dyn SoundStructure>,
+        inner_right: Rc<dyn SoundStructure>,
+        beat_frequency: SampleCalc,
+    ) -> SoundResult<Binaural> {
+        Binaural::check_beat_frequency(beat_frequency)?;
+        Ok(Binaural {
+            timer: Timer::new(sample_rate)?,
+            inner_left,
+            inner_right,
+            beat_frequency,
+            frequency_buffer: RefCell::new(vec![0.0; buffer_size]),
+        })
+    }
+
+    /// Sets the frequency difference between the ears.
+    pub fn set_beat_frequency(&mut self, beat_frequency: SampleCalc) -> SoundResult<()> {
+        Binaural::check_beat_frequency(beat_frequency)?;
+        self.beat_frequency = beat_frequency;
+        Ok(())
+    }
+
+    /// Returns the frequency difference between the ears.
+    pub fn get_beat_frequency(&self) -> SampleCalc {
+        self.beat_frequency
+    }
+
+    fn check_beat_frequency(beat_frequency: SampleCalc) -> SoundResult<()> {
+        if beat_frequency < 0.0 || !beat_frequency.is_finite() {
+            return Err(Error::FrequencyInvalid);
+        }
+        Ok(())
+    }
+}
+
+impl HasTimer for Binaural {
+    fn set_timing(&self, timing: TimingOption) -> SoundResult<()> {
+        self.timer.set_timing(timing)?;
+        self.restart();
+        Ok(())
+    }
+
+    fn get_timing(&self) -> TimingOption {
+        self.timer.get_timing()
+    }
+
+    fn restart(&self) {
+        self.timer.restart();
+        self.inner_left.restart();
+        self.inner_right.restart();
+    }
+
+    fn apply_parent_timing(&self, parent_timing: TimingOption) -> SoundResult<()> {
+        self.timer.apply_parent_timing(parent_timing)?;
+        self.inner_left
+            .apply_parent_timing(self.timer.get_timing())?;
+        self.inner_right
+            .apply_parent_timing(self.timer.get_timing())
+    }
+}
+
+impl StereoStructure for Binaural {
+    fn get_stereo(
+        &self,
+        base_frequency: &[SampleCalc],
+        left: &mut [SampleCalc],
+        right: &mut [SampleCalc],
+    ) -> SoundResult<()> {
+        if base_frequency.len() != left.len() || base_frequency.len() != right.len() {
+            return Err(Error::BufferSize);
+        }
+        let buffer_size = base_frequency.len();
+        let mut frequency_reserved = self.frequency_buffer.borrow_mut();
+        if buffer_size > frequency_reserved.len() {
+            return Err(Error::BufferSize);
+        }
+        let frequency_right = &mut frequency_reserved[..buffer_size];
+        for (item, frequency) in frequency_right.iter_mut().zip(base_frequency) {
+            *item = *frequency + self.beat_frequency;
+        }
+        self.inner_left.get(base_frequency, left)?;
+        self.inner_right.get(frequency_right, right)?;
+        self.timer.jump_by_time(buffer_size)
+    }
+}
+
+/// Adapter which turns a mono sound structure into a centered stereo one: both channels get
+/// the same signal (as the backends do with mono sound).
+pub struct MonoToStereo {
+    inner: Rc<dyn SoundStructure>,
+}
+
+impl MonoToStereo {
+    /// custom constructor
+    pub fn new(inner: Rc<dyn SoundStructure>) -> MonoToStereo {
+        MonoToStereo { inner }
+    }
+}
+
+/// The adapter has no timing on its own, it uses the timing of the inner sound structure.
+impl HasTimer for MonoToStereo {
+    fn set_timing(&self, timing: TimingOption) -> SoundResult<()> {
+        self.inner.set_timing(timing)
+    }
+
+    fn get_timing(&self) -> TimingOption {
+        self.inner.get_timing()
+    }
+
+    fn restart(&self) {
+        self.inner.restart();
+    }
+
+    fn apply_parent_timing(&self, parent_timing: TimingOption) -> SoundResult<()> {
+        self.inner.apply_parent_timing(parent_timing)
+    }
+}
+
+impl StereoStructure for MonoToStereo {
+    fn get_stereo(
+        &self,
+        base_frequency: &[SampleCalc],
+        left: &mut [SampleCalc],
+        right: &mut [SampleCalc],
+    ) -> SoundResult<()> {
+        if base_frequency.len() != left.len() || base_frequency.len() != right.len() {
+            return Err(Error::BufferSize);
+        }
+        let inner_result = self.inner.get(base_frequency, left);
+        right.copy_from_slice(left);
+        inner_result
+    }
+}
+
+/// Convenience methods for all the sound structures.
+pub trait SoundStructureExt: SoundStructure {
+    /// Renders `seconds` long sound at the constant base `frequency`. The `sample_rate` must be
+    /// the same as the sound structure's. The samples are calculated in `BUFFER_SIZE_DEFAULT`
+    /// sized chunks. If the sound ends earlier (`ItemsCompleted`), the result is shorter.
+    fn render(
+        &self,
+        frequency: SampleCalc,
+        sample_rate: SampleCalc,
+        seconds: SampleCalc,
+    ) -> SoundResult<Vec<SampleCalc>> {
+        if sample_rate <= 0.0 || !sample_rate.is_finite() {
+            return Err(Error::SampleRateInvalid);
+        }
+        if seconds < 0.0 || !seconds.is_finite() {
+            return Err(Error::DurationInvalid);
+        }
+        let sample_count = (seconds * sample_rate).round() as usize;
+        let frequency_buffer = vec![frequency; BUFFER_SIZE_DEFAULT];
+        let mut samples = vec![0.0; sample_count];
+        let mut position = 0;
+        while position < sample_count {
+            let buffer_size = (sample_count - position).min(BUFFER_SIZE_DEFAULT);
+            let chunk = &mut samples[position..position + buffer_size];
+            match self.get(&frequency_buffer[..buffer_size], chunk) {
+                Ok(()) => position += buffer_size,
+                Err(Error::ItemsCompleted(completed)) => {
+                    samples.truncate(position + completed);
+                    break;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(samples)
+    }
+}
+
+impl<T: SoundStructure + ?Sized> SoundStructureExt for T {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sound::test_utils::*;
+
+    const SAMPLE_RATE: SampleCalc = 48000.0;
+
+    #[test]
+    fn binaural_channels_differ_by_the_beat_frequency() {
+        let binaural = Binaural::new(
+            SAMPLE_RATE,
+            BUFFER_SIZE_DEFAULT,
+            sine(SAMPLE_RATE),
+            sine(SAMPLE_RATE),
+            10.0,
+        )
+        .unwrap();
+        let frequency = [440.0; 480];
+        let (mut left, mut right) = (Vec::new(), Vec::new());
+        let (mut left_buffer, mut right_buffer) = ([0.0; 480], [0.0; 480]);
+        for _ in 0..10 {
+            binaural
+                .get_stereo(&frequency, &mut left_buffer, &mut right_buffer)
+                .unwrap();
+            left.extend_from_slice(&left_buffer);
+            right.extend_from_slice(&right_buffer);
+        }
+        let left_frequency = dominant_frequency(&left, SAMPLE_RATE);
+        let right_frequency = dominant_frequency(&right, SAMPLE_RATE);
+        assert!((left_frequency - 440.0).abs() < 0.5, "{}", left_frequency);
+        assert!(
+            (right_frequency - left_frequency - 10.0).abs() < 0.5,
+            "{} {}",
+            left_frequency,
+            right_frequency
+        );
+    }
+
+    /// The amplitudes of the components of the samples, by frequency bins of
+    /// `SAMPLE_RATE / samples.len()` width.
+    fn amplitudes(samples: &[SampleCalc]) -> Vec<SampleCalc> {
+        let sample_count = samples.len() as SampleCalc;
+        spectrum(samples)
+            .iter()
+            .map(|magnitude| 2.0 * magnitude / sample_count)
+            .collect()
+    }
+
+    #[test]
+    fn subharmonic_generator_undertones() {
+        let generator =
+            SubharmonicGenerator::new(SAMPLE_RATE, BUFFER_SIZE_DEFAULT, &[1.0, 1.0, 1.0]).unwrap();
+        // 0.1 s contains whole periods of 480 Hz, 240 Hz and 160 Hz, the bins are 10 Hz wide
+        let samples = generator.render(480.0, SAMPLE_RATE, 0.1).unwrap();
+        let amplitudes = amplitudes(&samples);
+        for bin in [48, 24, 16].iter() {
+            assert!(
+                (amplitudes[*bin] - 1.0 / 3.0).abs() < 0.01,
+                "{}: {}",
+                bin,
+                amplitudes[*bin]
+            );
+        }
+        assert!(amplitudes[12] < 0.01);
+    }
+
+    /// The power of the aliased components of a 10 kHz sawtooth: everything except its
+    /// harmonics below the Nyquist frequency (10 kHz and 20 kHz).
+    fn sawtooth_alias_power(band_limited: bool) -> SampleCalc {
+        let mut sawtooth = Sawtooth::new(SAMPLE_RATE, 0).unwrap();
+        sawtooth.set_band_limited(band_limited);
+        let frequency = vec![10000.0; 4800];
+        let mut samples = vec![0.0; 4800];
+        sawtooth.get(&frequency, &mut samples).unwrap();
+        let power = samples.iter().map(|item| item * item).sum::<SampleCalc>() / 4800.0;
+        // the bins are 10 Hz wide
+        let amplitudes = amplitudes(&samples);
+        let harmonic_power: SampleCalc = [1000, 2000]
+            .iter()
+            .map(|bin| amplitudes[*bin].powi(2) / 2.0)
+            .sum();
+        power - harmonic_power
+    }
+
+    #[test]
+    fn poly_blep_sawtooth_reduces_aliasing() {
+        let naive = sawtooth_alias_power(false);
+        let band_limited = sawtooth_alias_power(true);
+        assert!(band_limited < 0.5 * naive, "{} {}", band_limited, naive);
+    }
+
+    #[test]
+    fn timbre_leaves_out_the_stretched_overtones_above_the_nyquist_frequency() {
+        let amplitude = AmplitudeConstOvertones::new(SAMPLE_RATE, 1, &[1.0, 1.0]).unwrap();
+        let mut timbre =
+            Timbre::new(SAMPLE_RATE, BUFFER_SIZE_DEFAULT, Rc::new(amplitude), 2).unwrap();
+        // the harmonic overtone would be 20 kHz, the stretched one is above 24 kHz
+        timbre.set_inharmonicity(0.5).unwrap();
+        let frequency = [10000.0; 480];
+        let mut samples = vec![0.0; 4800];
+        for chunk in samples.chunks_mut(480) {
+            timbre.get(&frequency, chunk).unwrap();
+        }
+        let power = samples.iter().map(|item| item * item).sum::<SampleCalc>() / 4800.0;
+        // the bins are 10 Hz wide, only the fundamental tone is left
+        let fundamental = amplitudes(&samples)[1000];
+        assert!((fundamental - 0.5).abs() < 0.01, "{}", fundamental);
+        assert!(
+            (power - fundamental.powi(2) / 2.0).abs() < 1e-4,
+            "{}",
+            power
+        );
+    }
+
+    /// Measures the frequency of a sine wave from its upward zero crossings.
+    fn measure_frequency(samples: &[SampleCalc]) -> SampleCalc {
+        let crossings: Vec<SampleCalc> = samples
+            .windows(2)
+            .enumerate()
+            .filter(|(_, pair)| pair[0] < 0.0 && pair[1] >= 0.0)
+            .map(|(index, pair)| index as SampleCalc + pair[0] / (pair[0] - pair[1]))
+            .collect();
+        let period_count = (crossings.len() - 1) as SampleCalc;
+        period_count * SAMPLE_RATE / (crossings[crossings.len() - 1] - crossings[0])
+    }
+
+    #[test]
+    fn inharmonic_overtone_frequencies() {
+        let inharmonicity: SampleCalc = 0.01;
+        let frequency = [100.0; BUFFER_SIZE_DEFAULT];
+        for overtone in 0..6 {
+            let mut wave = Wave::new(SAMPLE_RATE, overtone).unwrap();
+            wave.set_inharmonicity(inharmonicity).unwrap();
+            let mut samples = vec![0.0; 94 * BUFFER_SIZE_DEFAULT];
+            for chunk in samples.chunks_mut(BUFFER_SIZE_DEFAULT) {
+                wave.get(&frequency, chunk).unwrap();
+            }
+            let n = overtone as SampleCalc;
+            let expected = 100.0 * (n + 1.0) * (1.0 + inharmonicity * n * n).sqrt();
+            let measured = measure_frequency(&samples);
+            assert!(
+                (measured / expected - 1.0).abs() < 1e-4,
+                "{}: {} {}",
+                overtone,
+                measured,
+                expected
+            );
+        }
+    }
+}